@@ -47,12 +47,50 @@ enum Commands {
         /// Show timing breakdown
         #[arg(short, long)]
         timing: bool,
+        /// Save every frame, its timestamp and quality verdict, and the config to a session archive
+        #[arg(long, value_name = "SESSION.tar")]
+        record: Option<std::path::PathBuf>,
+        /// Rerun a recorded session instead of using the camera
+        #[arg(long, value_name = "SESSION.tar", conflicts_with = "record")]
+        replay: Option<std::path::PathBuf>,
     },
     /// Capture a snapshot from the camera
     Snapshot {
         /// Output file path
         output: String,
     },
+    /// Find the best camera, tune thresholds and write the config
+    Setup {
+        /// Config file to write
+        #[arg(short, long, default_value = "/etc/nihao/nihao.toml")]
+        output: std::path::PathBuf,
+        /// Frames to capture from each camera
+        #[arg(short, long, default_value_t = 15)]
+        frames: usize,
+        /// Accept the top-ranked camera and overwrite without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// List capture devices with their formats and IR likelihood
+    Devices {
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+        /// Skip streaming frames from each device (faster, name and formats only)
+        #[arg(long)]
+        no_sample: bool,
+    },
+    /// List the camera's V4L2 controls and their ranges
+    Controls {
+        /// Device to inspect (defaults to the configured camera)
+        device: Option<String>,
+    },
+    /// Measure the RGB→IR mapping for hybrid mode
+    CalibrateHybrid {
+        /// Frame pairs with a face in both cameras to collect
+        #[arg(short, long, default_value_t = 10)]
+        frames: usize,
+    },
     /// Show configuration
     Config {
         /// Validate configuration
@@ -88,8 +126,12 @@ fn main() -> anyhow::Result<()> {
         Commands::Add { username, label, debug } => cmd_add(username, label, debug),
         Commands::Remove { username, face_id } => cmd_remove(username, face_id),
         Commands::List { username } => cmd_list(username),
-        Commands::Test { username, timing } => cmd_test(username, timing),
+        Commands::Test { username, timing, record, replay } => cmd_test(username, timing, record, replay),
         Commands::Snapshot { output } => cmd_snapshot(output),
+        Commands::Setup { output, frames, yes } => cmd_setup(&output, frames, yes),
+        Commands::Devices { json, no_sample } => cmd_devices(json, !no_sample),
+        Commands::Controls { device } => cmd_controls(device),
+        Commands::CalibrateHybrid { frames } => cmd_calibrate_hybrid(frames),
         Commands::Config { validate } => cmd_config(validate),
         Commands::StorePassword { username } => cmd_store_password(username),
         Commands::RemovePassword { username } => cmd_remove_password(username),
@@ -152,7 +194,7 @@ fn cmd_list(username: String) -> anyhow::Result<()> {
 
     println!("Enrolled faces for {}:", username);
     println!();
-    println!("{:<15} {:<20} {}", "Face ID", "Label", "Enrolled At");
+    println!("{:<15} {:<20} Enrolled At", "Face ID", "Label");
     println!("{}", "-".repeat(60));
 
     for face in faces {
@@ -164,19 +206,51 @@ fn cmd_list(username: String) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn cmd_test(username: String, show_timing: bool) -> anyhow::Result<()> {
+fn cmd_test(
+    username: String,
+    show_timing: bool,
+    record: Option<std::path::PathBuf>,
+    replay: Option<std::path::PathBuf>,
+) -> anyhow::Result<()> {
     println!("Testing face recognition for user: {}", username);
-    println!("\nLook at the camera...");
 
-    let config = Config::load()?;
-    let mut recognizer = FaceRecognizer::new(config.clone())?;
+    let (config, mut recognizer) = match &replay {
+        Some(path) => {
+            let mut session = nihao_core::session::Session::open(path)?;
+            println!(
+                "\nReplaying {} frames recorded for {} (outcome: {})",
+                session.frames.len(),
+                session.username,
+                session.outcome
+            );
+
+            // Models live elsewhere on this machine; the files must be the same
+            let local = Config::load()?;
+            if !session.config.detection.model_path.exists() {
+                session.config.detection.model_path = local.detection.model_path;
+            }
+            if !session.config.embedding.model_path.exists() {
+                session.config.embedding.model_path = local.embedding.model_path;
+            }
+            (session.config.clone(), FaceRecognizer::replay(session)?)
+        }
+        None => {
+            println!("\nLook at the camera...");
+            let config = Config::load()?;
+            (config.clone(), FaceRecognizer::new(config)?)
+        }
+    };
+
+    if let Some(path) = &record {
+        recognizer.record_session(path);
+    }
 
     let start = Instant::now();
-    let result = recognizer.authenticate(&username)?;
+    let outcome = recognizer.authenticate(&username)?;
     let duration = start.elapsed();
 
     println!();
-    if result {
+    if outcome.matched {
         println!("✅ Authentication successful!");
 
         // Show debug screenshot location
@@ -184,12 +258,29 @@ fn cmd_test(username: String, show_timing: bool) -> anyhow::Result<()> {
             println!("📷 Debug screenshot saved to: {}/auth_{}*.jpg",
                      config.debug.output_dir.display(), username);
         }
+    } else if outcome.multiple_faces {
+        println!("❌ Authentication refused: {} faces in frame", outcome.faces_seen);
     } else {
         println!("❌ Authentication failed: No match found");
     }
+    if outcome.faces_seen > 1 && !outcome.multiple_faces {
+        println!("👥 Up to {} faces were in frame", outcome.faces_seen);
+    }
+
+    if let Some(path) = &record {
+        println!("📦 Session recorded to: {}", path.display());
+    }
 
     if show_timing {
         println!("\nTiming:");
+        if let Some(settle) = recognizer.exposure_settle() {
+            println!(
+                "Exposure settle: {:.2}ms ({} frames{})",
+                settle.elapsed.as_secs_f64() * 1000.0,
+                settle.frames,
+                if settle.converged { "" } else { ", not converged" }
+            );
+        }
         println!("Total: {:.2}ms", duration.as_secs_f64() * 1000.0);
     } else {
         println!("Total time: {:.2}ms", duration.as_secs_f64() * 1000.0);
@@ -203,7 +294,7 @@ fn cmd_snapshot(output: String) -> anyhow::Result<()> {
     println!("Capturing snapshot to: {}", output);
 
     let config = Config::load()?;
-    let mut camera = nihao_core::capture::Camera::new(&config.camera)?;
+    let mut camera = nihao_core::source::open(&config.camera)?;
 
     let frame = camera.capture_frame(false)?;  // No quality checks for snapshot
     frame.save(&output)?;
@@ -214,6 +305,261 @@ fn cmd_snapshot(output: String) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn cmd_setup(output: &std::path::Path, frames: usize, yes: bool) -> anyhow::Result<()> {
+    use nihao_core::setup;
+    use std::io::Write;
+
+    let prompt = |question: &str| -> anyhow::Result<String> {
+        print!("{}", question);
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        Ok(answer.trim().to_string())
+    };
+
+    // Start from the config being replaced so earlier tuning survives. One
+    // that fails to load would be lost to defaults, so ask even with --yes
+    let mut confirmed = yes;
+    let mut config = if output.exists() {
+        match Config::load_from_path(output).and_then(|config| config.validate().map(|_| config)) {
+            Ok(config) => config,
+            Err(e) => {
+                println!("⚠️  {} could not be loaded: {}", output.display(), e);
+                let answer = prompt("Replace it with a config built from the defaults? [y/N]: ")?;
+                if !answer.eq_ignore_ascii_case("y") {
+                    anyhow::bail!("Fix or move {} and run setup again", output.display());
+                }
+                confirmed = true;
+                Config::default()
+            }
+        }
+    } else {
+        Config::load().unwrap_or_else(|e| {
+            println!("⚠️  Current config could not be loaded ({}); starting from the defaults", e);
+            Config::default()
+        })
+    };
+
+    println!("[1/4] Checking models...");
+    if let Err(missing) = setup::locate_models(&mut config) {
+        for path in &missing {
+            println!("  ✗ {} not found", path.display());
+        }
+        anyhow::bail!("Models missing; run scripts/download_models.sh or install.sh first");
+    }
+    println!("  ✓ {}", config.detection.model_path.display());
+    println!("  ✓ {}", config.embedding.model_path.display());
+
+    println!("[2/4] Looking for cameras...");
+    let devices = nihao_core::device::list_capture_devices()?;
+    if devices.is_empty() {
+        anyhow::bail!("No video capture devices found");
+    }
+    for info in &devices {
+        println!(
+            "  {} - {}{}",
+            info.path.display(),
+            info.card,
+            if info.looks_ir() {
+                " (IR)"
+            } else {
+                ""
+            }
+        );
+    }
+
+    println!("[3/4] Look at the camera from your usual distance...");
+    let runtime = nihao_core::runtime::OnnxRuntime::new()?;
+    let mut detector = nihao_core::detect::load(&config.detection, &runtime, &config.runtime)?;
+
+    let mut candidates: Vec<setup::Candidate> = devices
+        .iter()
+        .map(|info| {
+            println!("  Trying {}...", info.path.display());
+            setup::trial(info, &config.camera, detector.as_mut(), frames)
+        })
+        .collect();
+    setup::rank(&mut candidates);
+
+    println!();
+    for (i, c) in candidates.iter().enumerate() {
+        match &c.error {
+            Some(error) if c.frames == 0 => println!("  {}. {} - unusable: {}", i + 1, c.path.display(), error),
+            _ => println!(
+                "  {}. {} - face in {}/{} frames, confidence {:.2}{}",
+                i + 1,
+                c.path.display(),
+                c.faces,
+                c.frames,
+                c.mean_confidence,
+                if c.looks_ir { ", IR" } else { "" }
+            ),
+        }
+    }
+    println!();
+
+    let chosen = if yes {
+        0
+    } else {
+        let answer = prompt(&format!("Use which camera? [1-{}, default 1]: ", candidates.len()))?;
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=candidates.len()).contains(&n) => n - 1,
+            _ if answer.is_empty() => 0,
+            _ => anyhow::bail!("Invalid choice: {:?}", answer),
+        }
+    };
+    let candidate = &candidates[chosen];
+    if candidate.faces == 0 {
+        println!("⚠️  No face was found on {}; thresholds keep their current values", candidate.path.display());
+    }
+
+    println!("[4/4] Writing {}...", output.display());
+    setup::configure(&mut config, candidate, setup::stable_device(&candidate.path));
+    println!("  device = {:?}", config.camera.device);
+    println!("  dark_threshold = {}", config.camera.dark_threshold);
+    println!("  detection_scale = {}", config.camera.detection_scale);
+
+    if output.exists() && !confirmed {
+        let answer = prompt(&format!("{} exists. Overwrite? [y/N]: ", output.display()))?;
+        if !answer.eq_ignore_ascii_case("y") {
+            println!("Not written.");
+            return Ok(());
+        }
+    }
+    config.save(output)?;
+
+    println!("✓ Configuration written");
+    println!();
+    println!("Next: enroll with `nihao add $USER`, then check with `nihao test $USER`");
+    Ok(())
+}
+
+fn cmd_devices(json: bool, sample: bool) -> anyhow::Result<()> {
+    let reports = nihao_core::probe::probe_all(sample)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+        return Ok(());
+    }
+
+    if reports.is_empty() {
+        println!("No video capture devices found");
+        return Ok(());
+    }
+
+    for report in &reports {
+        println!("{} - {}", report.path.display(), report.card);
+        for link in &report.links {
+            println!("  link:   {}", link.display());
+        }
+        println!("  driver: {}", report.driver);
+        println!("  bus:    {}", report.bus_info);
+        if let Some(usb_id) = &report.usb_id {
+            println!("  usb:    {}", usb_id);
+        }
+
+        let ir = &report.ir;
+        let judged = |value: Option<bool>| match value {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "unknown",
+        };
+        println!(
+            "  IR:     {:?} (name: {}, gray-only formats: {}, grayscale: {}, flicker: {})",
+            ir.likelihood,
+            judged(Some(ir.name)),
+            judged(Some(ir.gray_formats)),
+            judged(ir.grayscale),
+            judged(ir.flicker)
+        );
+        if let Some(error) = &ir.sample_error {
+            println!("          frames not checked: {}", error);
+        }
+
+        for format in &report.formats {
+            println!("  {} ({})", format.fourcc.trim(), format.description);
+            for size in &format.sizes {
+                let rates: Vec<String> = size.fps.iter().map(|fps| format!("{:.0}", fps)).collect();
+                println!(
+                    "    {}{}x{} @ {} fps",
+                    if size.stepwise { "up to " } else { "" },
+                    size.width,
+                    size.height,
+                    if rates.is_empty() { "?".to_string() } else { rates.join("/") }
+                );
+            }
+        }
+        println!();
+    }
+
+    println!("Use a by-id link (or device_name / usb_id) as [camera] device to survive renumbering.");
+    Ok(())
+}
+
+fn cmd_controls(device: Option<String>) -> anyhow::Result<()> {
+    let path = match device {
+        Some(device) => nihao_core::device::device_path(&device),
+        None => nihao_core::device::resolve(&Config::load()?.camera)?,
+    };
+
+    println!("Controls for {}:", path.display());
+    println!();
+
+    for control in nihao_core::controls::list(&path)? {
+        let value = control.value.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+        let mut flags = Vec::new();
+        if control.read_only {
+            flags.push("read-only");
+        }
+        if control.inactive {
+            flags.push("inactive");
+        }
+
+        println!(
+            "  {:<36} 0x{:08x} ({}) min={} max={} step={} default={} value={}{}",
+            control.key,
+            control.id,
+            control.kind,
+            control.minimum,
+            control.maximum,
+            control.step,
+            control.default,
+            value,
+            if flags.is_empty() { String::new() } else { format!(" [{}]", flags.join(", ")) }
+        );
+        for (index, label) in &control.menu {
+            println!("  {:<36}   {}: {}", "", index, label);
+        }
+    }
+
+    println!();
+    println!("Set any of these under [camera.controls] by key or ID, e.g.:");
+    println!("  auto_exposure = \"Manual Mode\"");
+    println!("  exposure_time_absolute = 300");
+
+    Ok(())
+}
+
+fn cmd_calibrate_hybrid(frames: usize) -> anyhow::Result<()> {
+    println!("Calibrating hybrid mode: look at the cameras from your usual distance");
+
+    let config = Config::load()?;
+    let mut recognizer = FaceRecognizer::new(config)?;
+    let calibration = recognizer.calibrate_hybrid(frames)?;
+    let m = calibration.transform.matrix;
+
+    println!("✓ Calibrated from {} frame pairs", calibration.pairs);
+    println!("Mean landmark error: {:.1}px", calibration.mean_error);
+    println!();
+    println!("Add this under [camera]:");
+    println!(
+        "ir_transform = [[{:.4}, {:.4}, {:.1}], [{:.4}, {:.4}, {:.1}]]",
+        m[0][0], m[0][1], m[0][2], m[1][0], m[1][1], m[1][2]
+    );
+
+    Ok(())
+}
+
 fn cmd_config(validate: bool) -> anyhow::Result<()> {
     let config = Config::load()?;
 
@@ -227,18 +573,143 @@ fn cmd_config(validate: bool) -> anyhow::Result<()> {
     println!();
 
     println!("[camera]");
+    println!("  backend = {:?}", config.camera.backend);
     println!("  device = {:?}", config.camera.device);
+    if let Some(name) = &config.camera.device_name {
+        println!("  device_name = {:?}", name);
+    }
+    if let Some(bus_info) = &config.camera.bus_info {
+        println!("  bus_info = {:?}", bus_info);
+    }
+    if let Some(usb_id) = &config.camera.usb_id {
+        println!("  usb_id = {:?}", usb_id);
+    }
+    if config.camera.prefer_ir {
+        println!("  prefer_ir = true");
+    }
     println!("  resolution = {}x{}", config.camera.width, config.camera.height);
+    println!(
+        "  pixel_format = {}",
+        config.camera.pixel_format.as_deref().unwrap_or("auto")
+    );
+    if config.camera.grayscale {
+        println!("  grayscale = true");
+    }
+    println!("  y16_bits = {}", config.camera.y16_bits);
+    if let Some([x, y, width, height]) = config.camera.crop {
+        println!("  crop = {}x{} at ({}, {})", width, height, x, y);
+    }
+    if config.camera.rotate != 0 || config.camera.flip_horizontal || config.camera.flip_vertical {
+        println!(
+            "  rotate = {}°, flip_horizontal = {}, flip_vertical = {}",
+            config.camera.rotate, config.camera.flip_horizontal, config.camera.flip_vertical
+        );
+    }
+    if config.camera.auto_orientation {
+        println!("  auto_orientation = true");
+    }
     println!("  detection_scale = {}", config.camera.detection_scale);
     println!("  dark_threshold = {}", config.camera.dark_threshold);
+    println!(
+        "  overexposure = {}% above {}",
+        config.camera.overexposure_threshold, config.camera.overexposure_level
+    );
+    println!("  max_noise = {}", config.camera.max_noise);
+    println!("  min_sharpness = {}", config.camera.min_sharpness);
+    println!("  shutter_frames = {}", config.camera.shutter_frames);
+    println!(
+        "  settle_frames = {} (tolerance {})",
+        config.camera.settle_frames, config.camera.settle_tolerance
+    );
+    println!(
+        "  face_brightness = {}..{}",
+        config.camera.min_face_brightness, config.camera.max_face_brightness
+    );
+    println!(
+        "  max pose = yaw {}°, pitch {}°, roll {}°",
+        config.camera.max_yaw, config.camera.max_pitch, config.camera.max_roll
+    );
+    println!(
+        "  min face = {}px between the eyes, {}% of the frame",
+        config.camera.min_eye_distance,
+        config.camera.min_face_fraction * 100.0
+    );
+    if config.camera.force_exposure_fusion {
+        if config.camera.exposure_bracket.is_empty() {
+            println!("  exposure fusion: {} consecutive frames", config.camera.fusion_frames);
+        } else {
+            println!(
+                "  exposure fusion: {} = {:?}",
+                config.camera.exposure_control, config.camera.exposure_bracket
+            );
+        }
+    } else {
+        println!("  exposure fusion: off");
+    }
+    if config.camera.denoise_frames > 1 {
+        println!(
+            "  denoise: {} frames, reset on {}% face movement",
+            config.camera.denoise_frames,
+            config.camera.denoise_motion * 100.0
+        );
+    }
+    for (name, stage) in [
+        ("detection", nihao_core::preprocess::Stage::Detection),
+        ("embedding", nihao_core::preprocess::Stage::Embedding),
+    ] {
+        let p = config.camera.preprocess_for(stage);
+        if p.is_enabled() {
+            println!(
+                "  {} preprocessing: stretch={} gamma={}{} clahe={} (clip {}, {}x{} tiles)",
+                name,
+                p.stretch_histogram,
+                if p.auto_gamma_correction { "auto≤" } else { "" },
+                p.gamma_value,
+                p.apply_clahe,
+                p.clahe_clip_limit,
+                p.clahe_tile_size,
+                p.clahe_tile_size
+            );
+        } else {
+            println!("  {} preprocessing: off", name);
+        }
+    }
+    for (key, value) in &config.camera.controls {
+        println!("  controls.{} = {:?}", key, value);
+    }
+    println!("  use_hybrid_mode = {}", config.camera.use_hybrid_mode);
+    if config.camera.use_hybrid_mode {
+        println!("  ir_device = {:?}", config.camera.ir_device.as_deref().unwrap_or(""));
+        if let Some(ir) = config.camera.ir_camera() {
+            println!("  ir_resolution = {}x{}", ir.width, ir.height);
+        }
+        match &config.camera.ir_transform {
+            Some(m) => println!("  ir_transform = {:?}", m),
+            None => println!("  ir_transform = rescale (uncalibrated)"),
+        }
+        for (key, value) in &config.camera.ir_controls {
+            println!("  ir_controls.{} = {:?}", key, value);
+        }
+    }
     println!();
 
     println!("[detection]");
+    println!("  backend = {:?}", config.detection.backend);
     println!("  model = {:?}", config.detection.model_path);
     println!(
         "  confidence_threshold = {}",
         config.detection.confidence_threshold
     );
+    println!("  scores = {:?} (retinaface)", config.detection.scores);
+    println!(
+        "  resize = {}",
+        if config.detection.letterbox { "letterbox" } else { "stretch" }
+    );
+    println!("  input_size = {} (models with a dynamic input)", config.detection.input_size);
+    println!("  nms_threshold = {}", config.detection.nms_threshold);
+    println!("  ranking = {:?}", config.detection.ranking);
+    println!("  retry = {:?}", config.detection.retry);
+    println!("  multi_face_policy = {:?}", config.detection.multi_face_policy);
     println!();
 
     println!("[embedding]");
@@ -249,6 +720,7 @@ fn cmd_config(validate: bool) -> anyhow::Result<()> {
     println!("  threshold = {}", config.matching.threshold);
     println!("  max_frames = {}", config.matching.max_frames);
     println!("  timeout = {}s", config.matching.timeout_secs);
+    println!("  confirm_frames = {} (same face track)", config.matching.confirm_frames);
     println!();
 
     println!("[runtime]");
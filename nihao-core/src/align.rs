@@ -1,5 +1,5 @@
 use crate::detect::FacialLandmarks;
-use image::{Rgb, RgbImage};
+use image::{DynamicImage, ImageBuffer, Pixel};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -27,10 +27,12 @@ pub struct FaceAligner;
 
 impl FaceAligner {
     /// Align a face to canonical position for embedding
+    ///
+    /// Gray frames give a gray face; anything else is aligned in RGB.
     pub fn align(
-        image: &RgbImage,
+        image: &DynamicImage,
         landmarks: &FacialLandmarks,
-    ) -> Result<RgbImage, AlignmentError> {
+    ) -> Result<DynamicImage, AlignmentError> {
         // Extract source landmarks as array
         let src_landmarks = [
             landmarks.left_eye,
@@ -45,21 +47,26 @@ impl FaceAligner {
             .ok_or_else(|| AlignmentError::Transform("Failed to compute transform".to_string()))?;
 
         // Apply transform to create aligned face
-        let aligned = Self::warp_affine(image, &transform, ALIGNED_SIZE, ALIGNED_SIZE)?;
+        let aligned: DynamicImage = match image {
+            DynamicImage::ImageLuma8(gray) => Self::warp_affine(gray, &transform, ALIGNED_SIZE, ALIGNED_SIZE)?.into(),
+            other => {
+                let rgb = crate::capture::rgb(other);
+                Self::warp_affine(&*rgb, &transform, ALIGNED_SIZE, ALIGNED_SIZE)?.into()
+            }
+        };
 
         Ok(aligned)
     }
 
-    /// Estimate similarity transform from source to destination landmarks
+    /// Estimate similarity transform from source to destination points
     /// Returns [a, b, tx, ty] where transform is:
     /// x' = a*x - b*y + tx
     /// y' = b*x + a*y + ty
-    fn estimate_similarity_transform(
-        src: &[(f32, f32); 5],
-        dst: &[(f32, f32); 5],
+    pub(crate) fn estimate_similarity_transform(
+        src: &[(f32, f32)],
+        dst: &[(f32, f32)],
     ) -> Option<[f32; 4]> {
         // Use least squares to solve for similarity transform
-        // We use the first 3 points (eyes and nose) for a stable estimate
 
         let mut sum_x = 0.0;
         let mut sum_y = 0.0;
@@ -69,11 +76,9 @@ impl FaceAligner {
         let mut sum_ux_vy = 0.0;
         let mut sum_vx_uy = 0.0;
 
-        let n = 5.0;
+        let n = src.len().min(dst.len()) as f32;
 
-        for i in 0..5 {
-            let (x, y) = src[i];
-            let (u, v) = dst[i];
+        for (&(x, y), &(u, v)) in src.iter().zip(dst) {
 
             sum_x += x;
             sum_y += y;
@@ -97,13 +102,13 @@ impl FaceAligner {
         Some([a, b, tx, ty])
     }
 
-    /// Apply affine warp to image
-    fn warp_affine(
-        image: &RgbImage,
+    /// Apply affine warp to image, channel by channel
+    fn warp_affine<P: Pixel<Subpixel = u8>>(
+        image: &ImageBuffer<P, Vec<u8>>,
         transform: &[f32; 4],
         out_width: u32,
         out_height: u32,
-    ) -> Result<RgbImage, AlignmentError> {
+    ) -> Result<ImageBuffer<P, Vec<u8>>, AlignmentError> {
         let [a, b, tx, ty] = *transform;
 
         // Compute inverse transform for backward mapping
@@ -117,7 +122,8 @@ impl FaceAligner {
         let a_inv = a / det;
         let b_inv = -b / det;
 
-        let mut output = RgbImage::new(out_width, out_height);
+        // Starts black, which is what out-of-bounds pixels stay
+        let mut output = ImageBuffer::<P, Vec<u8>>::new(out_width, out_height);
 
         for y_out in 0..out_height {
             for x_out in 0..out_width {
@@ -145,8 +151,6 @@ impl FaceAligner {
                     || x1 >= image.width() as i32
                     || y1 >= image.height() as i32
                 {
-                    // Out of bounds - use black
-                    output.put_pixel(x_out, y_out, Rgb([0, 0, 0]));
                     continue;
                 }
 
@@ -157,8 +161,8 @@ impl FaceAligner {
                 let p11 = image.get_pixel(x1 as u32, y1 as u32);
 
                 // Interpolate each channel
-                let mut pixel = [0u8; 3];
-                for c in 0..3 {
+                let (p00, p10, p01, p11) = (p00.channels(), p10.channels(), p01.channels(), p11.channels());
+                for (c, out) in output.get_pixel_mut(x_out, y_out).channels_mut().iter_mut().enumerate() {
                     let v00 = p00[c] as f32;
                     let v10 = p10[c] as f32;
                     let v01 = p01[c] as f32;
@@ -168,10 +172,8 @@ impl FaceAligner {
                     let v1 = v01 * (1.0 - x_frac) + v11 * x_frac;
                     let v = v0 * (1.0 - y_frac) + v1 * y_frac;
 
-                    pixel[c] = v.round().clamp(0.0, 255.0) as u8;
+                    *out = v.round().clamp(0.0, 255.0) as u8;
                 }
-
-                output.put_pixel(x_out, y_out, Rgb(pixel));
             }
         }
 
@@ -215,5 +217,17 @@ mod tests {
         assert!((transform[2] + 10.0).abs() < 1.0);
         assert!((transform[3] + 20.0).abs() < 1.0);
     }
+
+    #[test]
+    fn test_gray_warp_matches_rgb() {
+        let gray = image::GrayImage::from_fn(160, 160, |x, y| image::Luma([((x * 3 + y * 5) % 256) as u8]));
+        let rgb = DynamicImage::ImageLuma8(gray.clone()).to_rgb8();
+        let transform = [0.9, 0.1, 5.0, -3.0];
+
+        let from_gray = FaceAligner::warp_affine(&gray, &transform, ALIGNED_SIZE, ALIGNED_SIZE).unwrap();
+        let from_rgb = FaceAligner::warp_affine(&rgb, &transform, ALIGNED_SIZE, ALIGNED_SIZE).unwrap();
+        assert_eq!(from_gray.dimensions(), (ALIGNED_SIZE, ALIGNED_SIZE));
+        assert!(from_gray.pixels().zip(from_rgb.pixels()).all(|(g, c)| c.0 == [g.0[0]; 3]));
+    }
 }
 
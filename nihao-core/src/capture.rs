@@ -1,8 +1,11 @@
-use crate::config::CameraConfig;
-use image::{ImageBuffer, RgbImage};
+use crate::config::{CameraConfig, ControlValue};
+use crate::quality::FrameQuality;
+use image::{DynamicImage, GrayImage, ImageBuffer, RgbImage};
+use std::borrow::Cow;
 use std::fs;
+use std::time::Duration;
 use thiserror::Error;
-use v4l::io::traits::CaptureStream;
+use v4l::io::traits::{CaptureStream, Stream as _};
 use v4l::prelude::*;
 use v4l::video::Capture as V4lCapture;
 use v4l::{Device, FourCC};
@@ -11,6 +14,8 @@ use v4l::{Device, FourCC};
 pub enum CaptureError {
     #[error("Failed to open camera device: {0}")]
     DeviceOpen(String),
+    #[error("No camera matches {0}")]
+    NoMatchingDevice(String),
     #[error("Failed to capture frame: {0}")]
     Capture(String),
     #[error("Frame conversion failed: {0}")]
@@ -21,54 +26,358 @@ pub enum CaptureError {
     V4L(#[from] std::io::Error),
     #[error("Bad frame: {0}")]
     BadFrame(String), // Separate error for bad frames that can be retried
+    #[error("End of frame stream")]
+    EndOfStream, // Replay sources ran out of recorded frames
+    #[error("Camera is covered (privacy shutter closed?): {0} black frames in a row")]
+    ShutterClosed(u32),
+    #[error("Camera is in use by another application: {0}")]
+    Busy(String),
+    #[error("Camera disconnected: {0}")]
+    Disconnected(String),
+}
+
+impl CaptureError {
+    /// Errors that more frames won't fix; authentication gives up at once
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Self::ShutterClosed(_) | Self::Busy(_) | Self::Disconnected(_))
+    }
+
+    /// Classify a device I/O error, using `other` for anything not busy or unplugged
+    fn from_io(e: std::io::Error, other: impl FnOnce(std::io::Error) -> Self) -> Self {
+        match e.raw_os_error() {
+            Some(EBUSY) => Self::Busy(e.to_string()),
+            Some(ENODEV) => Self::Disconnected(e.to_string()),
+            _ => other(e),
+        }
+    }
+}
+
+/// Linux errno values the capture path reacts to
+const EBUSY: i32 = 16;
+const ENODEV: i32 = 19;
+
+/// Waits between attempts to open a camera another application holds
+const BUSY_BACKOFF: [Duration; 3] = [
+    Duration::from_millis(100),
+    Duration::from_millis(300),
+    Duration::from_millis(900),
+];
+
+/// Run `attempt`, retrying with `BUSY_BACKOFF` while the device is busy
+fn retry_busy<T>(mut attempt: impl FnMut() -> Result<T, CaptureError>) -> Result<T, CaptureError> {
+    for delay in BUSY_BACKOFF {
+        match attempt() {
+            Err(CaptureError::Busy(reason)) => {
+                log::debug!("Camera busy ({}), retrying in {}ms", reason, delay.as_millis());
+                std::thread::sleep(delay);
+            }
+            result => return result,
+        }
+    }
+    attempt()
+}
+
+/// Waits between attempts to reopen an unplugged camera, about 1.5s in all:
+/// udev needs a moment to re-create the node after the device comes back
+const REOPEN_BACKOFF: [Duration; 5] = [
+    Duration::from_millis(100),
+    Duration::from_millis(200),
+    Duration::from_millis(300),
+    Duration::from_millis(400),
+    Duration::from_millis(500),
+];
+
+/// Run `open` until it succeeds, waiting with `REOPEN_BACKOFF` in between,
+/// and give up with `Disconnected`. A busy device is reported as is, since
+/// `open` has already retried that.
+fn retry_reopen<T>(
+    mut open: impl FnMut() -> Result<T, CaptureError>,
+    mut sleep: impl FnMut(Duration),
+) -> Result<T, CaptureError> {
+    for delay in REOPEN_BACKOFF {
+        match open() {
+            Err(CaptureError::Busy(reason)) => return Err(CaptureError::Busy(reason)),
+            Ok(opened) => return Ok(opened),
+            Err(e) => {
+                log::debug!("Camera not back yet ({}), retrying in {}ms", e, delay.as_millis());
+                sleep(delay);
+            }
+        }
+    }
+    open().map_err(|e| match e {
+        CaptureError::Busy(_) => e,
+        e => CaptureError::Disconnected(format!("could not reopen: {}", e)),
+    })
+}
+
+/// Buffers in the capture queue. Kept small so a dequeued frame is never
+/// more than one frame stale when the pipeline runs slower than the sensor.
+pub(crate) const STREAM_BUFFERS: u32 = 2;
+
+/// Longest wait for the driver to hand back a filled buffer
+const FRAME_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Capture metadata reported by the driver for a frame
+#[derive(Debug, Clone, Copy)]
+pub struct FrameInfo {
+    /// Driver frame counter (gaps mean the driver dropped frames)
+    pub sequence: u32,
+    /// Driver capture timestamp (usually CLOCK_MONOTONIC)
+    pub timestamp: Duration,
+}
+
+/// How long the sensor's auto-exposure took to settle after the stream started
+#[derive(Debug, Clone, Copy)]
+pub struct ExposureSettle {
+    /// Frames read after warm-up, including the one that showed convergence
+    pub frames: u32,
+    pub elapsed: Duration,
+    /// False when `settle_frames` ran out first
+    pub converged: bool,
+}
+
+/// Mean luminance of a frame, 0-255
+fn mean_luminance(image: &DynamicImage) -> f32 {
+    let gray = luma(image);
+    let total: u64 = gray.as_raw().iter().map(|&v| v as u64).sum();
+    total as f32 / gray.as_raw().len().max(1) as f32
+}
+
+/// Luminance plane of a frame (BT.601), borrowed when the frame is gray
+pub fn luma(image: &DynamicImage) -> Cow<'_, GrayImage> {
+    match image {
+        DynamicImage::ImageLuma8(gray) => Cow::Borrowed(gray),
+        other => {
+            let rgb = other.to_rgb8();
+            Cow::Owned(GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+                let [r, g, b] = rgb.get_pixel(x, y).0;
+                image::Luma([(0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32).round() as u8])
+            }))
+        }
+    }
+}
+
+/// RGB copy of a frame, for output that needs color (debug overlays)
+pub fn rgb(image: &DynamicImage) -> Cow<'_, RgbImage> {
+    match image {
+        DynamicImage::ImageRgb8(rgb) => Cow::Borrowed(rgb),
+        other => Cow::Owned(other.to_rgb8()),
+    }
+}
+
+/// Bring a loaded image into one of the two frame layouts: gray stays gray,
+/// everything else becomes RGB
+pub fn normalize(image: DynamicImage) -> DynamicImage {
+    match image {
+        DynamicImage::ImageLuma8(_) | DynamicImage::ImageRgb8(_) => image,
+        DynamicImage::ImageLumaA8(_) | DynamicImage::ImageLuma16(_) | DynamicImage::ImageLumaA16(_) => {
+            DynamicImage::ImageLuma8(image.to_luma8())
+        }
+        other => DynamicImage::ImageRgb8(other.to_rgb8()),
+    }
+}
+
+/// Whether the latest luminance in `history` is within `tolerance` of the
+/// frame before it, or of the one before that so an emitter lighting every
+/// other frame still counts as settled
+fn has_settled(history: &[f32], tolerance: f32) -> bool {
+    let Some((&last, earlier)) = history.split_last() else {
+        return false;
+    };
+    earlier.iter().rev().take(2).any(|&m| (last - m).abs() <= tolerance)
+}
+
+/// Pixel formats the capture path can decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Mjpeg,
+    Yuyv,
+    Grey,
+    Y16,
+    Nv12,
+    Rgb24,
+    Bgr24,
+}
+
+impl PixelFormat {
+    /// Negotiation order when the config doesn't force a format
+    pub const PREFERENCE: [PixelFormat; 7] = [
+        PixelFormat::Mjpeg,
+        PixelFormat::Yuyv,
+        PixelFormat::Grey,
+        PixelFormat::Y16,
+        PixelFormat::Nv12,
+        PixelFormat::Rgb24,
+        PixelFormat::Bgr24,
+    ];
+
+    pub fn fourcc(self) -> FourCC {
+        FourCC::new(match self {
+            PixelFormat::Mjpeg => b"MJPG",
+            PixelFormat::Yuyv => b"YUYV",
+            PixelFormat::Grey => b"GREY",
+            PixelFormat::Y16 => b"Y16 ",
+            PixelFormat::Nv12 => b"NV12",
+            PixelFormat::Rgb24 => b"RGB3",
+            PixelFormat::Bgr24 => b"BGR3",
+        })
+    }
+
+    pub fn from_fourcc(fourcc: FourCC) -> Option<Self> {
+        Self::PREFERENCE.into_iter().find(|f| f.fourcc() == fourcc)
+    }
+
+    /// Parse a config value such as "GREY" or "Y16" (padded to four characters)
+    pub fn from_name(name: &str) -> Option<Self> {
+        let bytes = name.as_bytes();
+        if bytes.is_empty() || bytes.len() > 4 {
+            return None;
+        }
+        let mut repr = *b"    ";
+        repr[..bytes.len()].copy_from_slice(bytes);
+        Self::from_fourcc(FourCC::new(&repr))
+    }
+}
+
+/// A decoded frame and its quality report
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub image: DynamicImage,
+    pub quality: FrameQuality,
+}
+
+/// A source of camera frames
+///
+/// `Camera` captures from a V4L2 device. The backends in `crate::source`
+/// replay recorded images or raw frame dumps so the rest of the pipeline
+/// can run on machines without a webcam.
+pub trait FrameSource: Send {
+    /// Read and decode the next frame without any quality checks
+    ///
+    /// Frames are `ImageLuma8` from monochrome sensors (and with
+    /// `grayscale = true`), `ImageRgb8` otherwise.
+    fn next_frame(&mut self) -> Result<DynamicImage, CaptureError>;
+
+    /// Camera configuration used for quality checks
+    fn config(&self) -> &CameraConfig;
+
+    /// Capture a frame with its quality report, whether or not it passes
+    fn capture_with_quality(&mut self) -> Result<Frame, CaptureError> {
+        let image = self.next_frame()?;
+        let quality = FrameQuality::assess(&image, self.config());
+        Ok(Frame { image, quality })
+    }
+
+    /// Capture a single frame with optional quality checks
+    fn capture_frame(&mut self, check_quality: bool) -> Result<DynamicImage, CaptureError> {
+        if !check_quality {
+            return self.next_frame();
+        }
+
+        let frame = self.capture_with_quality()?;
+        frame.quality.check()?;
+        Ok(frame.image)
+    }
+
+    /// Metadata for the most recently read frame, if the source provides it
+    fn last_frame_info(&self) -> Option<FrameInfo> {
+        None
+    }
+
+    /// Auto-exposure settling of the current or last stream, for live cameras
+    fn exposure_settle(&self) -> Option<ExposureSettle> {
+        None
+    }
+
+    /// Change a V4L2 control while streaming, by name or ID
+    fn set_control(&mut self, key: &str, _value: &ControlValue) -> Result<(), CaptureError> {
+        Err(CaptureError::Capture(format!(
+            "Cannot set {:?}: frame source has no controls",
+            key
+        )))
+    }
+
+    /// Read a V4L2 control's current value, by name or ID
+    fn get_control(&self, key: &str) -> Result<i64, CaptureError> {
+        Err(CaptureError::Capture(format!(
+            "Cannot read {:?}: frame source has no controls",
+            key
+        )))
+    }
+
+    /// Release streaming resources held between frames
+    ///
+    /// Called at the end of each authentication or enrollment attempt; the
+    /// next `next_frame` call starts a fresh session.
+    fn stop(&mut self) {}
 }
 
 pub struct Camera {
     device: Device,
+    stream: Option<MmapStream<'static>>, // Live for one authentication attempt
     width: u32,
     height: u32,
-    format: FourCC,
+    stride: usize,
+    format: PixelFormat,
     config: CameraConfig, // Store config for quality checks
+    last_info: Option<FrameInfo>,
+    settle: Option<ExposureSettle>, // Kept after stop() for timing reports
 }
 
 impl Camera {
     /// Create a new camera instance from configuration
+    ///
+    /// A device held by another application is retried a few times with
+    /// backoff before giving up with `CaptureError::Busy`.
     pub fn new(config: &CameraConfig) -> Result<Self, CaptureError> {
-        let device_path = &config.device;
-
-        // Open the device - extract device number from path (e.g., "/dev/video2" -> 2)
-        let device_num = if device_path.starts_with("/dev/video") {
-            device_path
-                .trim_start_matches("/dev/video")
-                .parse::<usize>()
-                .unwrap_or(0)
-        } else {
-            device_path.parse::<usize>().unwrap_or(0)
-        };
+        retry_busy(|| Self::open(config))
+    }
+
+    fn open(config: &CameraConfig) -> Result<Self, CaptureError> {
+        // Resolve selectors/symlinks to a concrete node, never falling back to another camera
+        let device_path = crate::device::resolve(config)?;
 
-        let device = Device::new(device_num)
-            .map_err(|e| CaptureError::DeviceOpen(format!("{}: {}", device_path, e)))?;
+        let device = Device::with_path(&device_path).map_err(|e| {
+            CaptureError::from_io(e, |e| CaptureError::DeviceOpen(format!("{}: {}", device_path.display(), e)))
+        })?;
 
         // Get current format
         let fmt = device.format()
             .map_err(|e| CaptureError::DeviceOpen(format!("Failed to get format: {}", e)))?;
 
         // Try to set desired resolution
-        let mut format = fmt.clone();
-        format.width = config.width;
-        format.height = config.height;
-
-        // Prefer MJPEG if available, fallback to YUYV
-        let preferred_formats = [
-            FourCC::new(b"MJPG"),
-            FourCC::new(b"YUYV"),
-        ];
-
-        let mut set_format = format;
-        for &fourcc in &preferred_formats {
-            set_format.fourcc = fourcc;
-            if device.set_format(&set_format).is_ok() {
-                break;
+        let mut requested = fmt;
+        requested.width = config.width;
+        requested.height = config.height;
+
+        // Negotiate among the formats the device advertises, forced format first
+        let advertised: Vec<FourCC> = device
+            .enum_formats()
+            .map(|descs| descs.iter().map(|d| d.fourcc).collect())
+            .unwrap_or_default();
+        log::debug!(
+            "Device formats: {}",
+            advertised.iter().map(|f| f.to_string()).collect::<Vec<_>>().join(", ")
+        );
+
+        let forced = config.pixel_format.as_deref().map(|name| {
+            PixelFormat::from_name(name).ok_or_else(|| {
+                CaptureError::DeviceOpen(format!("Unsupported pixel_format: {:?}", name))
+            })
+        }).transpose()?;
+
+        let candidates = forced.into_iter().chain(
+            PixelFormat::PREFERENCE
+                .into_iter()
+                .filter(|f| advertised.is_empty() || advertised.contains(&f.fourcc())),
+        );
+
+        for pixel_format in candidates {
+            requested.fourcc = pixel_format.fourcc();
+            match device.set_format(&requested) {
+                Ok(actual) if actual.fourcc == requested.fourcc => break,
+                Ok(actual) => log::debug!("Driver replaced {} with {}", requested.fourcc, actual.fourcc),
+                Err(e) => log::debug!("Failed to set {}: {}", requested.fourcc, e),
             }
         }
 
@@ -76,6 +385,18 @@ impl Camera {
         let actual_format = device.format()
             .map_err(|e| CaptureError::DeviceOpen(format!("Failed to verify format: {}", e)))?;
 
+        let format = PixelFormat::from_fourcc(actual_format.fourcc).ok_or_else(|| {
+            CaptureError::DeviceOpen(format!(
+                "{}: no supported pixel format (device offers: {})",
+                device_path.display(),
+                advertised.iter().map(|f| f.to_string()).collect::<Vec<_>>().join(", ")
+            ))
+        })?;
+
+        if forced.is_some_and(|f| f != format) {
+            log::warn!("Requested pixel format {:?} not accepted, using {}", config.pixel_format, actual_format.fourcc);
+        }
+
         log::info!(
             "Camera initialized: {}x{} {}",
             actual_format.width,
@@ -83,203 +404,153 @@ impl Camera {
             actual_format.fourcc
         );
 
+        // Exposure, gain and auto-exposure mode from [camera.controls]
+        crate::controls::apply(&device, &config.controls)?;
+
+        crate::geometry::check_crop(config.crop, actual_format.width, actual_format.height)
+            .map_err(|e| CaptureError::DeviceOpen(format!("{}: {}", device_path.display(), e)))?;
+
+        // Some drivers report 0 for compressed formats
+        let stride = match actual_format.stride {
+            0 => actual_format.width as usize,
+            stride => stride as usize,
+        };
+
         Ok(Self {
             device,
+            stream: None,
             width: actual_format.width,
             height: actual_format.height,
-            format: actual_format.fourcc,
+            stride,
+            format,
             config: config.clone(),
+            last_info: None,
+            settle: None,
         })
     }
 
-
-    /// Capture a single frame from the camera with quality checks
-    pub fn capture_frame(&mut self, check_quality: bool) -> Result<RgbImage, CaptureError> {
-        let mut stream = MmapStream::with_buffers(&self.device, v4l::buffer::Type::VideoCapture, 4)
-            .map_err(|e| CaptureError::Capture(format!("Failed to create stream: {}", e)))?;
-
-        let (buf, _meta) = stream
-            .next()
-            .map_err(|e| CaptureError::Capture(format!("Failed to capture frame: {}", e)))?;
-
-        let rgb = match self.format.str() {
-            Ok("MJPG") => self.decode_mjpeg(buf)?,
-            Ok("YUYV") => self.decode_yuyv(buf)?,
-            _ => {
-                return Err(CaptureError::Conversion(format!(
-                    "Unsupported pixel format: {}",
-                    self.format
-                )))
-            }
-        };
-
-        if check_quality {
-            // Check frame darkness (filter bad IR emitter reads)
-            let (darkness_pct, is_too_dark) =
-                self.analyze_frame_darkness(&rgb, self.config.dark_threshold);
-
-            if is_too_dark {
-                return Err(CaptureError::BadFrame(format!(
-                    "too dark: {:.1}% (threshold: {:.1}%)",
-                    darkness_pct, self.config.dark_threshold
-                )));
-            }
-
-            // Check for severe overexposure
-            let (overexposed_pct, is_too_bright) = self.is_overexposed(&rgb);
-
-            if is_too_bright {
-                return Err(CaptureError::BadFrame(format!(
-                    "overexposed: {:.1}% blown out (threshold: 15%)",
-                    overexposed_pct
-                )));
-            }
+    /// Start streaming if not already running, drop warm-up frames and wait
+    /// for auto-exposure to settle
+    fn ensure_streaming(&mut self) -> Result<&mut MmapStream<'static>, CaptureError> {
+        if self.stream.is_none() {
+            // Buffers can't be allocated while another process streams
+            let stream = retry_busy(|| self.start_stream())?;
+            self.stream = Some(stream);
+            self.settle_exposure()?;
         }
 
-        Ok(rgb)
+        Ok(self.stream.as_mut().unwrap())
     }
 
-
-    /// Analyze frame darkness to filter out bad IR emitter reads
-    /// Returns (darkness_percentage, is_bad_frame)
-    /// Based on Howdy's approach: compare.py:254-274
-    fn analyze_frame_darkness(&self, image: &RgbImage, dark_threshold: f32) -> (f32, bool) {
-        // Convert to grayscale and build 8-bin histogram
-        const BINS: usize = 8;
-        const BIN_SIZE: f32 = 256.0 / BINS as f32;
-
-        let mut histogram = [0u32; BINS];
-        let mut total_pixels = 0u32;
-        let mut black_pixels = 0u32;
-
-        for pixel in image.pixels() {
-            let gray = ((pixel[0] as u16 + pixel[1] as u16 + pixel[2] as u16) / 3) as u8;
-
-            // Count 100% black pixels (bad camera read)
-            if gray == 0 {
-                black_pixels += 1;
-            }
-
-            // Add to histogram bin
-            let bin = ((gray as f32 / BIN_SIZE).floor() as usize).min(BINS - 1);
-            histogram[bin] += 1;
-            total_pixels += 1;
+    /// Read frames until mean luminance stops changing or `settle_frames`
+    /// run out, so the pipeline never sees the auto-exposure ramp
+    fn settle_exposure(&mut self) -> Result<(), CaptureError> {
+        self.settle = None;
+        if self.config.settle_frames == 0 {
+            return Ok(());
         }
 
-        // Check for 100% black frame (bad camera read)
-        if black_pixels == total_pixels {
-            log::warn!("Frame is 100% black - bad camera read, skipping");
-            return (100.0, true);
+        let start = std::time::Instant::now();
+        let mut history = Vec::with_capacity(self.config.settle_frames as usize);
+        let mut converged = false;
+        while !converged && history.len() < self.config.settle_frames as usize {
+            history.push(mean_luminance(&self.read_frame()?));
+            converged = has_settled(&history, self.config.settle_tolerance);
         }
 
-        // Calculate darkness from first bin (darkest pixels)
-        let darkness_pct = (histogram[0] as f32 / total_pixels as f32) * 100.0;
-        let is_too_dark = darkness_pct > dark_threshold;
-
-        if is_too_dark {
-            log::warn!(
-                "Frame too dark: {:.1}% (threshold: {:.1}%) - IR emitter flash issue, skipping",
-                darkness_pct,
-                dark_threshold
+        let settle = ExposureSettle {
+            frames: history.len() as u32,
+            elapsed: start.elapsed(),
+            converged,
+        };
+        if converged {
+            log::debug!(
+                "⏱️  Exposure settled: {}ms ({} frames, mean luminance {:.1})",
+                settle.elapsed.as_millis(),
+                settle.frames,
+                history.last().unwrap()
             );
         } else {
-            log::debug!("Frame darkness: {:.1}%", darkness_pct);
-        }
-
-        (darkness_pct, is_too_dark)
-    }
-
-    /// Detect if image is overexposed (too many bright pixels)
-    /// Returns (overexposure_percentage, is_too_bright)
-    fn is_overexposed(&self, image: &RgbImage) -> (f32, bool) {
-        let total_pixels = image.pixels().len();
-        let bright_threshold = 240u8; // Pixels above this are "blown out"
-
-        let bright_pixels = image
-            .pixels()
-            .filter(|p| {
-                let avg = (p[0] as u16 + p[1] as u16 + p[2] as u16) / 3;
-                avg as u8 > bright_threshold
-            })
-            .count();
-
-        let overexposed_pct = (bright_pixels as f32 / total_pixels as f32) * 100.0;
-
-        // Relaxed threshold for faster authentication (3-second target)
-        // 15% allows more frames to pass on first try
-        let is_too_bright = overexposed_pct > 15.0;
-
-        if is_too_bright {
-            log::warn!(
-                "Image is severely overexposed: {:.1}% of pixels are blown out (threshold: 15%)",
-                overexposed_pct
-            );
-        } else if overexposed_pct > 8.0 {
             log::debug!(
-                "Image has some overexposure: {:.1}% of pixels are blown out",
-                overexposed_pct
+                "⏱️  Exposure still changing after {} frames ({}ms), continuing anyway",
+                settle.frames,
+                settle.elapsed.as_millis()
             );
         }
-
-        (overexposed_pct, is_too_bright)
+        self.settle = Some(settle);
+        Ok(())
     }
 
+    fn start_stream(&self) -> Result<MmapStream<'static>, CaptureError> {
+        let mut stream = MmapStream::with_buffers(&self.device, v4l::buffer::Type::VideoCapture, STREAM_BUFFERS)
+            .map_err(|e| {
+                CaptureError::from_io(e, |e| CaptureError::Capture(format!("Failed to create stream: {}", e)))
+            })?;
+        stream.set_timeout(FRAME_TIMEOUT);
+
+        // STREAMON happens on the first dequeue
+        let start = std::time::Instant::now();
+        for _ in 0..self.config.warmup_frames {
+            stream.next().map_err(Self::stream_error)?;
+        }
+        log::debug!(
+            "Stream started, dropped {} warm-up frames in {}ms",
+            self.config.warmup_frames,
+            start.elapsed().as_millis()
+        );
 
-    /// Decode MJPEG frame to RGB
-    fn decode_mjpeg(&self, data: &[u8]) -> Result<RgbImage, CaptureError> {
-        let img = image::load_from_memory_with_format(data, image::ImageFormat::Jpeg)
-            .map_err(|e| CaptureError::Conversion(format!("MJPEG decode failed: {}", e)))?;
+        Ok(stream)
+    }
 
-        Ok(img.to_rgb8())
+    fn stream_error(e: std::io::Error) -> CaptureError {
+        if e.kind() == std::io::ErrorKind::TimedOut {
+            CaptureError::Timeout
+        } else {
+            CaptureError::from_io(e, |e| CaptureError::Capture(format!("Failed to capture frame: {}", e)))
+        }
     }
 
-    /// Decode YUYV frame to RGB
-    fn decode_yuyv(&self, data: &[u8]) -> Result<RgbImage, CaptureError> {
-        let width = self.width as usize;
-        let height = self.height as usize;
+    /// Open the camera again after it was unplugged, resolving selectors anew
+    /// since the node may come back under another number
+    fn reopen(&mut self) -> Result<(), CaptureError> {
+        self.stream = None;
+        let config = self.config.clone();
+        *self = retry_reopen(|| Self::new(&config), std::thread::sleep)?;
+        log::info!("Camera reopened");
+        Ok(())
+    }
 
-        if data.len() < width * height * 2 {
-            return Err(CaptureError::Conversion(
-                "YUYV buffer too small".to_string(),
-            ));
-        }
+    /// Dequeue and decode one frame
+    fn read_frame(&mut self) -> Result<DynamicImage, CaptureError> {
+        let (width, height, stride, format) = (self.width, self.height, self.stride, self.format);
+        let (previous, grayscale, y16_bits) = (self.last_info, self.config.grayscale, self.config.y16_bits);
+
+        let result = self.ensure_streaming()?.next().map_err(Self::stream_error);
+        let (buf, meta) = match result {
+            Ok(frame) => frame,
+            Err(e) => {
+                // Restart the session on the next call rather than reuse a broken queue
+                self.stream = None;
+                return Err(e);
+            }
+        };
 
-        let mut rgb_data = vec![0u8; width * height * 3];
-
-        // Convert YUYV to RGB
-        // YUYV format: Y0 U Y1 V (2 pixels in 4 bytes)
-        for y in 0..height {
-            for x in 0..(width / 2) {
-                let yuyv_offset = (y * width * 2) + (x * 4);
-                let rgb_offset = (y * width * 3) + (x * 2 * 3);
-
-                let y0 = data[yuyv_offset] as i32;
-                let u = data[yuyv_offset + 1] as i32 - 128;
-                let y1 = data[yuyv_offset + 2] as i32;
-                let v = data[yuyv_offset + 3] as i32 - 128;
-
-                // Convert YUV to RGB for pixel 0
-                let r0 = (y0 + ((1436 * v) >> 10)).clamp(0, 255) as u8;
-                let g0 = (y0 - ((354 * u + 732 * v) >> 10)).clamp(0, 255) as u8;
-                let b0 = (y0 + ((1814 * u) >> 10)).clamp(0, 255) as u8;
-
-                rgb_data[rgb_offset] = r0;
-                rgb_data[rgb_offset + 1] = g0;
-                rgb_data[rgb_offset + 2] = b0;
-
-                // Convert YUV to RGB for pixel 1
-                let r1 = (y1 + ((1436 * v) >> 10)).clamp(0, 255) as u8;
-                let g1 = (y1 - ((354 * u + 732 * v) >> 10)).clamp(0, 255) as u8;
-                let b1 = (y1 + ((1814 * u) >> 10)).clamp(0, 255) as u8;
-
-                rgb_data[rgb_offset + 3] = r1;
-                rgb_data[rgb_offset + 4] = g1;
-                rgb_data[rgb_offset + 5] = b1;
+        let info = FrameInfo {
+            sequence: meta.sequence,
+            timestamp: meta.timestamp.into(),
+        };
+        if let Some(prev) = previous {
+            let dropped = info.sequence.wrapping_sub(prev.sequence).saturating_sub(1);
+            if dropped > 0 {
+                log::debug!("Driver dropped {} frames before sequence {}", dropped, info.sequence);
             }
         }
 
-        ImageBuffer::from_raw(width as u32, height as u32, rgb_data)
-            .ok_or_else(|| CaptureError::Conversion("Failed to create RGB image".to_string()))
+        let image = decode_frame(format, buf, width, height, stride, grayscale, y16_bits)
+            .map(|image| crate::geometry::apply(image, &self.config));
+
+        self.last_info = Some(info);
+        image
     }
 
     /// Enumerate available camera devices
@@ -308,35 +579,266 @@ impl Camera {
         Ok(devices)
     }
 
-    /// Check if a device supports IR input
+    /// Check if a device's name marks it as an IR camera
+    ///
+    /// Name only; `probe::probe` also looks at formats and frame content.
     pub fn is_ir_camera(device_path: &str) -> Result<bool, CaptureError> {
-        // Parse device number from path
-        let device_num = if device_path.starts_with("/dev/video") {
-            device_path
-                .trim_start_matches("/dev/video")
-                .parse::<usize>()
-                .unwrap_or(0)
-        } else {
-            device_path.parse::<usize>().unwrap_or(0)
-        };
+        let info = crate::device::DeviceInfo::probe(&crate::device::device_path(device_path))?;
+        Ok(info.looks_ir())
+    }
+
+}
+
+impl FrameSource for Camera {
+    fn next_frame(&mut self) -> Result<DynamicImage, CaptureError> {
+        match self.read_frame() {
+            Err(CaptureError::Disconnected(reason)) => {
+                log::warn!("Camera disconnected ({}), reopening", reason);
+                self.reopen()?;
+                self.read_frame()
+            }
+            result => result,
+        }
+    }
+
+    fn config(&self) -> &CameraConfig {
+        &self.config
+    }
+
+    fn last_frame_info(&self) -> Option<FrameInfo> {
+        self.last_info
+    }
+
+    fn exposure_settle(&self) -> Option<ExposureSettle> {
+        self.settle
+    }
+
+    fn set_control(&mut self, key: &str, value: &ControlValue) -> Result<(), CaptureError> {
+        crate::controls::set(&self.device, key, value)
+    }
+
+    fn get_control(&self, key: &str) -> Result<i64, CaptureError> {
+        crate::controls::get(&self.device, key)
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut stream) = self.stream.take() {
+            if let Err(e) = stream.stop() {
+                log::warn!("Failed to stop camera stream: {}", e);
+            }
+            log::debug!("Camera stream stopped");
+        }
+        self.last_info = None;
+    }
+}
+
+/// Decode MJPEG frame, to gray if the JPEG is grayscale and RGB otherwise
+pub(crate) fn decode_mjpeg(data: &[u8]) -> Result<DynamicImage, CaptureError> {
+    let img = image::load_from_memory_with_format(data, image::ImageFormat::Jpeg)
+        .map_err(|e| CaptureError::Conversion(format!("MJPEG decode failed: {}", e)))?;
+
+    Ok(normalize(img))
+}
+
+/// Check that a buffer holds `height` rows of `row_bytes`, `stride` bytes apart
+fn check_buffer(
+    data: &[u8],
+    row_bytes: usize,
+    stride: usize,
+    height: usize,
+    name: &str,
+) -> Result<(), CaptureError> {
+    let needed = if height == 0 { 0 } else { stride * (height - 1) + row_bytes };
+    if stride < row_bytes || data.len() < needed {
+        return Err(CaptureError::Conversion(format!(
+            "{} buffer too small: {} bytes, expected {}",
+            name,
+            data.len(),
+            needed
+        )));
+    }
+    Ok(())
+}
+
+/// Convert one YUV sample (BT.601, U/V centered on zero) to RGB
+fn yuv_to_rgb(y: i32, u: i32, v: i32) -> [u8; 3] {
+    let r = (y + ((1436 * v) >> 10)).clamp(0, 255) as u8;
+    let g = (y - ((354 * u + 732 * v) >> 10)).clamp(0, 255) as u8;
+    let b = (y + ((1814 * u) >> 10)).clamp(0, 255) as u8;
+    [r, g, b]
+}
+
+/// Decode YUYV frame to RGB
+pub(crate) fn decode_yuyv(data: &[u8], width: u32, height: u32, stride: usize) -> Result<RgbImage, CaptureError> {
+    let (w, h) = (width as usize, height as usize);
+    check_buffer(data, w * 2, stride, h, "YUYV")?;
+
+    let mut rgb_data = vec![0u8; w * h * 3];
+
+    // Convert YUYV to RGB
+    // YUYV format: Y0 U Y1 V (2 pixels in 4 bytes)
+    for y in 0..h {
+        for x in 0..(w / 2) {
+            let yuyv_offset = (y * stride) + (x * 4);
+            let rgb_offset = (y * w * 3) + (x * 2 * 3);
+
+            let y0 = data[yuyv_offset] as i32;
+            let u = data[yuyv_offset + 1] as i32 - 128;
+            let y1 = data[yuyv_offset + 2] as i32;
+            let v = data[yuyv_offset + 3] as i32 - 128;
+
+            rgb_data[rgb_offset..rgb_offset + 3].copy_from_slice(&yuv_to_rgb(y0, u, v));
+            rgb_data[rgb_offset + 3..rgb_offset + 6].copy_from_slice(&yuv_to_rgb(y1, u, v));
+        }
+    }
+
+    ImageBuffer::from_raw(width, height, rgb_data)
+        .ok_or_else(|| CaptureError::Conversion("Failed to create RGB image".to_string()))
+}
+
+/// Decode only the Y samples of a YUYV frame
+pub(crate) fn decode_yuyv_luma(data: &[u8], width: u32, height: u32, stride: usize) -> Result<GrayImage, CaptureError> {
+    let (w, h) = (width as usize, height as usize);
+    check_buffer(data, w * 2, stride, h, "YUYV")?;
+
+    let luma_data = (0..h)
+        .flat_map(|y| data[y * stride..y * stride + w * 2].iter().step_by(2).copied())
+        .collect();
+
+    ImageBuffer::from_raw(width, height, luma_data)
+        .ok_or_else(|| CaptureError::Conversion("Failed to create gray image".to_string()))
+}
+
+/// Decode NV12 frame (full-res Y plane, half-res interleaved UV plane) to RGB
+///
+/// Odd widths and heights round the chroma plane up, so the last column
+/// and row of pixels still have a UV pair of their own.
+pub(crate) fn decode_nv12(data: &[u8], width: u32, height: u32, stride: usize) -> Result<RgbImage, CaptureError> {
+    let (w, h) = (width as usize, height as usize);
+    check_buffer(data, w, stride, h, "NV12")?;
+    let uv_plane = data.get(stride * h..).unwrap_or_default();
+    check_buffer(uv_plane, w.div_ceil(2) * 2, stride, h.div_ceil(2), "NV12 chroma")?;
+
+    let mut rgb_data = vec![0u8; w * h * 3];
+
+    for y in 0..h {
+        for x in 0..w {
+            let luma = data[y * stride + x] as i32;
+            let uv_offset = (y / 2) * stride + (x / 2) * 2;
+            let u = uv_plane[uv_offset] as i32 - 128;
+            let v = uv_plane[uv_offset + 1] as i32 - 128;
+
+            let rgb_offset = (y * w + x) * 3;
+            rgb_data[rgb_offset..rgb_offset + 3].copy_from_slice(&yuv_to_rgb(luma, u, v));
+        }
+    }
+
+    ImageBuffer::from_raw(width, height, rgb_data)
+        .ok_or_else(|| CaptureError::Conversion("Failed to create RGB image".to_string()))
+}
 
-        // Try to open the device
-        let device = Device::new(device_num)
-            .map_err(|e| CaptureError::DeviceOpen(format!("{}: {}", device_path, e)))?;
+/// Decode 8-bit GREY frame (or the Y plane of NV12)
+pub(crate) fn decode_grey(data: &[u8], width: u32, height: u32, stride: usize) -> Result<GrayImage, CaptureError> {
+    let (w, h) = (width as usize, height as usize);
+    check_buffer(data, w, stride, h, "GREY")?;
 
-        // Get device capabilities
-        let caps = device.query_caps()
-            .map_err(|e| CaptureError::DeviceOpen(format!("Failed to query caps: {}", e)))?;
+    let luma_data = (0..h)
+        .flat_map(|y| &data[y * stride..y * stride + w])
+        .copied()
+        .collect();
 
-        // Check device name for IR indicators
-        let name_lower = caps.card.to_lowercase();
-        let is_ir = name_lower.contains("ir") ||
-                    name_lower.contains("infrared") ||
-                    name_lower.contains("depth");
+    ImageBuffer::from_raw(width, height, luma_data)
+        .ok_or_else(|| CaptureError::Conversion("Failed to create gray image".to_string()))
+}
 
-        Ok(is_ir)
+/// Decode 16-bit little-endian Y16 frame to 8-bit gray
+///
+/// IR sensors usually fill only the low 10-12 bits of each sample, so the
+/// shift down to 8 bits comes from the sensor's bit depth (`[camera]
+/// y16_bits`) instead of always dropping the low byte, which would leave a
+/// nearly black image. The shift is the same for every frame so brightness
+/// stays comparable across frames; samples above the bit depth saturate.
+pub(crate) fn decode_y16(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    bits: u32,
+) -> Result<GrayImage, CaptureError> {
+    let (w, h) = (width as usize, height as usize);
+    check_buffer(data, w * 2, stride, h, "Y16")?;
+
+    let shift = bits.clamp(8, 16) - 8;
+    let mut luma_data = Vec::with_capacity(w * h);
+    for y in 0..h {
+        for x in 0..w {
+            let offset = y * stride + x * 2;
+            let sample = u16::from_le_bytes([data[offset], data[offset + 1]]);
+            luma_data.push((sample >> shift).min(255) as u8);
+        }
     }
 
+    ImageBuffer::from_raw(width, height, luma_data)
+        .ok_or_else(|| CaptureError::Conversion("Failed to create gray image".to_string()))
+}
+
+/// Decode packed 24-bit RGB (`swap_rb = false`) or BGR (`swap_rb = true`) frame
+pub(crate) fn decode_rgb24(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    swap_rb: bool,
+) -> Result<RgbImage, CaptureError> {
+    let (w, h) = (width as usize, height as usize);
+    check_buffer(data, w * 3, stride, h, if swap_rb { "BGR24" } else { "RGB24" })?;
+
+    let mut rgb_data = Vec::with_capacity(w * h * 3);
+    for y in 0..h {
+        let row = &data[y * stride..y * stride + w * 3];
+        if swap_rb {
+            for px in row.chunks_exact(3) {
+                rgb_data.extend_from_slice(&[px[2], px[1], px[0]]);
+            }
+        } else {
+            rgb_data.extend_from_slice(row);
+        }
+    }
+
+    ImageBuffer::from_raw(width, height, rgb_data)
+        .ok_or_else(|| CaptureError::Conversion("Failed to create RGB image".to_string()))
+}
+
+/// Decode a raw frame in any supported pixel format
+///
+/// Monochrome formats always decode to gray. With `luma_only`, so do the
+/// color ones; YUV layouts then skip chroma altogether. `y16_bits` is the
+/// sensor bit depth of Y16 samples.
+pub(crate) fn decode_frame(
+    format: PixelFormat,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    luma_only: bool,
+    y16_bits: u32,
+) -> Result<DynamicImage, CaptureError> {
+    let image = match format {
+        PixelFormat::Grey => decode_grey(data, width, height, stride)?.into(),
+        PixelFormat::Y16 => decode_y16(data, width, height, stride, y16_bits)?.into(),
+        PixelFormat::Yuyv if luma_only => decode_yuyv_luma(data, width, height, stride)?.into(),
+        PixelFormat::Nv12 if luma_only => decode_grey(data, width, height, stride)?.into(),
+        PixelFormat::Mjpeg => decode_mjpeg(data)?,
+        PixelFormat::Yuyv => decode_yuyv(data, width, height, stride)?.into(),
+        PixelFormat::Nv12 => decode_nv12(data, width, height, stride)?.into(),
+        PixelFormat::Rgb24 => decode_rgb24(data, width, height, stride, false)?.into(),
+        PixelFormat::Bgr24 => decode_rgb24(data, width, height, stride, true)?.into(),
+    };
+
+    Ok(match image {
+        DynamicImage::ImageRgb8(_) if luma_only => DynamicImage::ImageLuma8(luma(&image).into_owned()),
+        image => image,
+    })
 }
 
 #[cfg(test)]
@@ -357,6 +859,168 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_classify_io_errors() {
+        let other = |e: std::io::Error| CaptureError::Capture(e.to_string());
+        let busy = CaptureError::from_io(std::io::Error::from_raw_os_error(EBUSY), other);
+        assert!(matches!(busy, CaptureError::Busy(_)));
+        assert!(busy.is_fatal());
+
+        let gone = CaptureError::from_io(std::io::Error::from_raw_os_error(ENODEV), other);
+        assert!(matches!(gone, CaptureError::Disconnected(_)));
+
+        let io = CaptureError::from_io(std::io::Error::from_raw_os_error(5), other);
+        assert!(matches!(io, CaptureError::Capture(_)));
+        assert!(!io.is_fatal());
+    }
+
+    #[test]
+    fn test_reopen_waits_for_node() {
+        // Node missing for three attempts, then back
+        let (mut attempts, mut waited) = (0, Duration::ZERO);
+        let reopened = retry_reopen(
+            || {
+                attempts += 1;
+                if attempts <= 3 {
+                    Err(CaptureError::NoMatchingDevice("usb_id 04f2:b6d9".to_string()))
+                } else {
+                    Ok(attempts)
+                }
+            },
+            |delay| waited += delay,
+        );
+        assert_eq!(reopened.unwrap(), 4);
+        assert_eq!(waited, Duration::from_millis(600));
+
+        // Never comes back: bounded wait, then Disconnected
+        let mut waited = Duration::ZERO;
+        let gone = retry_reopen(
+            || Err::<(), _>(CaptureError::DeviceOpen("/dev/video2 does not exist".to_string())),
+            |delay| waited += delay,
+        );
+        assert!(matches!(gone, Err(CaptureError::Disconnected(_))));
+        assert!(waited >= Duration::from_secs(1) && waited <= Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_exposure_settling() {
+        assert!(!has_settled(&[], 2.0));
+        assert!(!has_settled(&[40.0], 2.0));
+        // Auto-exposure ramp
+        assert!(!has_settled(&[20.0, 45.0, 70.0], 2.0));
+        assert!(has_settled(&[20.0, 45.0, 70.0, 71.5], 2.0));
+        // Emitter lighting every other frame
+        assert!(has_settled(&[90.0, 12.0, 91.0], 2.0));
+
+        let gray = RgbImage::from_pixel(2, 2, image::Rgb([100; 3])).into();
+        assert!((mean_luminance(&gray) - 100.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_pixel_format_names() {
+        assert_eq!(PixelFormat::from_name("GREY"), Some(PixelFormat::Grey));
+        assert_eq!(PixelFormat::from_name("Y16"), Some(PixelFormat::Y16));
+        assert_eq!(PixelFormat::from_name("BGR3"), Some(PixelFormat::Bgr24));
+        assert_eq!(PixelFormat::from_name("H264"), None);
+        assert_eq!(PixelFormat::from_name(""), None);
+    }
+
+    #[test]
+    fn test_decode_grey_with_stride() {
+        // 2x2 frame with 2 bytes of row padding
+        let data = [10, 20, 0, 0, 30, 40, 0, 0];
+        let gray = decode_grey(&data, 2, 2, 4).unwrap();
+        assert_eq!(gray.get_pixel(1, 0).0, [20]);
+        assert_eq!(gray.get_pixel(0, 1).0, [30]);
+
+        assert!(decode_grey(&data[..5], 2, 2, 4).is_err());
+    }
+
+    #[test]
+    fn test_decode_y16_scales_10bit() {
+        // 10-bit samples: 1023 is full scale, 512 is mid grey
+        let mut data = Vec::new();
+        for v in [1023u16, 512, 0, 4] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        let gray = decode_y16(&data, 2, 2, 4, 10).unwrap();
+        assert_eq!(gray.get_pixel(0, 0).0, [255]);
+        assert_eq!(gray.get_pixel(1, 0).0, [128]);
+        assert_eq!(gray.get_pixel(0, 1).0, [0]);
+        assert_eq!(gray.get_pixel(1, 1).0, [1]);
+    }
+
+    #[test]
+    fn test_decode_y16_fixed_scale() {
+        // Same face pixel, once with a glint crossing a power of two
+        let frame = |peak: u16| {
+            let mut data = Vec::new();
+            for v in [400u16, peak] {
+                data.extend_from_slice(&v.to_le_bytes());
+            }
+            decode_y16(&data, 2, 1, 4, 10).unwrap()
+        };
+        let (dim, glint) = (frame(500), frame(1000));
+        assert_eq!(dim.get_pixel(0, 0), glint.get_pixel(0, 0));
+        assert_eq!(glint.get_pixel(0, 0).0, [100]);
+
+        // Samples above the configured depth saturate instead of wrapping
+        assert_eq!(frame(4000).get_pixel(1, 0).0, [255]);
+    }
+
+    #[test]
+    fn test_decode_nv12_neutral_chroma() {
+        // 2x2 Y plane followed by one UV pair
+        let data = [50, 100, 150, 200, 128, 128];
+        let rgb = decode_nv12(&data, 2, 2, 2).unwrap();
+        assert_eq!(rgb.get_pixel(0, 0).0, [50, 50, 50]);
+        assert_eq!(rgb.get_pixel(1, 1).0, [200, 200, 200]);
+    }
+
+    #[test]
+    fn test_decode_nv12_odd_size() {
+        // 3x3 Y plane and 2x2 UV pairs, rows 4 bytes apart
+        let mut data = vec![100, 100, 100, 0, 100, 100, 100, 0, 100, 100, 100, 0];
+        data.extend_from_slice(&[128, 128, 128, 128, 128, 128, 200, 128]);
+        let rgb = decode_nv12(&data, 3, 3, 4).unwrap();
+        assert_eq!(rgb.get_pixel(1, 1).0, [100, 100, 100]);
+        // The last row and column use the second chroma row and column
+        assert_ne!(rgb.get_pixel(2, 2).0, [100, 100, 100]);
+
+        // Missing the last chroma row
+        assert!(decode_nv12(&data[..16], 3, 3, 4).is_err());
+    }
+
+    #[test]
+    fn test_luma_only_decoding() {
+        // 2x2 YUYV with a row of padding bytes: Y0 U Y1 V
+        let data = [10, 90, 20, 160, 0, 0, 30, 90, 40, 160, 0, 0];
+        let gray = decode_yuyv_luma(&data, 2, 2, 6).unwrap();
+        assert_eq!(gray.as_raw(), &[10, 20, 30, 40]);
+
+        let frame = decode_frame(PixelFormat::Yuyv, &data, 2, 2, 6, true, 10).unwrap();
+        assert_eq!(frame.as_luma8(), Some(&gray));
+        assert!(decode_frame(PixelFormat::Yuyv, &data, 2, 2, 6, false, 10).unwrap().as_rgb8().is_some());
+
+        // Monochrome formats are gray either way
+        let nv12 = [50, 100, 150, 200, 128, 128];
+        let frame = decode_frame(PixelFormat::Grey, &nv12[..4], 2, 2, 2, false, 10).unwrap();
+        assert!(frame.as_luma8().is_some());
+        let frame = decode_frame(PixelFormat::Nv12, &nv12, 2, 2, 2, true, 10).unwrap();
+        assert_eq!(frame.as_luma8().unwrap().as_raw(), &[50, 100, 150, 200]);
+    }
+
+    #[test]
+    fn test_decode_bgr24_swaps_channels() {
+        let data = [1, 2, 3, 4, 5, 6];
+        let rgb = decode_rgb24(&data, 2, 1, 6, true).unwrap();
+        assert_eq!(rgb.get_pixel(0, 0).0, [3, 2, 1]);
+        assert_eq!(rgb.get_pixel(1, 0).0, [6, 5, 4]);
+
+        let rgb = decode_rgb24(&data, 2, 1, 6, false).unwrap();
+        assert_eq!(rgb.get_pixel(0, 0).0, [1, 2, 3]);
+    }
+
     #[test]
     #[ignore] // Requires actual camera hardware
     fn test_camera_capture() {
@@ -364,8 +1028,7 @@ mod tests {
             device: "/dev/video0".to_string(),
             width: 640,
             height: 480,
-            dark_threshold: 80.0,
-            detection_scale: 0.5,
+            ..crate::config::Config::default().camera
         };
 
         let mut camera = Camera::new(&config).expect("Failed to open camera");
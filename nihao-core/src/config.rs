@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
 use thiserror::Error;
@@ -9,6 +10,8 @@ pub enum ConfigError {
     Io(#[from] std::io::Error),
     #[error("Failed to parse config: {0}")]
     Parse(#[from] toml::de::Error),
+    #[error("Failed to serialize config: {0}")]
+    Serialize(#[from] toml::ser::Error),
     #[error("Invalid configuration: {0}")]
     Validation(String),
 }
@@ -26,30 +29,503 @@ pub struct Config {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CameraConfig {
+    /// V4L2 device path, or the file/directory/FIFO to read for replay backends
     pub device: String,
     pub width: u32,
     pub height: u32,
 
+    // Stable selectors, resolved at startup and preferred over `device` when set
+    #[serde(default)]
+    pub device_name: Option<String>,  // V4L2 card name (case-insensitive substring)
+    #[serde(default)]
+    pub bus_info: Option<String>,     // V4L2 bus info, e.g. "usb-0000:00:14.0-6"
+    #[serde(default)]
+    pub usb_id: Option<String>,       // USB "vendor:product", e.g. "04f2:b6d9"
+
+    // When selectors match several cameras, pick the one that looks infrared
+    #[serde(default)]
+    pub prefer_ir: bool,
+
+    // Hybrid mode: detect on this (RGB) camera, embed the same face from `ir_device`
+    #[serde(default)]
+    pub use_hybrid_mode: bool,
+    #[serde(default)]
+    pub ir_device: Option<String>,
+    // IR camera resolution; the RGB width and height if unset
+    #[serde(default)]
+    pub ir_width: Option<u32>,
+    #[serde(default)]
+    pub ir_height: Option<u32>,
+    // RGB→IR pixel mapping [[a, b, c], [d, e, f]] from `nihao calibrate-hybrid`;
+    // rescales between the two resolutions if unset
+    #[serde(default)]
+    pub ir_transform: Option<[[f32; 3]; 2]>,
+
+    // Frame source: "v4l2" (live camera), "image", "directory" or "raw"
+    #[serde(default)]
+    pub backend: CaptureBackend,
+
+    // Force a V4L2 pixel format (e.g. "GREY", "Y16"); negotiated automatically if unset
+    #[serde(default)]
+    pub pixel_format: Option<String>,
+    // Keep frames single-channel from decoding to the model inputs. GREY/Y16
+    // cameras always are; this extends it to IR cameras that send YUYV/MJPEG/NV12
+    #[serde(default)]
+    pub grayscale: bool,
+    // Significant bits per Y16 sample (usually 10-12); sets a fixed scale down to 8 bits
+    #[serde(default = "default_y16_bits")]
+    pub y16_bits: u32,
+
+    // Pixel layout of frames read by the "raw" backend
+    #[serde(default)]
+    pub raw_format: RawFormat,
+
+    // Frame geometry, applied to every camera frame before detection:
+    // crop (sensor pixels), then rotate, then flip
+    #[serde(default)]
+    pub crop: Option<[u32; 4]>,  // [x, y, width, height]
+    #[serde(default)]
+    pub rotate: u32,  // Clockwise: 0, 90, 180 or 270
+    #[serde(default)]
+    pub flip_horizontal: bool,
+    #[serde(default)]
+    pub flip_vertical: bool,
+    // Try rotated copies until a face is found and keep using that rotation
+    #[serde(default)]
+    pub auto_orientation: bool,
+
+    // Frame quality gates; see `quality::FrameQuality`
     #[serde(default = "default_dark_threshold")]
     pub dark_threshold: f32,  // Filter bad IR frames
+    #[serde(default = "default_overexposure_level")]
+    pub overexposure_level: u8,  // Pixels brighter than this count as blown out
+    #[serde(default = "default_overexposure_threshold")]
+    pub overexposure_threshold: f32,  // Max % of blown-out pixels
+    #[serde(default = "default_max_noise")]
+    pub max_noise: f32,  // Max estimated noise (gray levels), after temporal denoising
+    #[serde(default = "default_min_sharpness")]
+    pub min_sharpness: f32,  // Min Laplacian variance of the face region (motion blur)
+    #[serde(default = "default_min_face_brightness")]
+    pub min_face_brightness: f32,  // Mean face luminance bounds, 0-255
+    #[serde(default = "default_max_face_brightness")]
+    pub max_face_brightness: f32,
+    #[serde(default = "default_max_yaw")]
+    pub max_yaw: f32,  // Head pose limits in degrees, estimated from the landmarks
+    #[serde(default = "default_max_pitch")]
+    pub max_pitch: f32,
+    #[serde(default = "default_max_roll")]
+    pub max_roll: f32,
+    #[serde(default = "default_min_eye_distance")]
+    pub min_eye_distance: f32,  // Min pixels between the eyes
+    #[serde(default = "default_min_face_fraction")]
+    pub min_face_fraction: f32,  // Min face box area as a fraction of the frame
+    #[serde(default = "default_shutter_frames")]
+    pub shutter_frames: u32,  // All-black frames in a row that mean the shutter is closed (0 = never give up)
+
+    // Frames dropped after STREAMON while the sensor settles
+    #[serde(default = "default_warmup_frames")]
+    pub warmup_frames: u32,
+
+    // Auto-exposure convergence: after warm-up, read frames until mean
+    // luminance stops changing, then start handing frames to the pipeline
+    #[serde(default = "default_settle_frames")]
+    pub settle_frames: u32,  // Most frames to wait (0 = don't wait)
+    #[serde(default = "default_settle_tolerance")]
+    pub settle_tolerance: f32,  // Max mean luminance change between frames, gray levels
+
+    // Exposure fusion: merge several frames into one well-exposed frame, for
+    // IR emitters that only light every other frame or very dim scenes
+    #[serde(default)]
+    pub force_exposure_fusion: bool,
+    #[serde(default = "default_fusion_frames")]
+    pub fusion_frames: u32,  // Consecutive frames per fused frame
+    #[serde(default)]
+    pub exposure_bracket: Vec<i64>,  // Exposure per fused input instead, e.g. [100, 400, 1200]
+    #[serde(default = "default_exposure_control")]
+    pub exposure_control: String,  // Control stepped by exposure_bracket
+
+    // Temporal denoising: average the aligned face over the last accepted
+    // frames, starting over when the face moves
+    #[serde(default)]
+    pub denoise_frames: u32,  // Aligned faces averaged (0 or 1 = off)
+    #[serde(default = "default_denoise_motion")]
+    pub denoise_motion: f32,  // Face movement that restarts the average, fraction of face width
+
+    // IR preprocessing for both models; override per model with the
+    // [camera.detection_preprocess] and [camera.embedding_preprocess] tables
+    #[serde(flatten)]
+    pub preprocess: PreprocessConfig,
+    #[serde(default)]
+    pub detection_preprocess: Option<PreprocessConfig>,
+    #[serde(default)]
+    pub embedding_preprocess: Option<PreprocessConfig>,
 
     // Performance: downscale images for faster detection
     #[serde(default = "default_detection_scale")]
     pub detection_scale: f32,  // 0.5 = half resolution (4x faster), 1.0 = full res
+
+    // V4L2 controls applied when the camera opens, keyed by name or ID
+    // (see `nihao controls`), e.g. auto_exposure = "Manual Mode", gain = 64
+    #[serde(default)]
+    pub controls: BTreeMap<String, ControlValue>,
+
+    // Controls for the IR camera in hybrid mode
+    #[serde(default)]
+    pub ir_controls: BTreeMap<String, ControlValue>,
+}
+
+impl CameraConfig {
+    /// Preprocessing for frames fed to one model
+    ///
+    /// A per-model table replaces the shared settings as a whole; keys it
+    /// leaves out take their defaults.
+    pub fn preprocess_for(&self, stage: crate::preprocess::Stage) -> &PreprocessConfig {
+        let per_model = match stage {
+            crate::preprocess::Stage::Detection => &self.detection_preprocess,
+            crate::preprocess::Stage::Embedding => &self.embedding_preprocess,
+        };
+        per_model.as_ref().unwrap_or(&self.preprocess)
+    }
+
+    /// Whether frames are cropped, rotated or mirrored
+    pub fn has_geometry(&self) -> bool {
+        self.crop.is_some() || self.rotate % 360 != 0 || self.flip_horizontal || self.flip_vertical
+    }
+
+    /// Settings for the IR camera when hybrid mode is on
+    ///
+    /// Opened by path at `ir_width`x`ir_height` with `ir_controls` and an
+    /// automatically negotiated pixel format, decoded to gray. The crop is in
+    /// RGB sensor pixels and the exposure bracket in RGB exposure units, so
+    /// neither carries over; frames are never detected on, so neither does
+    /// auto-orientation. Everything else applies to both cameras: backend,
+    /// rotation and flips (the two share a bezel), quality gates, warm-up and
+    /// settling, consecutive-frame fusion, denoising and preprocessing.
+    pub fn ir_camera(&self) -> Option<CameraConfig> {
+        if !self.use_hybrid_mode {
+            return None;
+        }
+        Some(CameraConfig {
+            device: self.ir_device.clone()?,
+            width: self.ir_width.unwrap_or(self.width),
+            height: self.ir_height.unwrap_or(self.height),
+            device_name: None,
+            bus_info: None,
+            usb_id: None,
+            use_hybrid_mode: false,
+            ir_device: None,
+            ir_width: None,
+            ir_height: None,
+            ir_transform: None,
+            pixel_format: None,
+            grayscale: true,
+            crop: None,
+            auto_orientation: false,
+            exposure_bracket: Vec::new(),
+            controls: self.ir_controls.clone(),
+            ir_controls: BTreeMap::new(),
+            ..self.clone()
+        })
+    }
+}
+
+/// Where frames come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureBackend {
+    /// Live V4L2 camera
+    #[default]
+    V4l2,
+    /// A single JPEG/PNG image, returned for every frame
+    Image,
+    /// A directory of JPEG/PNG frames, replayed in file name order
+    Directory,
+    /// Raw frames of `width`x`height` read back-to-back from a file or FIFO
+    Raw,
+}
+
+/// Pixel layout of raw frame dumps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RawFormat {
+    #[default]
+    Yuyv,
+    Grey,
+}
+
+/// Contrast and brightness correction applied before detection or embedding
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreprocessConfig {
+    #[serde(default)]
+    pub stretch_histogram: bool,  // Stretch 1st..99th percentile to the full range first
+    #[serde(default)]
+    pub auto_gamma_correction: bool,  // Pick gamma from frame brightness, capped by gamma_value
+    #[serde(default = "default_gamma_value")]
+    pub gamma_value: f32,  // > 1.0 darkens, < 1.0 brightens, 1.0 = off
+    #[serde(default)]
+    pub apply_clahe: bool,  // Contrast Limited Adaptive Histogram Equalization, applied last
+    #[serde(default = "default_clahe_clip_limit")]
+    pub clahe_clip_limit: f32,  // Higher = more local contrast (and noise)
+    #[serde(default = "default_clahe_tile_size")]
+    pub clahe_tile_size: u32,  // CLAHE grid is N x N tiles
+}
+
+impl PreprocessConfig {
+    /// Whether any step would change the frame
+    pub fn is_enabled(&self) -> bool {
+        self.stretch_histogram
+            || self.auto_gamma_correction
+            || (self.gamma_value - 1.0).abs() > f32::EPSILON
+            || self.apply_clahe
+    }
+
+    fn validate(&self, table: &str) -> Result<(), ConfigError> {
+        if self.gamma_value <= 0.0 {
+            return Err(ConfigError::Validation(format!("{}: gamma_value must be positive", table)));
+        }
+        if self.clahe_clip_limit <= 0.0 {
+            return Err(ConfigError::Validation(format!("{}: clahe_clip_limit must be positive", table)));
+        }
+        if !(1..=64).contains(&self.clahe_tile_size) {
+            return Err(ConfigError::Validation(format!("{}: clahe_tile_size must be between 1 and 64", table)));
+        }
+        Ok(())
+    }
+}
+
+impl Default for PreprocessConfig {
+    fn default() -> Self {
+        Self {
+            stretch_histogram: false,
+            auto_gamma_correction: false,
+            gamma_value: default_gamma_value(),
+            apply_clahe: false,
+            clahe_clip_limit: default_clahe_clip_limit(),
+            clahe_tile_size: default_clahe_tile_size(),
+        }
+    }
+}
+
+fn default_fusion_frames() -> u32 {
+    3  // Covers one lit frame for emitters flashing every other frame
+}
+
+fn default_denoise_motion() -> f32 {
+    0.1
+}
+
+fn default_exposure_control() -> String {
+    "exposure_time_absolute".to_string()
+}
+
+fn default_gamma_value() -> f32 {
+    1.0  // No gamma correction
+}
+
+fn default_clahe_clip_limit() -> f32 {
+    2.0  // Howdy's value
+}
+
+fn default_clahe_tile_size() -> u32 {
+    8
+}
+
+/// Value of a `[camera.controls]` entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ControlValue {
+    Bool(bool),
+    Int(i64),
+    /// Menu entry label, e.g. "Manual Mode"
+    Name(String),
 }
 
 fn default_detection_scale() -> f32 {
     0.5  // Half resolution for faster detection
 }
 
+fn default_y16_bits() -> u32 {
+    10
+}
+
 fn default_dark_threshold() -> f32 {
     80.0  // Threshold for filtering bad IR frames
 }
 
+fn default_overexposure_level() -> u8 {
+    240
+}
+
+fn default_overexposure_threshold() -> f32 {
+    15.0  // Relaxed for faster authentication
+}
+
+fn default_max_noise() -> f32 {
+    25.0  // Only rejects very grainy low-light frames
+}
+
+fn default_min_sharpness() -> f32 {
+    15.0  // Motion-blurred faces fall well below this
+}
+
+fn default_min_face_brightness() -> f32 {
+    30.0
+}
+
+fn default_max_face_brightness() -> f32 {
+    235.0
+}
+
+fn default_max_yaw() -> f32 {
+    30.0  // ArcFace embeddings degrade quickly past this
+}
+
+fn default_max_pitch() -> f32 {
+    35.0  // Laptop cameras look up at the face, so allow more than yaw
+}
+
+fn default_max_roll() -> f32 {
+    30.0  // Alignment undoes roll; this only catches bad landmarks
+}
+
+fn default_min_eye_distance() -> f32 {
+    20.0  // The aligned 112x112 crop puts the eyes 35px apart
+}
+
+fn default_min_face_fraction() -> f32 {
+    0.01  // A 64x48 face in a 640x480 frame
+}
+
+fn default_settle_frames() -> u32 {
+    15  // Half a second at 30fps; most UVC sensors converge well within it
+}
+
+fn default_settle_tolerance() -> f32 {
+    2.0
+}
+
+fn default_shutter_frames() -> u32 {
+    5  // Well past warm-up; about 170ms at 30fps
+}
+
+fn default_warmup_frames() -> u32 {
+    2  // First frames after STREAMON are often stale or half-exposed
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DetectionConfig {
+    // Detection model family; model_path must point at a model of that kind
+    #[serde(default)]
+    pub backend: DetectionBackend,
     pub model_path: PathBuf,
     pub confidence_threshold: f32,
+    // What the RetinaFace class output holds: "probabilities" when the export
+    // applies softmax (Pytorch_Retinaface's convert_to_onnx.py does), or raw
+    // "logits"
+    #[serde(default)]
+    pub scores: DetectionScores,
+    // Scale frames into the model input keeping their aspect ratio and pad
+    // the rest; false stretches them to fill it
+    #[serde(default = "default_letterbox")]
+    pub letterbox: bool,
+    // Square input size for models that take any size (multiple of 32);
+    // smaller is faster but misses distant faces
+    #[serde(default = "default_detection_input_size")]
+    pub input_size: u32,
+    // Overlap (IoU) above which the weaker of two detections is dropped
+    #[serde(default = "default_nms_threshold")]
+    pub nms_threshold: f32,
+    // Order detections are ranked in: decides the face under the "ranked"
+    // policy and the order "any_match" tries them in
+    #[serde(default)]
+    pub ranking: DetectionRanking,
+    // What to try on a frame where detection_scale found no face
+    #[serde(default)]
+    pub retry: DetectionRetry,
+    // Which face to authenticate when a frame holds several
+    #[serde(default)]
+    pub multi_face_policy: MultiFacePolicy,
+}
+
+fn default_letterbox() -> bool {
+    true
+}
+
+fn default_detection_input_size() -> u32 {
+    640  // SCRFD's training size
+}
+
+fn default_nms_threshold() -> f32 {
+    0.4
+}
+
+/// Order detections are ranked in, best first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionRanking {
+    /// Confidence × sqrt(box area), so the person at the camera beats a
+    /// more confident face in the background
+    #[default]
+    ConfidenceArea,
+    /// Detector confidence alone
+    Confidence,
+    /// Box area alone
+    Area,
+}
+
+/// Extra detection passes over a frame with no face at `detection_scale`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectionRetry {
+    /// Move on to the next frame
+    Off,
+    /// Retry at full resolution
+    #[default]
+    Full,
+    /// Retry at full resolution, then on 2x2 overlapping tiles
+    Tiles,
+}
+
+/// Face detection model family
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectionBackend {
+    /// insightface SCRFD (500m, 2.5g, 10g, 34g)
+    #[default]
+    Scrfd,
+    /// OpenCV zoo YuNet
+    Yunet,
+    /// RetinaFace (MobileNet-0.25 or ResNet-50)
+    Retinaface,
+}
+
+/// Form of a detector's two-class (background, face) score output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectionScores {
+    /// Softmax already applied; the face column is the probability
+    #[default]
+    Probabilities,
+    /// Raw logits; softmax is applied to each pair
+    Logits,
+}
+
+/// What to do when a frame holds more than one face
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MultiFacePolicy {
+    /// Refuse to authenticate while a second face is in frame
+    Reject,
+    /// Use the face with the largest box, as `ranking = "area"` would
+    Largest,
+    /// Use the first face in `ranking` order
+    #[default]
+    Ranked,
+    /// Use the face closest to the center of the frame, with the distance
+    /// divided by the detector confidence
+    MostCentral,
+    /// Embed and compare every face; any match authenticates
+    AnyMatch,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -62,6 +538,13 @@ pub struct MatchingConfig {
     pub threshold: f32,
     pub max_frames: u32,
     pub timeout_secs: u64,
+    // Matching frames needed to authenticate, all from the same face track
+    #[serde(default = "default_confirm_frames")]
+    pub confirm_frames: u32,
+}
+
+fn default_confirm_frames() -> u32 {
+    1  // First match wins
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -110,13 +593,24 @@ impl Config {
         Ok(config)
     }
 
-    /// Load configuration from a specific file path
-    fn load_from_path<P: AsRef<std::path::Path>>(path: P) -> Result<Self, ConfigError> {
+    /// Load configuration from a specific file path, without validating it
+    pub fn load_from_path<P: AsRef<std::path::Path>>(path: P) -> Result<Self, ConfigError> {
         let contents = fs::read_to_string(path)?;
         let config: Config = toml::from_str(&contents)?;
         Ok(config)
     }
 
+    /// Validate and write the configuration, creating parent directories
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), ConfigError> {
+        self.validate()?;
+        let contents = toml::to_string_pretty(self)?;
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
     /// Validate configuration values
     pub fn validate(&self) -> Result<(), ConfigError> {
         // Validate camera dimensions
@@ -126,6 +620,31 @@ impl Config {
             ));
         }
 
+        if self.camera.rotate % 90 != 0 || self.camera.rotate >= 360 {
+            return Err(ConfigError::Validation(format!(
+                "rotate must be 0, 90, 180 or 270, not {}",
+                self.camera.rotate
+            )));
+        }
+        if self.camera.crop.is_some_and(|[_, _, width, height]| width == 0 || height == 0) {
+            return Err(ConfigError::Validation(
+                "crop width and height must be non-zero".to_string(),
+            ));
+        }
+        if self.camera.crop.is_some_and(|[x, y, width, height]| {
+            x.checked_add(width).is_none() || y.checked_add(height).is_none()
+        }) {
+            return Err(ConfigError::Validation(
+                "crop extends past the largest possible frame".to_string(),
+            ));
+        }
+
+        if self.camera.settle_tolerance < 0.0 {
+            return Err(ConfigError::Validation(
+                "settle_tolerance must not be negative".to_string(),
+            ));
+        }
+
         // Validate confidence threshold
         if !(0.0..=1.0).contains(&self.detection.confidence_threshold) {
             return Err(ConfigError::Validation(
@@ -133,6 +652,20 @@ impl Config {
             ));
         }
 
+        // Validate detector geometry
+        let input_size = self.detection.input_size;
+        if !(128..=1920).contains(&input_size) || input_size % 32 != 0 {
+            return Err(ConfigError::Validation(format!(
+                "Detection input_size must be a multiple of 32 between 128 and 1920, got {}",
+                input_size
+            )));
+        }
+        if !(self.detection.nms_threshold > 0.0 && self.detection.nms_threshold <= 1.0) {
+            return Err(ConfigError::Validation(
+                "Detection nms_threshold must be greater than 0.0 and at most 1.0".to_string(),
+            ));
+        }
+
         // Validate matching threshold
         if !(-1.0..=1.0).contains(&self.matching.threshold) {
             return Err(ConfigError::Validation(
@@ -146,6 +679,11 @@ impl Config {
                 "Max frames must be greater than 0".to_string(),
             ));
         }
+        if self.matching.confirm_frames == 0 || self.matching.confirm_frames > self.matching.max_frames {
+            return Err(ConfigError::Validation(
+                "confirm_frames must be between 1 and max_frames".to_string(),
+            ));
+        }
 
         // Validate timeout
         if self.matching.timeout_secs == 0 {
@@ -161,6 +699,78 @@ impl Config {
             ));
         }
 
+        // Validate USB vendor:product selector
+        if let Some(usb_id) = &self.camera.usb_id {
+            if crate::device::UsbId::parse(usb_id).is_none() {
+                return Err(ConfigError::Validation(format!(
+                    "USB ID {:?} must be hex \"vendor:product\", e.g. \"04f2:b6d9\"",
+                    usb_id
+                )));
+            }
+        }
+
+        // Validate forced pixel format
+        if let Some(name) = &self.camera.pixel_format {
+            if crate::capture::PixelFormat::from_name(name).is_none() {
+                return Err(ConfigError::Validation(format!(
+                    "Unsupported pixel format {:?} (expected MJPG, YUYV, GREY, Y16, NV12, RGB3 or BGR3)",
+                    name
+                )));
+            }
+        }
+
+        if !(8..=16).contains(&self.camera.y16_bits) {
+            return Err(ConfigError::Validation(
+                "y16_bits must be between 8 and 16".to_string(),
+            ));
+        }
+
+        // Validate hybrid mode
+        if self.camera.use_hybrid_mode && self.camera.ir_device.is_none() {
+            return Err(ConfigError::Validation(
+                "Hybrid mode needs an ir_device for the IR camera".to_string(),
+            ));
+        }
+
+        if self.camera.ir_width == Some(0) || self.camera.ir_height == Some(0) {
+            return Err(ConfigError::Validation(
+                "ir_width and ir_height must be greater than 0".to_string(),
+            ));
+        }
+
+        // Validate exposure fusion
+        if !(1..=8).contains(&self.camera.fusion_frames) {
+            return Err(ConfigError::Validation(
+                "fusion_frames must be between 1 and 8".to_string(),
+            ));
+        }
+        if self.camera.exposure_bracket.len() > 8 {
+            return Err(ConfigError::Validation(
+                "exposure_bracket can have at most 8 steps".to_string(),
+            ));
+        }
+
+        // Validate temporal denoising
+        if self.camera.denoise_frames > 16 {
+            return Err(ConfigError::Validation(
+                "denoise_frames must be at most 16".to_string(),
+            ));
+        }
+        if self.camera.denoise_motion <= 0.0 {
+            return Err(ConfigError::Validation(
+                "denoise_motion must be positive".to_string(),
+            ));
+        }
+
+        // Validate preprocessing
+        self.camera.preprocess.validate("[camera]")?;
+        if let Some(preprocess) = &self.camera.detection_preprocess {
+            preprocess.validate("[camera.detection_preprocess]")?;
+        }
+        if let Some(preprocess) = &self.camera.embedding_preprocess {
+            preprocess.validate("[camera.embedding_preprocess]")?;
+        }
+
         // Validate darkness threshold
         if !(0.0..=100.0).contains(&self.camera.dark_threshold) {
             return Err(ConfigError::Validation(
@@ -168,6 +778,37 @@ impl Config {
             ));
         }
 
+        // Validate remaining quality thresholds
+        if !(0.0..=100.0).contains(&self.camera.overexposure_threshold) {
+            return Err(ConfigError::Validation(
+                "Overexposure threshold must be between 0.0 and 100.0".to_string(),
+            ));
+        }
+        if self.camera.max_noise < 0.0 || self.camera.min_sharpness < 0.0 {
+            return Err(ConfigError::Validation(
+                "max_noise and min_sharpness cannot be negative".to_string(),
+            ));
+        }
+        if !(0.0..=255.0).contains(&self.camera.min_face_brightness)
+            || !(0.0..=255.0).contains(&self.camera.max_face_brightness)
+            || self.camera.min_face_brightness > self.camera.max_face_brightness
+        {
+            return Err(ConfigError::Validation(
+                "Face brightness bounds must satisfy 0 <= min_face_brightness <= max_face_brightness <= 255".to_string(),
+            ));
+        }
+        let pose_limits = [self.camera.max_yaw, self.camera.max_pitch, self.camera.max_roll];
+        if pose_limits.iter().any(|limit| !(0.0..=90.0).contains(limit)) {
+            return Err(ConfigError::Validation(
+                "max_yaw, max_pitch and max_roll must be between 0 and 90 degrees".to_string(),
+            ));
+        }
+        if self.camera.min_eye_distance < 0.0 || !(0.0..1.0).contains(&self.camera.min_face_fraction) {
+            return Err(ConfigError::Validation(
+                "min_eye_distance cannot be negative and min_face_fraction must be between 0.0 and 1.0".to_string(),
+            ));
+        }
+
         Ok(())
     }
 }
@@ -179,12 +820,65 @@ impl Default for Config {
                 device: "/dev/video2".to_string(),  // IR camera
                 width: 640,
                 height: 480,
+                device_name: None,
+                bus_info: None,
+                usb_id: None,
+                prefer_ir: false,
+                use_hybrid_mode: false,         // Single camera
+                ir_device: None,
+                ir_width: None,                 // Same as the RGB camera
+                ir_height: None,
+                ir_transform: None,
+                backend: CaptureBackend::V4l2,
+                pixel_format: None,
+                grayscale: false,               // Decode color formats to RGB
+                y16_bits: 10,
+                raw_format: RawFormat::Yuyv,
+                crop: None,                     // Full frame, as the sensor delivers it
+                rotate: 0,
+                flip_horizontal: false,
+                flip_vertical: false,
+                auto_orientation: false,
                 dark_threshold: 80.0,           // Filter bad IR frames
+                overexposure_level: 240,
+                overexposure_threshold: 15.0,
+                max_noise: 25.0,
+                min_sharpness: 15.0,            // Reject motion-blurred faces
+                min_face_brightness: 30.0,
+                max_face_brightness: 235.0,
+                max_yaw: 30.0,                  // Near-frontal faces only
+                max_pitch: 35.0,
+                max_roll: 30.0,
+                min_eye_distance: 20.0,
+                min_face_fraction: 0.01,
+                shutter_frames: 5,              // Give up fast on a covered camera
+                warmup_frames: 2,               // Drop stale frames after STREAMON
+                settle_frames: 15,              // Wait for auto-exposure, up to 15 frames
+                settle_tolerance: 2.0,
+                force_exposure_fusion: false,   // One frame per capture
+                fusion_frames: 3,
+                exposure_bracket: Vec::new(),
+                exposure_control: default_exposure_control(),
+                denoise_frames: 0,              // Embed each frame on its own
+                denoise_motion: 0.1,
+                preprocess: PreprocessConfig::default(),  // No preprocessing
+                detection_preprocess: None,
+                embedding_preprocess: None,
                 detection_scale: 0.5,           // Half resolution for faster detection
+                controls: BTreeMap::new(),      // Leave driver defaults alone
+                ir_controls: BTreeMap::new(),
             },
             detection: DetectionConfig {
+                backend: DetectionBackend::Scrfd,
                 model_path: PathBuf::from("models/scrfd_500m.onnx"),
                 confidence_threshold: 0.5,
+                scores: DetectionScores::Probabilities,
+                letterbox: true,                // Faces keep their proportions
+                input_size: 640,
+                nms_threshold: 0.4,
+                ranking: DetectionRanking::ConfidenceArea,
+                retry: DetectionRetry::Full,    // Catch faces too small for detection_scale
+                multi_face_policy: MultiFacePolicy::Ranked,
             },
             embedding: EmbeddingConfig {
                 model_path: PathBuf::from("models/arcface_mobilefacenet.onnx"),
@@ -193,6 +887,7 @@ impl Default for Config {
                 threshold: 0.4,
                 max_frames: 10,
                 timeout_secs: 3,
+                confirm_frames: 1,
             },
             runtime: RuntimeConfig {},
             storage: StorageConfig {
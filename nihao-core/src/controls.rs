@@ -0,0 +1,302 @@
+use crate::capture::CaptureError;
+use crate::config::ControlValue;
+use std::collections::BTreeMap;
+use std::path::Path;
+use v4l::control::{Control, Description, Flags, MenuItem, Type, Value};
+use v4l::Device;
+
+/// A V4L2 control with its range and current value
+#[derive(Debug, Clone)]
+pub struct ControlInfo {
+    pub id: u32,
+    /// Name as reported by the driver, e.g. "Exposure Time, Absolute"
+    pub name: String,
+    /// Key accepted in `[camera.controls]`, e.g. "exposure_time_absolute"
+    pub key: String,
+    pub kind: String,
+    pub minimum: i64,
+    pub maximum: i64,
+    pub step: u64,
+    pub default: i64,
+    pub value: Option<i64>,
+    /// Menu entries as (value, label)
+    pub menu: Vec<(u32, String)>,
+    pub read_only: bool,
+    /// Currently ignored by the driver, e.g. exposure while auto-exposure is on
+    pub inactive: bool,
+}
+
+/// Turn a driver control name into a config key
+///
+/// Lowercases and collapses everything but letters and digits into single
+/// underscores, the same keys `v4l2-ctl --list-ctrls` prints.
+pub fn control_key(name: &str) -> String {
+    let mut key = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            key.push(c.to_ascii_lowercase());
+        } else if !key.is_empty() && !key.ends_with('_') {
+            key.push('_');
+        }
+    }
+    key.trim_end_matches('_').to_string()
+}
+
+/// Parse a numeric control ID key such as `"0x009a0902"` or `"10094850"`
+fn parse_control_id(key: &str) -> Option<u32> {
+    match key.strip_prefix("0x").or_else(|| key.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => key.parse().ok(),
+    }
+}
+
+fn menu_label(item: &MenuItem) -> String {
+    match item {
+        MenuItem::Name(name) => name.clone(),
+        MenuItem::Value(value) => value.to_string(),
+    }
+}
+
+/// Find the control a `[camera.controls]` key refers to
+fn find<'a>(descriptions: &'a [Description], key: &str) -> Option<&'a Description> {
+    let id = parse_control_id(key);
+    let key = control_key(key);
+    descriptions
+        .iter()
+        .filter(|d| d.typ != Type::CtrlClass)
+        .find(|d| Some(d.id) == id || control_key(&d.name) == key)
+}
+
+/// Convert a configured value to the integer the driver expects, checking its range
+fn resolve_value(desc: &Description, value: &ControlValue) -> Result<i64, String> {
+    let raw = match value {
+        ControlValue::Bool(b) => *b as i64,
+        ControlValue::Int(v) => *v,
+        ControlValue::Name(label) => {
+            let items = desc.items.as_deref().unwrap_or_default();
+            items
+                .iter()
+                .find(|(_, item)| menu_label(item).eq_ignore_ascii_case(label.trim()))
+                .map(|(index, _)| *index as i64)
+                .ok_or_else(|| {
+                    let labels: Vec<String> = items.iter().map(|(_, item)| menu_label(item)).collect();
+                    format!("no menu entry {:?} (options: {})", label, labels.join(", "))
+                })?
+        }
+    };
+
+    if raw < desc.minimum || raw > desc.maximum {
+        return Err(format!(
+            "{} is outside {}..={}",
+            raw, desc.minimum, desc.maximum
+        ));
+    }
+    if let Some(items) = &desc.items {
+        if !items.iter().any(|(index, _)| *index as i64 == raw) {
+            return Err(format!("{} is not a menu entry", raw));
+        }
+    }
+    Ok(raw)
+}
+
+/// Apply `[camera.controls]` to an open device
+///
+/// Mode switches (menus and booleans such as auto-exposure) are written
+/// before plain values, because drivers ignore exposure or gain while the
+/// matching auto mode is still on. A control that cannot be set is logged
+/// and skipped so one bad entry doesn't take the camera down.
+pub fn apply(device: &Device, controls: &BTreeMap<String, ControlValue>) -> Result<(), CaptureError> {
+    if controls.is_empty() {
+        return Ok(());
+    }
+
+    let descriptions = device
+        .query_controls()
+        .map_err(|e| CaptureError::DeviceOpen(format!("Failed to query controls: {}", e)))?;
+
+    let mut pending = Vec::new();
+    for (key, value) in controls {
+        let Some(desc) = find(&descriptions, key) else {
+            log::warn!("Camera has no control {:?} (see `nihao controls`)", key);
+            continue;
+        };
+        if desc.flags.contains(Flags::READ_ONLY) {
+            log::warn!("Control {:?} is read-only", desc.name);
+            continue;
+        }
+        match resolve_value(desc, value) {
+            Ok(raw) => pending.push((desc, raw)),
+            Err(e) => log::warn!("Control {:?}: {}", desc.name, e),
+        }
+    }
+
+    // Modes first, then the values they unlock
+    pending.sort_by_key(|(desc, _)| !matches!(desc.typ, Type::Menu | Type::IntegerMenu | Type::Boolean));
+
+    for (desc, raw) in pending {
+        let value = match desc.typ {
+            Type::Boolean => Value::Boolean(raw != 0),
+            _ => Value::Integer(raw),
+        };
+        match device.set_control(Control { id: desc.id, value }) {
+            Ok(()) => log::debug!("Set control {:?} = {}", desc.name, raw),
+            Err(e) => log::warn!("Failed to set control {:?} = {}: {}", desc.name, raw, e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Set one control by name or ID, e.g. for exposure bracketing
+pub fn set(device: &Device, key: &str, value: &ControlValue) -> Result<(), CaptureError> {
+    let descriptions = device
+        .query_controls()
+        .map_err(|e| CaptureError::Capture(format!("Failed to query controls: {}", e)))?;
+    let desc = find(&descriptions, key)
+        .ok_or_else(|| CaptureError::Capture(format!("Camera has no control {:?}", key)))?;
+    let raw = resolve_value(desc, value)
+        .map_err(|e| CaptureError::Capture(format!("Control {:?}: {}", desc.name, e)))?;
+
+    let value = match desc.typ {
+        Type::Boolean => Value::Boolean(raw != 0),
+        _ => Value::Integer(raw),
+    };
+    device
+        .set_control(Control { id: desc.id, value })
+        .map_err(|e| CaptureError::Capture(format!("Failed to set control {:?} = {}: {}", desc.name, raw, e)))
+}
+
+/// Read the current value of one control by name or ID
+pub fn get(device: &Device, key: &str) -> Result<i64, CaptureError> {
+    let descriptions = device
+        .query_controls()
+        .map_err(|e| CaptureError::Capture(format!("Failed to query controls: {}", e)))?;
+    let desc = find(&descriptions, key)
+        .ok_or_else(|| CaptureError::Capture(format!("Camera has no control {:?}", key)))?;
+
+    match device.control(desc.id).map(|c| c.value) {
+        Ok(Value::Integer(v)) => Ok(v),
+        Ok(Value::Boolean(b)) => Ok(b as i64),
+        Ok(_) => Err(CaptureError::Capture(format!("Control {:?} has no readable value", desc.name))),
+        Err(e) => Err(CaptureError::Capture(format!("Failed to read control {:?}: {}", desc.name, e))),
+    }
+}
+
+/// List the controls of a device with their ranges and current values
+pub fn list(device_path: &Path) -> Result<Vec<ControlInfo>, CaptureError> {
+    let device = Device::with_path(device_path)
+        .map_err(|e| CaptureError::DeviceOpen(format!("{}: {}", device_path.display(), e)))?;
+    let descriptions = device
+        .query_controls()
+        .map_err(|e| CaptureError::DeviceOpen(format!("Failed to query controls: {}", e)))?;
+
+    Ok(descriptions
+        .into_iter()
+        .filter(|d| d.typ != Type::CtrlClass && !d.flags.contains(Flags::DISABLED))
+        .map(|d| {
+            // Write-only and button controls have no readable value
+            let value = match device.control(d.id).map(|c| c.value) {
+                Ok(Value::Integer(v)) => Some(v),
+                Ok(Value::Boolean(b)) => Some(b as i64),
+                _ => None,
+            };
+            ControlInfo {
+                id: d.id,
+                key: control_key(&d.name),
+                kind: d.typ.to_string().to_lowercase(),
+                minimum: d.minimum,
+                maximum: d.maximum,
+                step: d.step,
+                default: d.default,
+                value,
+                menu: d
+                    .items
+                    .iter()
+                    .flatten()
+                    .map(|(index, item)| (*index, menu_label(item)))
+                    .collect(),
+                read_only: d.flags.contains(Flags::READ_ONLY),
+                inactive: d.flags.contains(Flags::INACTIVE),
+                name: d.name,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auto_exposure() -> Description {
+        Description {
+            id: 0x009a0901,
+            typ: Type::Menu,
+            name: "Auto Exposure".to_string(),
+            minimum: 0,
+            maximum: 3,
+            step: 1,
+            default: 3,
+            flags: Flags::empty(),
+            items: Some(vec![
+                (1, MenuItem::Name("Manual Mode".to_string())),
+                (3, MenuItem::Name("Aperture Priority Mode".to_string())),
+            ]),
+        }
+    }
+
+    fn exposure() -> Description {
+        Description {
+            id: 0x009a0902,
+            typ: Type::Integer,
+            name: "Exposure Time, Absolute".to_string(),
+            minimum: 3,
+            maximum: 2047,
+            step: 1,
+            default: 250,
+            flags: Flags::INACTIVE,
+            items: None,
+        }
+    }
+
+    #[test]
+    fn test_control_key() {
+        assert_eq!(control_key("Exposure Time, Absolute"), "exposure_time_absolute");
+        assert_eq!(control_key("White Balance Temperature, Auto"), "white_balance_temperature_auto");
+        assert_eq!(control_key("Gain"), "gain");
+        assert_eq!(control_key(" Power Line Frequency "), "power_line_frequency");
+    }
+
+    #[test]
+    fn test_find_by_name_or_id() {
+        let descriptions = vec![auto_exposure(), exposure()];
+        assert_eq!(find(&descriptions, "exposure_time_absolute").unwrap().id, 0x009a0902);
+        assert_eq!(find(&descriptions, "Auto Exposure").unwrap().id, 0x009a0901);
+        assert_eq!(find(&descriptions, "0x009a0902").unwrap().id, 0x009a0902);
+        assert_eq!(find(&descriptions, "10094849").unwrap().id, 0x009a0901);
+        assert!(find(&descriptions, "gain").is_none());
+    }
+
+    #[test]
+    fn test_config_values() {
+        let controls: BTreeMap<String, ControlValue> = toml::from_str(
+            "auto_exposure = \"Manual Mode\"\nexposure_time_absolute = 300\nbacklight_compensation = false\n",
+        )
+        .unwrap();
+        assert_eq!(controls["auto_exposure"], ControlValue::Name("Manual Mode".to_string()));
+        assert_eq!(controls["exposure_time_absolute"], ControlValue::Int(300));
+        assert_eq!(controls["backlight_compensation"], ControlValue::Bool(false));
+    }
+
+    #[test]
+    fn test_resolve_value() {
+        let ae = auto_exposure();
+        assert_eq!(resolve_value(&ae, &ControlValue::Name("manual mode".to_string())), Ok(1));
+        assert_eq!(resolve_value(&ae, &ControlValue::Int(3)), Ok(3));
+        assert!(resolve_value(&ae, &ControlValue::Int(2)).is_err());
+        assert!(resolve_value(&ae, &ControlValue::Name("shutter".to_string())).is_err());
+
+        let exp = exposure();
+        assert_eq!(resolve_value(&exp, &ControlValue::Int(300)), Ok(300));
+        assert!(resolve_value(&exp, &ControlValue::Int(5000)).is_err());
+    }
+}
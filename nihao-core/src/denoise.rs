@@ -0,0 +1,197 @@
+use crate::capture;
+use crate::config::CameraConfig;
+use crate::detect::{BoundingBox, DetectedFace};
+use image::{DynamicImage, GrayImage, RgbImage};
+use std::collections::VecDeque;
+
+/// Averages aligned face crops over consecutive accepted frames
+///
+/// Crops come out of `FaceAligner`, so they are already registered on the
+/// landmarks and average pixel for pixel. Sensor noise is independent from
+/// frame to frame and drops with the square root of the frame count, while
+/// the face stays put. When the face box moves more than `denoise_motion`
+/// of its width, or changes size by as much, the history is dropped so a
+/// new pose is never blended with the old one; so is a face from another
+/// track, so two people are never blended either.
+pub struct TemporalDenoiser {
+    frames: usize,
+    motion: f32,
+    history: VecDeque<DynamicImage>,
+    last_bbox: Option<BoundingBox>,
+    last_track: Option<u32>,
+}
+
+impl TemporalDenoiser {
+    pub fn new(config: &CameraConfig) -> Self {
+        Self {
+            frames: config.denoise_frames.max(1) as usize,
+            motion: config.denoise_motion,
+            history: VecDeque::new(),
+            last_bbox: None,
+            last_track: None,
+        }
+    }
+
+    /// Whether more than one frame is averaged
+    pub fn is_enabled(&self) -> bool {
+        self.frames > 1
+    }
+
+    /// Crops in the current average
+    pub fn depth(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Whether the average covers `denoise_frames` crops
+    pub fn is_full(&self) -> bool {
+        !self.is_enabled() || self.history.len() >= self.frames
+    }
+
+    /// Add the aligned crop of `face` and return the average
+    pub fn push(&mut self, aligned: DynamicImage, face: &DetectedFace) -> DynamicImage {
+        if !self.is_enabled() {
+            return aligned;
+        }
+        let bbox = &face.bbox;
+
+        if face.track != self.last_track && !self.history.is_empty() {
+            log::debug!("Another face track, restarting temporal average after {} frames", self.history.len());
+            self.history.clear();
+        }
+        if let Some(last) = &self.last_bbox {
+            if self.moved(last, bbox) {
+                log::debug!("Face moved, restarting temporal average after {} frames", self.history.len());
+                self.history.clear();
+            }
+        }
+        // A format change (e.g. hybrid mode switching cameras) can't be blended
+        if self.history.front().is_some_and(|first| !same_layout(first, &aligned)) {
+            self.history.clear();
+        }
+        self.last_bbox = Some(bbox.clone());
+        self.last_track = face.track;
+
+        if self.history.len() >= self.frames {
+            self.history.pop_front();
+        }
+        self.history.push_back(aligned);
+        average(self.history.make_contiguous())
+    }
+
+    fn moved(&self, last: &BoundingBox, bbox: &BoundingBox) -> bool {
+        let width = last.width.max(1.0);
+        let dx = (bbox.x + bbox.width / 2.0) - (last.x + last.width / 2.0);
+        let dy = (bbox.y + bbox.height / 2.0) - (last.y + last.height / 2.0);
+        let shift = (dx * dx + dy * dy).sqrt() / width;
+        let resize = (bbox.width - last.width).abs() / width;
+        shift > self.motion || resize > self.motion
+    }
+}
+
+fn same_layout(a: &DynamicImage, b: &DynamicImage) -> bool {
+    a.color() == b.color() && a.width() == b.width() && a.height() == b.height()
+}
+
+/// Pixel-wise mean, gray if every crop is gray
+fn average(crops: &[DynamicImage]) -> DynamicImage {
+    if crops.len() == 1 {
+        return crops[0].clone();
+    }
+    let (width, height) = (crops[0].width(), crops[0].height());
+
+    if crops.iter().all(|crop| crop.as_luma8().is_some()) {
+        let planes: Vec<&[u8]> = crops.iter().map(|crop| crop.as_bytes()).collect();
+        GrayImage::from_raw(width, height, mean(&planes)).unwrap().into()
+    } else {
+        let rgb: Vec<_> = crops.iter().map(capture::rgb).collect();
+        let planes: Vec<&[u8]> = rgb.iter().map(|crop| crop.as_raw().as_slice()).collect();
+        RgbImage::from_raw(width, height, mean(&planes)).unwrap().into()
+    }
+}
+
+fn mean(planes: &[&[u8]]) -> Vec<u8> {
+    let count = planes.len() as u32;
+    (0..planes[0].len())
+        .map(|i| {
+            let sum: u32 = planes.iter().map(|plane| plane[i] as u32).sum();
+            ((sum + count / 2) / count) as u8
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::detect::FacialLandmarks;
+    use image::Luma;
+
+    fn denoiser(frames: u32) -> TemporalDenoiser {
+        let mut config = Config::default().camera;
+        config.denoise_frames = frames;
+        TemporalDenoiser::new(&config)
+    }
+
+    fn face(x: f32) -> DetectedFace {
+        let bbox = BoundingBox {
+            x,
+            y: 100.0,
+            width: 200.0,
+            height: 240.0,
+        };
+        DetectedFace { landmarks: FacialLandmarks::estimate(&bbox), bbox, confidence: 0.9, track: Some(0) }
+    }
+
+    fn crop(value: u8) -> DynamicImage {
+        GrayImage::from_pixel(4, 4, Luma([value])).into()
+    }
+
+    fn value(image: &DynamicImage) -> u8 {
+        image.as_luma8().unwrap().get_pixel(0, 0).0[0]
+    }
+
+    #[test]
+    fn test_averages_last_frames() {
+        let mut denoiser = denoiser(3);
+        assert_eq!(value(&denoiser.push(crop(90), &face(100.0))), 90);
+        assert_eq!(value(&denoiser.push(crop(110), &face(102.0))), 100);
+        assert_eq!(value(&denoiser.push(crop(130), &face(104.0))), 110);
+        assert!(denoiser.is_full());
+
+        // Oldest crop drops out
+        assert_eq!(value(&denoiser.push(crop(150), &face(106.0))), 130);
+        assert_eq!(denoiser.depth(), 3);
+    }
+
+    #[test]
+    fn test_motion_resets() {
+        let mut denoiser = denoiser(4);
+        denoiser.push(crop(50), &face(100.0));
+        denoiser.push(crop(70), &face(105.0));
+        assert_eq!(denoiser.depth(), 2);
+
+        // Half a face width to the side
+        assert_eq!(value(&denoiser.push(crop(200), &face(205.0))), 200);
+        assert_eq!(denoiser.depth(), 1);
+
+        // RGB crop can't join a gray history
+        let rgb = DynamicImage::ImageRgb8(RgbImage::new(4, 4));
+        denoiser.push(rgb, &face(205.0));
+        assert_eq!(denoiser.depth(), 1);
+
+        // Nor can someone else's face in the same place
+        let mut other = face(205.0);
+        other.track = Some(1);
+        denoiser.push(crop(90), &face(205.0));
+        assert_eq!(value(&denoiser.push(crop(30), &other)), 30);
+    }
+
+    #[test]
+    fn test_disabled_passes_through() {
+        let mut denoiser = denoiser(0);
+        assert!(!denoiser.is_enabled());
+        assert!(denoiser.is_full());
+        denoiser.push(crop(10), &face(0.0));
+        assert_eq!(value(&denoiser.push(crop(30), &face(0.0))), 30);
+    }
+}
@@ -1,9 +1,8 @@
-use crate::config::RuntimeConfig;
+use crate::config::{DetectionBackend, DetectionConfig, DetectionRanking, MultiFacePolicy, RuntimeConfig};
 use crate::runtime::OnnxRuntime;
-use image::{imageops, RgbImage};
+use image::{imageops, DynamicImage};
 use ort::session::Session;
-use ort::value::Value;
-use std::path::Path;
+use ort::value::DynValue;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -14,395 +13,457 @@ pub enum DetectionError {
     Inference(String),
     #[error("No faces detected")]
     NoFaces,
+    #[error("Unsupported detection model: {0}")]
+    UnsupportedModel(String),
     #[error("Runtime error: {0}")]
     Runtime(#[from] crate::runtime::RuntimeError),
 }
 
-const INPUT_SIZE: u32 = 640;
+/// Channel order of the model input, as indices into RGB
+pub(crate) const RGB: [usize; 3] = [0, 1, 2];
+pub(crate) const BGR: [usize; 3] = [2, 1, 0];
 
-/// SCRFD uses 3 feature pyramid levels with different strides
-const FEATURE_STRIDES: [usize; 3] = [8, 16, 32];
-const NUM_ANCHORS: usize = 2; // SCRFD uses 2 anchors per location
+/// Name and shape of a model input or output; dynamic dimensions are -1
+pub(crate) type Outlet = (String, Vec<i64>);
 
-#[derive(Debug, Clone)]
-pub struct BoundingBox {
-    pub x: f32,
-    pub y: f32,
-    pub width: f32,
-    pub height: f32,
+/// A box `(x, y, width, height)` and five landmarks, in model input coordinates
+pub(crate) type Decoded = ((f32, f32, f32, f32), [(f32, f32); 5]);
+
+/// A face detection model
+///
+/// Implementations take a frame of any size and return the faces in its
+/// coordinates, best first, each with the five landmarks alignment needs.
+pub trait FaceDetector: Send {
+    /// Detect faces in an image
+    fn detect(&mut self, image: &DynamicImage) -> Result<Vec<DetectedFace>, DetectionError>;
+
+    /// Set confidence threshold for testing/debugging
+    fn set_confidence_threshold(&mut self, threshold: f32);
 }
 
-impl BoundingBox {
-    pub fn area(&self) -> f32 {
-        self.width * self.height
-    }
+/// Load the detector selected by `[detection] backend`
+pub fn load(
+    config: &DetectionConfig,
+    runtime: &OnnxRuntime,
+    runtime_config: &RuntimeConfig,
+) -> Result<Box<dyn FaceDetector>, DetectionError> {
+    let session = runtime
+        .create_session(&config.model_path, runtime_config)
+        .map_err(|e| DetectionError::ModelLoad(e.to_string()))?;
+
+    let detector: Box<dyn FaceDetector> = match config.backend {
+        DetectionBackend::Scrfd => Box::new(crate::scrfd::ScrfdDetector::new(session, config)?),
+        DetectionBackend::Yunet => Box::new(crate::yunet::YuNetDetector::new(session, config)?),
+        DetectionBackend::Retinaface => Box::new(crate::retinaface::RetinaFaceDetector::new(session, config)?),
+    };
+
+    log::info!("Face detector: {:?} ({})", config.backend, config.model_path.display());
+    Ok(detector)
+}
 
-    pub fn iou(&self, other: &BoundingBox) -> f32 {
-        let x1 = self.x.max(other.x);
-        let y1 = self.y.max(other.y);
-        let x2 = (self.x + self.width).min(other.x + other.width);
-        let y2 = (self.y + self.height).min(other.y + other.height);
+/// Names and shapes of a session's inputs and outputs
+pub(crate) fn outlets(session: &Session) -> (Vec<Outlet>, Vec<Outlet>) {
+    let list = |outlets: &[ort::value::Outlet]| -> Vec<Outlet> {
+        outlets
+            .iter()
+            .map(|o| {
+                let shape = o.dtype().tensor_shape().map(|s| s.to_vec()).unwrap_or_default();
+                (o.name().to_string(), shape)
+            })
+            .collect()
+    };
+    (list(session.inputs()), list(session.outputs()))
+}
 
-        let intersection = (x2 - x1).max(0.0) * (y2 - y1).max(0.0);
-        let union = self.area() + other.area() - intersection;
+/// Name and fixed `(width, height)` of a model's single image input
+///
+/// The size is `None` when the model takes any size.
+pub(crate) fn image_input(inputs: &[Outlet]) -> Result<(String, Option<(u32, u32)>), DetectionError> {
+    let [(name, shape)] = inputs else {
+        return Err(DetectionError::UnsupportedModel(format!(
+            "expected one input, found {}",
+            inputs.len()
+        )));
+    };
+    if shape.len() != 4 || !matches!(shape[1], 3 | -1) {
+        return Err(DetectionError::UnsupportedModel(format!(
+            "input {:?} has shape {:?}, expected [1, 3, height, width]",
+            name, shape
+        )));
+    }
+    let size = match (shape[3], shape[2]) {
+        (w, h) if w > 0 && h > 0 => Some((w as u32, h as u32)),
+        _ => None,
+    };
+    Ok((name.clone(), size))
+}
 
-        if union > 0.0 {
-            intersection / union
-        } else {
-            0.0
+/// Size frames are resized to: the model's own, or `[detection] input_size`
+/// for models that take any size
+pub(crate) fn input_size(model: Option<(u32, u32)>, config: &DetectionConfig) -> (u32, u32) {
+    let configured = (config.input_size, config.input_size);
+    match model {
+        Some(size) if size != configured => {
+            log::info!(
+                "Detection model has a fixed {}x{} input; input_size = {} does not apply",
+                size.0,
+                size.1,
+                config.input_size
+            );
+            size
         }
+        Some(size) => size,
+        None => configured,
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct FacialLandmarks {
-    pub left_eye: (f32, f32),
-    pub right_eye: (f32, f32),
-    pub nose: (f32, f32),
-    pub left_mouth: (f32, f32),
-    pub right_mouth: (f32, f32),
+/// Feature map side at a stride; strided convolutions round up
+pub(crate) fn feature_size(size: u32, stride: usize) -> usize {
+    (size as usize).div_ceil(stride)
 }
 
-#[derive(Debug, Clone)]
-pub struct DetectedFace {
-    pub bbox: BoundingBox,
-    pub landmarks: FacialLandmarks,
-    pub confidence: f32,
+/// Float data of a model output
+pub(crate) fn tensor<'a>(value: &'a DynValue, what: &str) -> Result<&'a [f32], DetectionError> {
+    value
+        .try_extract_tensor::<f32>()
+        .map(|(_, data)| data)
+        .map_err(|e| DetectionError::Inference(format!("Failed to extract {}: {}", what, e)))
 }
 
-pub struct FaceDetector {
-    session: Session,
-    confidence_threshold: f32,
+/// Where a frame sits inside the model input
+///
+/// Letterboxing scales both axes alike and centers the frame, padding the
+/// rest; stretching scales each axis to fill the input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct InputMapping {
+    pub(crate) scale_x: f32,
+    pub(crate) scale_y: f32,
+    pub(crate) pad_x: u32,
+    pub(crate) pad_y: u32,
 }
 
-impl FaceDetector {
-    /// Create a new face detector from model path
-    pub fn new<P: AsRef<Path>>(
-        model_path: P,
-        runtime: &OnnxRuntime,
-        runtime_config: &RuntimeConfig,
-        confidence_threshold: f32,
-    ) -> Result<Self, DetectionError> {
-        let session = runtime
-            .create_session(model_path, runtime_config)
-            .map_err(|e| DetectionError::ModelLoad(e.to_string()))?;
-
-        Ok(Self {
-            session,
-            confidence_threshold,
-        })
+impl InputMapping {
+    pub(crate) fn new(frame: (u32, u32), input: (u32, u32), letterbox: bool) -> Self {
+        let scale_x = input.0 as f32 / frame.0.max(1) as f32;
+        let scale_y = input.1 as f32 / frame.1.max(1) as f32;
+        if !letterbox {
+            return Self { scale_x, scale_y, pad_x: 0, pad_y: 0 };
+        }
+
+        let scale = scale_x.min(scale_y);
+        let (width, height) = Self { scale_x: scale, scale_y: scale, pad_x: 0, pad_y: 0 }.scaled(frame);
+        Self {
+            scale_x: scale,
+            scale_y: scale,
+            pad_x: (input.0 - width) / 2,
+            pad_y: (input.1 - height) / 2,
+        }
     }
 
-    /// Set confidence threshold for testing/debugging
-    pub fn set_confidence_threshold(&mut self, threshold: f32) {
-        self.confidence_threshold = threshold;
+    /// Size of the frame once scaled into the input
+    pub(crate) fn scaled(&self, frame: (u32, u32)) -> (u32, u32) {
+        (
+            ((frame.0 as f32 * self.scale_x).round() as u32).max(1),
+            ((frame.1 as f32 * self.scale_y).round() as u32).max(1),
+        )
     }
 
-    /// Detect faces in an image
-    pub fn detect(&mut self, image: &RgbImage) -> Result<Vec<DetectedFace>, DetectionError> {
-        // Preprocess image
-        let (input_tensor, scale_x, scale_y) = self.preprocess(image);
-
-        // Convert to Value
-        let input_value = Value::from_array(input_tensor)
-            .map_err(|e| DetectionError::Inference(format!("Failed to create input tensor: {}", e)))?;
-
-        // Run inference using ort 2.0 API
-        // SCRFD models expect the input tensor to be named "input.1"
-        let outputs = self
-            .session
-            .run(ort::inputs!["input.1" => input_value])
-            .map_err(|e| DetectionError::Inference(e.to_string()))?;
-
-        // SCRFD outputs: scores, bbox_preds, kps_preds for each stride level
-        // Typically 3 output groups (one per stride: 8, 16, 32)
-        log::debug!("SCRFD model has {} outputs", outputs.len());
-
-        // Validate output count
-        if outputs.len() != 9 {
-            log::warn!(
-                "Expected 9 outputs (3 strides × 3 tensors), got {}. Output parsing may fail.",
-                outputs.len()
-            );
+    /// Model input coordinates back to frame coordinates
+    pub(crate) fn unmap(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        ((x - self.pad_x as f32) / self.scale_x, (y - self.pad_y as f32) / self.scale_y)
+    }
+
+    /// Box in model input coordinates, as `(x, y, width, height)`, back in the frame
+    pub(crate) fn unmap_box(&self, (x, y, width, height): (f32, f32, f32, f32)) -> BoundingBox {
+        let (x, y) = self.unmap((x, y));
+        BoundingBox {
+            x,
+            y,
+            width: width / self.scale_x,
+            height: height / self.scale_y,
         }
+    }
+}
 
-        // Log all output shapes for debugging
-        for i in 0..outputs.len() {
-            if let Ok((shape, _)) = outputs[i].try_extract_tensor::<f32>() {
-                log::debug!("Output {}: shape = {:?}", i, shape);
-            }
+/// Build the NCHW input tensor for a detection model
+///
+/// The frame is scaled into `input` (letterboxed or stretched), padding is
+/// black, and `normalize` turns each pixel value into the model's range
+/// given the model channel it lands in. `order` picks the RGB channel for
+/// each model channel; gray frames fill every channel from their one plane.
+pub(crate) fn input_tensor(
+    image: &DynamicImage,
+    input: (u32, u32),
+    letterbox: bool,
+    order: [usize; 3],
+    normalize: impl Fn(u8, usize) -> f32,
+) -> (([usize; 4], Vec<f32>), InputMapping) {
+    let (input_width, input_height) = input;
+    let mapping = InputMapping::new((image.width(), image.height()), input, letterbox);
+
+    // Scale into the input, keeping gray frames single-channel
+    let (width, height) = mapping.scaled((image.width(), image.height()));
+    let resized = image.resize_exact(width, height, imageops::FilterType::Triangle);
+
+    let plane = (input_width * input_height) as usize;
+    let mut input_data = vec![0.0; plane * 3];
+    let offset = (mapping.pad_x as usize, mapping.pad_y as usize);
+    let rgb;
+    let (pixels, channels) = match &resized {
+        DynamicImage::ImageLuma8(gray) => (gray.as_raw().as_slice(), 1),
+        other => {
+            rgb = crate::capture::rgb(other);
+            (rgb.as_raw().as_slice(), 3)
         }
+    };
+    for (c, channel) in input_data.chunks_exact_mut(plane).enumerate() {
+        let source = if channels == 1 { 0 } else { order[c] };
+        let normalize = |value: u8| normalize(value, c);
+        channel.fill(normalize(0));
+        blit(channel, input_width as usize, pixels, width as usize, channels, source, offset, normalize);
+    }
 
-        let mut detections = Vec::new();
+    // Return as tuple (shape, data) for ONNX Runtime
+    let shape = [1, 3, input_height as usize, input_width as usize];
+    ((shape, input_data), mapping)
+}
 
-        // SCRFD typically outputs in groups of 3: (score, bbox, kps) for each stride
-        // With 3 strides and 2 anchors per location
-        for stride_idx in 0..FEATURE_STRIDES.len() {
-            let stride = FEATURE_STRIDES[stride_idx];
-            let feat_size = INPUT_SIZE as usize / stride;
+/// Copy one channel of an interleaved image into a model input plane,
+/// `offset` pixels from its top-left corner
+#[allow(clippy::too_many_arguments)]
+fn blit(
+    plane: &mut [f32],
+    plane_width: usize,
+    pixels: &[u8],
+    width: usize,
+    channels: usize,
+    channel: usize,
+    (offset_x, offset_y): (usize, usize),
+    normalize: impl Fn(u8) -> f32,
+) {
+    for (y, row) in pixels.chunks_exact(width * channels).enumerate() {
+        let start = (offset_y + y) * plane_width + offset_x;
+        for (out, pixel) in plane[start..start + width].iter_mut().zip(row.chunks_exact(channels)) {
+            *out = normalize(pixel[channel]);
+        }
+    }
+}
 
-            // Generate anchors for this stride
-            let anchors = Self::generate_anchors(stride, feat_size);
-            let num_anchors_per_loc = NUM_ANCHORS;
+/// Drop detections overlapping a better one by more than `nms_threshold`
+/// and order the rest best first by `ranking`
+pub(crate) fn finish(
+    detections: Vec<DetectedFace>,
+    nms_threshold: f32,
+    ranking: DetectionRanking,
+) -> Result<Vec<DetectedFace>, DetectionError> {
+    log::debug!("Found {} detections before NMS", detections.len());
+
+    if detections.is_empty() {
+        return Err(DetectionError::NoFaces);
+    }
 
-            // Output indices: ALL scores (0-2), ALL bboxes (3-5), ALL keypoints (6-8)
-            let score_idx = stride_idx;          // 0, 1, 2
-            let bbox_idx = stride_idx + 3;        // 3, 4, 5
-            let kps_idx = stride_idx + 6;         // 6, 7, 8
+    let mut detections = nms(detections, nms_threshold);
 
-            if score_idx >= outputs.len() || bbox_idx >= outputs.len() || kps_idx >= outputs.len() {
-                log::warn!("Missing outputs for stride {}, skipping", stride);
-                continue;
-            }
+    detections.sort_by(|a, b| score(b, ranking).total_cmp(&score(a, ranking)));
 
-            let (score_shape, score_data) = outputs[score_idx]
-                .try_extract_tensor::<f32>()
-                .map_err(|e| DetectionError::Inference(format!("Failed to extract scores for stride {}: {}", stride, e)))?;
-
-            let (_bbox_shape, bbox_data) = outputs[bbox_idx]
-                .try_extract_tensor::<f32>()
-                .map_err(|e| DetectionError::Inference(format!("Failed to extract bboxes for stride {}: {}", stride, e)))?;
-
-            let (_kps_shape, kps_data) = outputs[kps_idx]
-                .try_extract_tensor::<f32>()
-                .map_err(|e| DetectionError::Inference(format!("Failed to extract landmarks for stride {}: {}", stride, e)))?;
-
-            log::debug!("Stride {}: score_shape={:?}, {} anchors", stride, score_shape, anchors.len());
-
-            // Process each anchor location
-            for (anchor_idx, &anchor) in anchors.iter().enumerate() {
-                for anchor_num in 0..num_anchors_per_loc {
-                    let idx = anchor_idx * num_anchors_per_loc + anchor_num;
-
-                    // Score is typically at index [idx, 0] or just [idx]
-                    // Apply sigmoid to convert logits to probabilities [0, 1]
-                    let raw_score = if idx < score_data.len() {
-                        score_data[idx]
-                    } else {
-                        continue;
-                    };
-                    let score = 1.0 / (1.0 + (-raw_score).exp());
-
-                    // VALIDATION: Check for abnormal confidence scores
-                    if score > 1.0 {
-                        log::warn!(
-                            "Abnormal detection confidence: {:.2} (expected 0.0-1.0). \
-                             This may indicate preprocessing issues with IR camera input.",
-                            score
-                        );
-
-                        // Log image statistics for debugging
-                        log::warn!(
-                            "If this persists, try: (1) Increase CLAHE clip_limit, \
-                             (2) Check camera exposure, (3) Verify image preprocessing"
-                        );
-
-                        // Skip this detection as it's likely a false positive
-                        continue;
-                    }
-
-                    if score < self.confidence_threshold {
-                        continue;
-                    }
-
-                    // Decode bounding box (4 values: dx1, dy1, dx2, dy2)
-                    let bbox_offset = idx * 4;
-                    if bbox_offset + 4 > bbox_data.len() {
-                        continue;
-                    }
-                    let bbox_pred = &bbox_data[bbox_offset..bbox_offset + 4];
-                    let (x, y, w, h) = Self::decode_bbox(anchor, bbox_pred, stride as f32);
-
-                    log::trace!(
-                        "Detection: stride={}, anchor=({:.1},{:.1}), bbox_pred=[{:.3},{:.3},{:.3},{:.3}], decoded=({:.1},{:.1},{:.1},{:.1}), score={:.3}",
-                        stride, anchor.0, anchor.1,
-                        bbox_pred[0], bbox_pred[1], bbox_pred[2], bbox_pred[3],
-                        x, y, w, h, score
-                    );
-
-                    // Decode landmarks (10 values: 5 points x 2 coords)
-                    let kps_offset = idx * 10;
-                    if kps_offset + 10 > kps_data.len() {
-                        continue;
-                    }
-                    let kps_pred = &kps_data[kps_offset..kps_offset + 10];
-                    let landmarks = Self::decode_landmarks(anchor, kps_pred, stride as f32);
-
-                    // Scale back to original image size
-                    let final_x = x / scale_x;
-                    let final_y = y / scale_y;
-                    let final_w = w / scale_x;
-                    let final_h = h / scale_y;
-
-                    log::trace!(
-                        "Final bbox: ({:.1},{:.1},{:.1},{:.1}) [scale_x={:.3}, scale_y={:.3}]",
-                        final_x, final_y, final_w, final_h, scale_x, scale_y
-                    );
-
-                    detections.push(DetectedFace {
-                        bbox: BoundingBox {
-                            x: final_x,
-                            y: final_y,
-                            width: final_w,
-                            height: final_h,
-                        },
-                        landmarks: FacialLandmarks {
-                            left_eye: (landmarks.left_eye.0 / scale_x, landmarks.left_eye.1 / scale_y),
-                            right_eye: (landmarks.right_eye.0 / scale_x, landmarks.right_eye.1 / scale_y),
-                            nose: (landmarks.nose.0 / scale_x, landmarks.nose.1 / scale_y),
-                            left_mouth: (landmarks.left_mouth.0 / scale_x, landmarks.left_mouth.1 / scale_y),
-                            right_mouth: (landmarks.right_mouth.0 / scale_x, landmarks.right_mouth.1 / scale_y),
-                        },
-                        confidence: score,
-                    });
-                }
-            }
-        }
+    Ok(detections)
+}
+
+/// How strongly `ranking` prefers a face; higher ranks first
+pub(crate) fn score(face: &DetectedFace, ranking: DetectionRanking) -> f32 {
+    match ranking {
+        DetectionRanking::ConfidenceArea => face.confidence * face.bbox.area().sqrt(),
+        DetectionRanking::Confidence => face.confidence,
+        DetectionRanking::Area => face.bbox.area(),
+    }
+}
 
-        log::debug!("Found {} detections before NMS", detections.len());
+/// Non-Maximum Suppression
+fn nms(mut detections: Vec<DetectedFace>, iou_threshold: f32) -> Vec<DetectedFace> {
+    if detections.is_empty() {
+        return detections;
+    }
 
-        if detections.is_empty() {
-            return Err(DetectionError::NoFaces);
-        }
+    // Sort by confidence (descending)
+    detections.sort_by(|a, b| {
+        b.confidence
+            .partial_cmp(&a.confidence)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
 
-        // Apply NMS (Non-Maximum Suppression)
-        let mut detections = Self::nms(detections, 0.4);
+    let mut keep = Vec::new();
+    let mut suppressed = vec![false; detections.len()];
 
-        // Sort by confidence and area (prefer larger, more confident faces)
-        detections.sort_by(|a, b| {
-            let score_a = a.confidence * a.bbox.area().sqrt();
-            let score_b = b.confidence * b.bbox.area().sqrt();
-            score_b
-                .partial_cmp(&score_a)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
+    for i in 0..detections.len() {
+        if suppressed[i] {
+            continue;
+        }
 
-        Ok(detections)
-    }
+        keep.push(detections[i].clone());
+
+        for j in (i + 1)..detections.len() {
+            if suppressed[j] {
+                continue;
+            }
 
-    /// Generate anchor centers for a given stride
-    fn generate_anchors(stride: usize, feat_size: usize) -> Vec<(f32, f32)> {
-        let mut anchors = Vec::new();
-        for i in 0..feat_size {
-            for j in 0..feat_size {
-                let cx = (j as f32 + 0.5) * stride as f32;
-                let cy = (i as f32 + 0.5) * stride as f32;
-                anchors.push((cx, cy));
+            let iou = detections[i].bbox.iou(&detections[j].bbox);
+            if iou > iou_threshold {
+                suppressed[j] = true;
             }
         }
-        anchors
     }
 
-    /// Decode SCRFD bounding box predictions from anchor-relative format
-    fn decode_bbox(anchor: (f32, f32), pred: &[f32], _stride: f32) -> (f32, f32, f32, f32) {
-        let (cx, cy) = anchor;
-
-        // det_10g format: distances WITHOUT stride multiplication
-        let left = pred[0].abs();
-        let top = pred[1].abs();
-        let right = pred[2].abs();
-        let bottom = pred[3].abs();
-
-        let x1 = cx - left;
-        let y1 = cy - top;
-        let x2 = cx + right;
-        let y2 = cy + bottom;
+    keep
+}
 
-        (x1, y1, x2 - x1, y2 - y1)
+/// Faces to embed under `policy`, in the order they should be tried
+///
+/// `faces` comes ranked from `FaceDetector::detect`; `frame` is the size of
+/// the image they were found in. `Ranked` takes the first face; the other
+/// policies choose by their own measure and the ranking only breaks ties.
+/// Returns `None` when the policy refuses the frame, i.e. `Reject` with
+/// more than one face.
+pub fn select(
+    mut faces: Vec<DetectedFace>,
+    policy: MultiFacePolicy,
+    (width, height): (u32, u32),
+) -> Option<Vec<DetectedFace>> {
+    if faces.len() < 2 {
+        return Some(faces);
     }
 
-    /// Decode SCRFD landmark predictions from anchor-relative format
-    fn decode_landmarks(anchor: (f32, f32), pred: &[f32], stride: f32) -> FacialLandmarks {
-        let (cx, cy) = anchor;
-        FacialLandmarks {
-            left_eye: (cx + pred[0] * stride, cy + pred[1] * stride),
-            right_eye: (cx + pred[2] * stride, cy + pred[3] * stride),
-            nose: (cx + pred[4] * stride, cy + pred[5] * stride),
-            left_mouth: (cx + pred[6] * stride, cy + pred[7] * stride),
-            right_mouth: (cx + pred[8] * stride, cy + pred[9] * stride),
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let cost = |face: &DetectedFace| match policy {
+        // A weak detection at the center doesn't beat a sure one beside it
+        MultiFacePolicy::MostCentral => {
+            let (x, y) = face.bbox.center();
+            (x - cx).hypot(y - cy) / face.confidence.max(f32::EPSILON)
+        }
+        _ => -score(face, DetectionRanking::Area),
+    };
+
+    match policy {
+        MultiFacePolicy::Reject => None,
+        MultiFacePolicy::AnyMatch => Some(faces),
+        MultiFacePolicy::Ranked => {
+            faces.truncate(1);
+            Some(faces)
+        }
+        MultiFacePolicy::Largest | MultiFacePolicy::MostCentral => {
+            // min_by keeps the first of equals, i.e. the better ranked
+            let best = (0..faces.len())
+                .min_by(|&a, &b| cost(&faces[a]).total_cmp(&cost(&faces[b])))
+                .unwrap_or(0);
+            Some(vec![faces.swap_remove(best)])
         }
     }
+}
 
-    /// Preprocess image for SCRFD model
-    fn preprocess(&self, image: &RgbImage) -> (([usize; 4], Vec<f32>), f32, f32) {
-        let (orig_width, orig_height) = image.dimensions();
-
-        // Image statistics disabled for performance
-
-        // Resize to 640x640
-        let resized = imageops::resize(
-            image,
-            INPUT_SIZE,
-            INPUT_SIZE,
-            imageops::FilterType::Triangle,
-        );
-
-        let scale_x = INPUT_SIZE as f32 / orig_width as f32;
-        let scale_y = INPUT_SIZE as f32 / orig_height as f32;
-
-        // Convert to NCHW format with BGR ordering and normalize to [-1, 1]
-        // SCRFD expects BGR format (not RGB) with mean=127.5, std=128.0
-        let mut input_data = Vec::with_capacity((INPUT_SIZE * INPUT_SIZE * 3) as usize);
-
-        // Channel-first (CHW) ordering with RGB to BGR conversion
-        for c in 0..3 {
-            for y in 0..INPUT_SIZE {
-                for x in 0..INPUT_SIZE {
-                    let pixel = resized.get_pixel(x, y);
-                    // Try RGB order (no channel swap) with [0, 1] normalization
-                    let value = pixel[c] as f32 / 255.0;
-                    input_data.push(value);
-                }
-            }
-        }
+#[derive(Debug, Clone)]
+pub struct BoundingBox {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
 
-        // Return as tuple (shape, data) for ONNX Runtime
-        let shape = [1, 3, INPUT_SIZE as usize, INPUT_SIZE as usize];
-        ((shape, input_data), scale_x, scale_y)
+impl BoundingBox {
+    pub fn area(&self) -> f32 {
+        self.width * self.height
     }
 
-    /// Non-Maximum Suppression
-    fn nms(mut detections: Vec<DetectedFace>, iou_threshold: f32) -> Vec<DetectedFace> {
-        if detections.is_empty() {
-            return detections;
-        }
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
 
-        // Sort by confidence (descending)
-        detections.sort_by(|a, b| {
-            b.confidence
-                .partial_cmp(&a.confidence)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
+    pub fn iou(&self, other: &BoundingBox) -> f32 {
+        let x1 = self.x.max(other.x);
+        let y1 = self.y.max(other.y);
+        let x2 = (self.x + self.width).min(other.x + other.width);
+        let y2 = (self.y + self.height).min(other.y + other.height);
 
-        let mut keep = Vec::new();
-        let mut suppressed = vec![false; detections.len()];
+        let intersection = (x2 - x1).max(0.0) * (y2 - y1).max(0.0);
+        let union = self.area() + other.area() - intersection;
 
-        for i in 0..detections.len() {
-            if suppressed[i] {
-                continue;
-            }
+        if union > 0.0 {
+            intersection / union
+        } else {
+            0.0
+        }
+    }
+}
 
-            keep.push(detections[i].clone());
+#[derive(Debug, Clone)]
+pub struct FacialLandmarks {
+    pub left_eye: (f32, f32),
+    pub right_eye: (f32, f32),
+    pub nose: (f32, f32),
+    pub left_mouth: (f32, f32),
+    pub right_mouth: (f32, f32),
+}
 
-            for j in (i + 1)..detections.len() {
-                if suppressed[j] {
-                    continue;
-                }
+impl FacialLandmarks {
+    /// Points in the order eyes, nose, mouth corners
+    pub fn points(&self) -> [(f32, f32); 5] {
+        [self.left_eye, self.right_eye, self.nose, self.left_mouth, self.right_mouth]
+    }
 
-                let iou = detections[i].bbox.iou(&detections[j].bbox);
-                if iou > iou_threshold {
-                    suppressed[j] = true;
-                }
-            }
+    /// Pixels between the eye centers
+    pub fn eye_distance(&self) -> f32 {
+        (self.right_eye.0 - self.left_eye.0).hypot(self.right_eye.1 - self.left_eye.1)
+    }
+
+    /// Landmarks from points in the order of `points()`
+    pub fn from_points([left_eye, right_eye, nose, left_mouth, right_mouth]: [(f32, f32); 5]) -> Self {
+        Self {
+            left_eye,
+            right_eye,
+            nose,
+            left_mouth,
+            right_mouth,
         }
+    }
 
-        keep
+    /// Typical landmark positions for an upright face filling `bbox`, for
+    /// models without a keypoint head
+    pub fn estimate(bbox: &BoundingBox) -> Self {
+        let at = |fx: f32, fy: f32| (bbox.x + fx * bbox.width, bbox.y + fy * bbox.height);
+        Self {
+            left_eye: at(0.32, 0.40),
+            right_eye: at(0.68, 0.40),
+            nose: at(0.50, 0.58),
+            left_mouth: at(0.36, 0.77),
+            right_mouth: at(0.64, 0.77),
+        }
     }
+
+    /// Apply a coordinate mapping to every point
+    pub fn map(&self, f: impl Fn((f32, f32)) -> (f32, f32)) -> Self {
+        Self {
+            left_eye: f(self.left_eye),
+            right_eye: f(self.right_eye),
+            nose: f(self.nose),
+            left_mouth: f(self.left_mouth),
+            right_mouth: f(self.right_mouth),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DetectedFace {
+    pub bbox: BoundingBox,
+    pub landmarks: FacialLandmarks,
+    pub confidence: f32,
+    /// Same subject across frames; set by `track::FaceTracker`, `None`
+    /// straight from a detector
+    pub track: Option<u32>,
 }
 
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use image::{GrayImage, Luma, Rgb, RgbImage};
 
     #[test]
     fn test_bbox_area() {
@@ -438,12 +499,88 @@ mod tests {
     }
 
     #[test]
-    #[ignore] // Requires model file
-    fn test_face_detection() {
-        // This test requires the SCRFD model
-        // let runtime = OnnxRuntime::new().unwrap();
-        // let config = RuntimeConfig { provider: ExecutionProvider::CPU };
-        // let detector = FaceDetector::new("models/scrfd_500m.onnx", &runtime, &config, 0.5).unwrap();
+    fn test_letterbox_mapping() {
+        // 640x480 into 640x640: 80px bands above and below
+        let mapping = InputMapping::new((640, 480), (640, 640), true);
+        assert_eq!(mapping, InputMapping { scale_x: 1.0, scale_y: 1.0, pad_x: 0, pad_y: 80 });
+        assert_eq!(mapping.scaled((640, 480)), (640, 480));
+        assert_eq!(mapping.unmap((320.0, 80.0)), (320.0, 0.0));
+
+        // Portrait frame, halved and centered horizontally
+        let mapping = InputMapping::new((480, 1280), (640, 640), true);
+        assert_eq!(mapping.scaled((480, 1280)), (240, 640));
+        assert_eq!(mapping.pad_x, 200);
+        assert_eq!(mapping.unmap((200.0, 640.0)), (0.0, 1280.0));
+
+        // Stretching scales the axes apart and never pads
+        let mapping = InputMapping::new((640, 480), (640, 640), false);
+        assert_eq!((mapping.pad_x, mapping.pad_y), (0, 0));
+        assert_eq!(mapping.unmap((640.0, 640.0)), (640.0, 480.0));
+    }
+
+    #[test]
+    fn test_input_tensor_order_and_padding() {
+        // 2x1 frame letterboxed into 2x2: padding row on top, none below
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 1, Rgb([10, 20, 30])));
+        let ((shape, data), mapping) = input_tensor(&image, (2, 2), true, BGR, |v, c| v as f32 + c as f32 * 0.5);
+        assert_eq!(shape, [1, 3, 2, 2]);
+        assert_eq!(mapping.pad_y, 0);
+        // Blue first, each channel normalized as itself
+        assert_eq!(&data[..2], &[30.0, 30.0]);
+        assert_eq!(&data[4..6], &[20.5, 20.5]);
+        assert_eq!(&data[8..10], &[11.0, 11.0]);
+        assert_eq!(data[2], 0.0);
+
+        // Gray fills all three channels from its one plane
+        let image = DynamicImage::ImageLuma8(GrayImage::from_pixel(4, 4, Luma([200])));
+        let ((_, data), _) = input_tensor(&image, (4, 4), true, RGB, |v, _| v as f32);
+        assert!(data.iter().all(|&v| v == 200.0));
+    }
+
+    #[test]
+    fn test_finish_orders_and_suppresses() {
+        let face = |x: f32, size: f32, confidence: f32| {
+            let bbox = BoundingBox { x, y: 0.0, width: size, height: size };
+            DetectedFace { landmarks: FacialLandmarks::estimate(&bbox), bbox, confidence, track: None }
+        };
+        let faces = || vec![face(0.0, 20.0, 0.9), face(1.0, 20.0, 0.8), face(100.0, 80.0, 0.7)];
+        let ranked = finish(faces(), 0.4, DetectionRanking::ConfidenceArea).unwrap();
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].bbox.x, 100.0);
+        assert!(matches!(finish(Vec::new(), 0.4, DetectionRanking::Area), Err(DetectionError::NoFaces)));
+
+        // Confidence alone puts the small face first; a strict IoU keeps both small ones
+        let ranked = finish(faces(), 0.95, DetectionRanking::Confidence).unwrap();
+        assert_eq!(ranked.len(), 3);
+        assert_eq!(ranked[0].bbox.x, 0.0);
+        assert_eq!(finish(faces(), 0.95, DetectionRanking::Area).unwrap()[0].bbox.x, 100.0);
     }
-}
 
+    #[test]
+    fn test_select_policies() {
+        let scored = |x: f32, size: f32, confidence: f32| {
+            let bbox = BoundingBox { x, y: 200.0, width: size, height: size };
+            DetectedFace { landmarks: FacialLandmarks::estimate(&bbox), bbox, confidence, track: None }
+        };
+        let face = |x: f32, size: f32| scored(x, size, 0.9);
+        // Detector ranking first; large face off to the side, small one centered
+        let faces = || vec![face(300.0, 40.0), face(0.0, 200.0)];
+        let frame = (640, 480);
+
+        assert!(select(faces(), MultiFacePolicy::Reject, frame).is_none());
+        assert_eq!(select(faces()[..1].to_vec(), MultiFacePolicy::Reject, frame).unwrap().len(), 1);
+        assert_eq!(select(faces(), MultiFacePolicy::Largest, frame).unwrap()[0].bbox.x, 0.0);
+        let ranked = select(faces(), MultiFacePolicy::Ranked, frame).unwrap();
+        assert_eq!((ranked.len(), ranked[0].bbox.x), (1, 300.0));
+        let central = select(faces(), MultiFacePolicy::MostCentral, frame).unwrap();
+        assert_eq!((central.len(), central[0].bbox.x), (1, 300.0));
+        assert_eq!(select(faces(), MultiFacePolicy::AnyMatch, frame).unwrap().len(), 2);
+
+        // A barely detected face at the center loses to a sure one next to it
+        let weak = vec![scored(300.0, 40.0, 0.3), scored(340.0, 40.0, 0.9)];
+        assert_eq!(select(weak, MultiFacePolicy::MostCentral, frame).unwrap()[0].bbox.x, 340.0);
+        // Equal boxes: the better ranked one is kept
+        let tied = vec![scored(100.0, 40.0, 0.9), scored(500.0, 40.0, 0.8)];
+        assert_eq!(select(tied, MultiFacePolicy::Largest, frame).unwrap()[0].confidence, 0.9);
+    }
+}
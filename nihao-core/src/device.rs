@@ -0,0 +1,293 @@
+use crate::capture::CaptureError;
+use crate::config::CameraConfig;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use v4l::capability::Flags;
+use v4l::Device;
+
+/// USB vendor:product pair, written as `"046d:0825"` in the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsbId {
+    pub vendor: u16,
+    pub product: u16,
+}
+
+impl UsbId {
+    /// Parse a `vendor:product` pair of hex IDs
+    pub fn parse(s: &str) -> Option<Self> {
+        let (vendor, product) = s.trim().split_once(':')?;
+        Some(Self {
+            vendor: u16::from_str_radix(vendor, 16).ok()?,
+            product: u16::from_str_radix(product, 16).ok()?,
+        })
+    }
+}
+
+impl fmt::Display for UsbId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04x}:{:04x}", self.vendor, self.product)
+    }
+}
+
+/// Identity of a V4L2 video capture node
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub path: PathBuf,
+    pub card: String,
+    pub driver: String,
+    pub bus_info: String,
+    pub usb_id: Option<UsbId>,
+    /// Node can capture video (metadata-only nodes of the same camera cannot)
+    pub is_capture: bool,
+}
+
+impl DeviceInfo {
+    /// Open a device node and read its identity
+    pub fn probe(path: &Path) -> Result<Self, CaptureError> {
+        let device = Device::with_path(path)
+            .map_err(|e| CaptureError::DeviceOpen(format!("{}: {}", path.display(), e)))?;
+        let caps = device.query_caps()
+            .map_err(|e| CaptureError::DeviceOpen(format!("Failed to query caps: {}", e)))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            card: caps.card,
+            driver: caps.driver,
+            bus_info: caps.bus,
+            usb_id: usb_id_for(path),
+            is_capture: caps.capabilities.intersects(Flags::VIDEO_CAPTURE | Flags::VIDEO_CAPTURE_MPLANE),
+        })
+    }
+
+    /// Check this device against the `[camera]` selectors that are set
+    pub fn matches(&self, config: &CameraConfig) -> bool {
+        if let Some(name) = &config.device_name {
+            if !self.card.to_lowercase().contains(&name.to_lowercase()) {
+                return false;
+            }
+        }
+        if let Some(bus_info) = &config.bus_info {
+            if self.bus_info.trim() != bus_info.trim() {
+                return false;
+            }
+        }
+        if let Some(usb_id) = config.usb_id.as_deref().and_then(UsbId::parse) {
+            if self.usb_id != Some(usb_id) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Whether the card name suggests an infrared or depth sensor
+    ///
+    /// "IR" must be a word of its own, so names like "Mirror" or "FireWire"
+    /// don't count.
+    pub fn looks_ir(&self) -> bool {
+        self.card
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .map(|word| word.to_lowercase())
+            .any(|word| word == "ir" || word.contains("infrared") || word.contains("depth"))
+    }
+}
+
+/// Map a `[camera] device` value to a device path
+///
+/// A bare index such as `"2"` means `/dev/video2`; anything else is used as
+/// a path, so `/dev/v4l/by-id/...` and `/dev/v4l/by-path/...` symlinks work.
+pub fn device_path(device: &str) -> PathBuf {
+    match device.parse::<usize>() {
+        Ok(index) => PathBuf::from(format!("/dev/video{}", index)),
+        Err(_) => PathBuf::from(device),
+    }
+}
+
+/// Read the USB vendor/product of a video node from sysfs
+fn usb_id_for(path: &Path) -> Option<UsbId> {
+    // Resolve by-id/by-path symlinks to the videoN node name
+    let node = fs::canonicalize(path).ok()?;
+    let name = node.file_name()?.to_str()?;
+
+    // The device link points at the USB interface; the IDs live on its parent
+    let sysfs = fs::canonicalize(format!("/sys/class/video4linux/{}/device", name)).ok()?;
+    sysfs.ancestors().take(3).find_map(|dir| {
+        let read = |file: &str| {
+            let value = fs::read_to_string(dir.join(file)).ok()?;
+            u16::from_str_radix(value.trim(), 16).ok()
+        };
+        Some(UsbId {
+            vendor: read("idVendor")?,
+            product: read("idProduct")?,
+        })
+    })
+}
+
+/// Probe every `/dev/video*` node that can capture video, sorted by path
+pub fn list_capture_devices() -> Result<Vec<DeviceInfo>, CaptureError> {
+    let mut devices = Vec::new();
+
+    for entry in fs::read_dir("/dev")
+        .map_err(|e| CaptureError::DeviceOpen(format!("Failed to read /dev: {}", e)))?
+    {
+        let path = entry.map_err(|e| CaptureError::DeviceOpen(e.to_string()))?.path();
+        let is_video = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("video"));
+        if !is_video {
+            continue;
+        }
+
+        match DeviceInfo::probe(&path) {
+            Ok(info) if info.is_capture => devices.push(info),
+            Ok(_) => log::debug!("{}: not a video capture node", path.display()),
+            Err(e) => log::debug!("Skipping {}: {}", path.display(), e),
+        }
+    }
+
+    devices.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(devices)
+}
+
+/// Resolve the `[camera]` section to a device path
+///
+/// With no `device_name`, `bus_info` or `usb_id` selector, this is just
+/// `device`. Otherwise every capture node is probed and the first that
+/// matches all selectors wins; no match is an error rather than a fallback.
+pub fn resolve(config: &CameraConfig) -> Result<PathBuf, CaptureError> {
+    let selectors = describe_selectors(config);
+    if selectors.is_empty() {
+        let path = device_path(&config.device);
+        if !path.exists() {
+            return Err(CaptureError::NoMatchingDevice(format!(
+                "{} does not exist",
+                path.display()
+            )));
+        }
+        return Ok(path);
+    }
+
+    let devices = list_capture_devices()?;
+    let matching: Vec<&DeviceInfo> = devices.iter().filter(|d| d.matches(config)).collect();
+
+    let preferred = if config.prefer_ir {
+        matching.iter().position(|d| d.looks_ir()).unwrap_or(0)
+    } else {
+        0
+    };
+
+    let Some(&found) = matching.get(preferred) else {
+        let available = devices
+            .iter()
+            .map(|d| {
+                let usb = d.usb_id.map(|id| format!(", usb {}", id)).unwrap_or_default();
+                format!("{} ({}, bus {}{})", d.path.display(), d.card, d.bus_info, usb)
+            })
+            .collect::<Vec<_>>();
+        return Err(CaptureError::NoMatchingDevice(format!(
+            "{}; available: {}",
+            selectors,
+            if available.is_empty() { "none".to_string() } else { available.join("; ") }
+        )));
+    };
+
+    if let Some(other) = matching.iter().find(|d| d.path != found.path) {
+        log::warn!(
+            "Several cameras match {}: using {}, also {}",
+            selectors,
+            found.path.display(),
+            other.path.display()
+        );
+    }
+
+    log::info!("Resolved camera {} to {} ({})", selectors, found.path.display(), found.card);
+    Ok(found.path.clone())
+}
+
+/// Human-readable summary of the selectors that are set
+fn describe_selectors(config: &CameraConfig) -> String {
+    let mut parts = Vec::new();
+    if let Some(name) = &config.device_name {
+        parts.push(format!("device_name={:?}", name));
+    }
+    if let Some(bus_info) = &config.bus_info {
+        parts.push(format!("bus_info={:?}", bus_info));
+    }
+    if let Some(usb_id) = &config.usb_id {
+        parts.push(format!("usb_id={:?}", usb_id));
+    }
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn info() -> DeviceInfo {
+        DeviceInfo {
+            path: PathBuf::from("/dev/video2"),
+            card: "Integrated IR Camera: Integrat".to_string(),
+            driver: "uvcvideo".to_string(),
+            bus_info: "usb-0000:00:14.0-6".to_string(),
+            usb_id: Some(UsbId { vendor: 0x04f2, product: 0xb6d9 }),
+            is_capture: true,
+        }
+    }
+
+    #[test]
+    fn test_usb_id_parse() {
+        assert_eq!(UsbId::parse("04f2:b6d9"), Some(UsbId { vendor: 0x04f2, product: 0xb6d9 }));
+        assert_eq!(UsbId::parse("04F2:B6D9").unwrap().to_string(), "04f2:b6d9");
+        assert_eq!(UsbId::parse("04f2"), None);
+        assert_eq!(UsbId::parse("zzzz:0001"), None);
+    }
+
+    #[test]
+    fn test_device_path() {
+        assert_eq!(device_path("2"), PathBuf::from("/dev/video2"));
+        assert_eq!(device_path("/dev/video0"), PathBuf::from("/dev/video0"));
+        assert_eq!(
+            device_path("/dev/v4l/by-id/usb-Chicony_IR-video-index0"),
+            PathBuf::from("/dev/v4l/by-id/usb-Chicony_IR-video-index0")
+        );
+    }
+
+    #[test]
+    fn test_selector_matching() {
+        let mut config = Config::default().camera;
+        assert!(info().matches(&config));
+
+        config.device_name = Some("ir camera".to_string());
+        assert!(info().matches(&config));
+
+        config.usb_id = Some("04f2:b6d9".to_string());
+        assert!(info().matches(&config));
+
+        config.bus_info = Some("usb-0000:00:14.0-7".to_string());
+        assert!(!info().matches(&config));
+
+        config.bus_info = None;
+        config.usb_id = Some("046d:0825".to_string());
+        assert!(!info().matches(&config));
+    }
+
+    #[test]
+    fn test_looks_ir() {
+        assert!(info().looks_ir());
+
+        let mut other = info();
+        other.card = "Integrated Camera: Integrated C".to_string();
+        assert!(!other.looks_ir());
+
+        // "ir" inside another word
+        other.card = "Virtual Mirror Cam (FireWire)".to_string();
+        assert!(!other.looks_ir());
+
+        other.card = "Intel(R) RealSense(TM) Depth Camera 435".to_string();
+        assert!(other.looks_ir());
+        other.card = "USB2.0 HD IR UVC WebCam".to_string();
+        assert!(other.looks_ir());
+    }
+}
@@ -1,6 +1,6 @@
 use crate::config::RuntimeConfig;
 use crate::runtime::OnnxRuntime;
-use image::RgbImage;
+use image::DynamicImage;
 use ndarray::Array1;
 use ort::session::Session;
 use ort::value::Value;
@@ -47,10 +47,10 @@ impl FaceEmbedder {
     }
 
     /// Generate embedding for an aligned face image
-    /// Input should be 112x112 RGB image (from face alignment)
-    pub fn embed(&mut self, aligned_face: &RgbImage) -> Result<Embedding, EmbedError> {
+    /// Input should be a 112x112 RGB or gray image (from face alignment)
+    pub fn embed(&mut self, aligned_face: &DynamicImage) -> Result<Embedding, EmbedError> {
         // Verify input dimensions
-        let (width, height) = aligned_face.dimensions();
+        let (width, height) = (aligned_face.width(), aligned_face.height());
         if width != ARCFACE_INPUT_SIZE || height != ARCFACE_INPUT_SIZE {
             return Err(EmbedError::Inference(format!(
                 "Input image must be {}x{}, got {}x{}",
@@ -95,23 +95,34 @@ impl FaceEmbedder {
     }
 
     /// Preprocess aligned face for ArcFace model
-    /// Converts 112x112 RGB image to NCHW tensor with normalization
-    fn preprocess(&self, image: &RgbImage) -> ([usize; 4], Vec<f32>) {
+    /// Converts 112x112 image to NCHW tensor with normalization
+    fn preprocess(&self, image: &DynamicImage) -> ([usize; 4], Vec<f32>) {
         let size = ARCFACE_INPUT_SIZE as usize;
-        let mut input_data = Vec::with_capacity(size * size * 3);
 
         // Convert to NCHW format and normalize
         // ArcFace typically uses mean=[127.5, 127.5, 127.5] and std=[128.0, 128.0, 128.0]
         // Which is equivalent to: (pixel - 127.5) / 128.0
-        for c in 0..3 {
-            for y in 0..ARCFACE_INPUT_SIZE {
-                for x in 0..ARCFACE_INPUT_SIZE {
-                    let pixel = image.get_pixel(x, y);
-                    let value = (pixel[c] as f32 - 127.5) / 128.0;
-                    input_data.push(value);
+        let input_data = match image {
+            // Gray faces fill one plane and copy it to all three channels
+            DynamicImage::ImageLuma8(gray) => {
+                let plane: Vec<f32> = gray.as_raw().iter().map(|&v| (v as f32 - 127.5) / 128.0).collect();
+                plane.repeat(3)
+            }
+            other => {
+                let image = crate::capture::rgb(other);
+                let mut input_data = Vec::with_capacity(size * size * 3);
+                for c in 0..3 {
+                    for y in 0..ARCFACE_INPUT_SIZE {
+                        for x in 0..ARCFACE_INPUT_SIZE {
+                            let pixel = image.get_pixel(x, y);
+                            let value = (pixel[c] as f32 - 127.5) / 128.0;
+                            input_data.push(value);
+                        }
+                    }
                 }
+                input_data
             }
-        }
+        };
 
         // Return as tuple (shape, data) for ONNX Runtime
         ([1, 3, size, size], input_data)
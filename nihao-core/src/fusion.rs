@@ -0,0 +1,371 @@
+use crate::capture::{CaptureError, ExposureSettle, FrameInfo, FrameSource, STREAM_BUFFERS};
+use crate::config::{CameraConfig, ControlValue};
+use image::{DynamicImage, GrayImage, RgbImage};
+
+/// Spread of the well-exposedness weight around mid-gray (Mertens et al.)
+const EXPOSEDNESS_SIGMA: f32 = 0.2;
+
+/// Keeps a pixel's weight above zero when every input is badly exposed
+const MIN_WEIGHT: f32 = 1e-3;
+
+/// Auto-exposure mode control, switched to manual while bracketing
+const AUTO_EXPOSURE: &str = "auto_exposure";
+
+/// `V4L2_EXPOSURE_MANUAL`
+const MANUAL_EXPOSURE: i64 = 1;
+
+/// Merges several frames into one well-exposed frame
+///
+/// Wraps another source so each `next_frame` reads `fusion_frames`
+/// consecutive frames, or one frame per `exposure_bracket` value when a
+/// bracket is configured, and blends them. IR emitters that light only
+/// every other frame then yield one usable frame per pair instead of a
+/// rejected dark frame.
+///
+/// Bracketing switches the camera to manual exposure and puts the original
+/// exposure and mode back on `stop` (or drop), so the next attempt and other
+/// applications find the camera as it was.
+pub struct FusionSource {
+    inner: Box<dyn FrameSource>,
+    config: CameraConfig,
+    bracket: Vec<i64>,
+    /// Controls changed for the bracket with their original values, in the
+    /// order to write them back
+    restore: Vec<(String, i64)>,
+}
+
+impl FusionSource {
+    pub fn new(inner: Box<dyn FrameSource>, config: &CameraConfig) -> Self {
+        log::info!(
+            "Exposure fusion: {}",
+            if config.exposure_bracket.is_empty() {
+                format!("{} consecutive frames", config.fusion_frames)
+            } else {
+                format!("{} = {:?}", config.exposure_control, config.exposure_bracket)
+            }
+        );
+
+        Self {
+            inner,
+            config: config.clone(),
+            bracket: config.exposure_bracket.clone(),
+            restore: Vec::new(),
+        }
+    }
+
+    /// Remember the current exposure and mode, then switch to manual exposure
+    fn begin_bracket(&mut self) -> Result<(), CaptureError> {
+        let control = self.config.exposure_control.clone();
+        let exposure = self.inner.get_control(&control)?;
+        match self.inner.get_control(AUTO_EXPOSURE) {
+            Ok(mode) => {
+                // Exposure goes back first, while the camera is still manual
+                self.restore = vec![(control, exposure), (AUTO_EXPOSURE.to_string(), mode)];
+                self.inner.set_control(AUTO_EXPOSURE, &ControlValue::Int(MANUAL_EXPOSURE))?;
+            }
+            Err(e) => {
+                log::debug!("No auto-exposure mode to switch off: {}", e);
+                self.restore = vec![(control, exposure)];
+            }
+        }
+        Ok(())
+    }
+
+    /// Put back the controls changed for the bracket
+    fn restore_exposure(&mut self) {
+        for (key, value) in std::mem::take(&mut self.restore) {
+            if let Err(e) = self.inner.set_control(&key, &ControlValue::Int(value)) {
+                log::warn!("Failed to restore {} = {}: {}", key, value, e);
+            }
+        }
+    }
+
+    /// Read one frame per bracket step, dropping the frames queued while
+    /// the new exposure takes effect
+    fn read_bracket(&mut self) -> Result<Vec<DynamicImage>, CaptureError> {
+        let mut frames = Vec::with_capacity(self.bracket.len());
+        for i in 0..self.bracket.len() {
+            let mut set = Ok(());
+            if self.restore.is_empty() {
+                set = self.begin_bracket();
+            }
+            if set.is_ok() {
+                set = self
+                    .inner
+                    .set_control(&self.config.exposure_control, &ControlValue::Int(self.bracket[i]));
+            }
+            if let Err(e) = set {
+                log::warn!("Exposure bracketing disabled, fusing consecutive frames instead: {}", e);
+                self.bracket.clear();
+                self.restore_exposure();
+                return self.read_consecutive();
+            }
+            // Buffers already queued were exposed with the previous value
+            for _ in 0..STREAM_BUFFERS {
+                self.inner.next_frame()?;
+            }
+            frames.push(self.inner.next_frame()?);
+        }
+        Ok(frames)
+    }
+
+    fn read_consecutive(&mut self) -> Result<Vec<DynamicImage>, CaptureError> {
+        let mut frames = Vec::with_capacity(self.config.fusion_frames as usize);
+        for _ in 0..self.config.fusion_frames {
+            match self.inner.next_frame() {
+                Ok(frame) => frames.push(frame),
+                // A replay that runs out mid-group still fuses what it has
+                Err(CaptureError::EndOfStream) if !frames.is_empty() => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(frames)
+    }
+}
+
+impl FrameSource for FusionSource {
+    fn next_frame(&mut self) -> Result<DynamicImage, CaptureError> {
+        let frames = if self.bracket.is_empty() {
+            self.read_consecutive()?
+        } else {
+            self.read_bracket()?
+        };
+        fuse(&frames)
+    }
+
+    fn config(&self) -> &CameraConfig {
+        &self.config
+    }
+
+    fn last_frame_info(&self) -> Option<FrameInfo> {
+        self.inner.last_frame_info()
+    }
+
+    fn exposure_settle(&self) -> Option<ExposureSettle> {
+        self.inner.exposure_settle()
+    }
+
+    fn set_control(&mut self, key: &str, value: &ControlValue) -> Result<(), CaptureError> {
+        self.inner.set_control(key, value)
+    }
+
+    fn get_control(&self, key: &str) -> Result<i64, CaptureError> {
+        self.inner.get_control(key)
+    }
+
+    fn stop(&mut self) {
+        self.restore_exposure();
+        self.inner.stop();
+    }
+}
+
+impl Drop for FusionSource {
+    fn drop(&mut self) {
+        self.restore_exposure();
+    }
+}
+
+/// Weight of a gray or RGB pixel by how close its luminance is to mid-gray
+fn exposedness(pixel: &[u8]) -> f32 {
+    let y = match *pixel {
+        [r, g, b] => 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32,
+        _ => pixel[0] as f32,
+    } / 255.0;
+    (-(y - 0.5).powi(2) / (2.0 * EXPOSEDNESS_SIGMA * EXPOSEDNESS_SIGMA)).exp() + MIN_WEIGHT
+}
+
+/// Blend frames per pixel, weighting each by its well-exposedness
+///
+/// Single-scale exposure fusion: black (unlit) and blown-out pixels get
+/// almost no weight, so the result takes each region from the frames where
+/// it is best exposed. Gray frames are fused as gray; mixing in any color
+/// frame fuses in RGB.
+pub fn fuse(frames: &[DynamicImage]) -> Result<DynamicImage, CaptureError> {
+    let first = frames
+        .first()
+        .ok_or_else(|| CaptureError::Capture("No frames to fuse".to_string()))?;
+    if frames.len() == 1 {
+        return Ok(first.clone());
+    }
+
+    let (width, height) = (first.width(), first.height());
+    if let Some(other) = frames.iter().find(|f| (f.width(), f.height()) != (width, height)) {
+        return Err(CaptureError::Conversion(format!(
+            "Cannot fuse {}x{} with {}x{} frame",
+            width,
+            height,
+            other.width(),
+            other.height()
+        )));
+    }
+
+    let fused: DynamicImage = if frames.iter().all(|f| f.as_luma8().is_some()) {
+        let planes: Vec<&[u8]> = frames.iter().map(|f| f.as_luma8().unwrap().as_raw().as_slice()).collect();
+        GrayImage::from_raw(width, height, blend(&planes, 1)).unwrap().into()
+    } else {
+        let rgb: Vec<_> = frames.iter().map(crate::capture::rgb).collect();
+        let planes: Vec<&[u8]> = rgb.iter().map(|f| f.as_raw().as_slice()).collect();
+        RgbImage::from_raw(width, height, blend(&planes, 3)).unwrap().into()
+    };
+
+    log::debug!("Fused {} frames", frames.len());
+    Ok(fused)
+}
+
+/// Weighted per-pixel average of same-sized interleaved buffers
+fn blend(frames: &[&[u8]], channels: usize) -> Vec<u8> {
+    let mut fused = vec![0u8; frames[0].len()];
+    for (i, out) in fused.chunks_exact_mut(channels).enumerate() {
+        let mut sum = [0.0f32; 3];
+        let mut total = 0.0;
+        for frame in frames {
+            let p = &frame[i * channels..(i + 1) * channels];
+            let w = exposedness(p);
+            for c in 0..channels {
+                sum[c] += w * p[c] as f32;
+            }
+            total += w;
+        }
+        for (c, v) in out.iter_mut().enumerate() {
+            *v = (sum[c] / total).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    fused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use image::{Luma, Rgb};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// Alternates lit and unlit frames like a flashing IR emitter
+    struct Flashing {
+        count: Arc<AtomicU32>,
+        config: CameraConfig,
+        /// Every control written, in order
+        controls: Arc<Mutex<Vec<(String, i64)>>>,
+    }
+
+    impl FrameSource for Flashing {
+        fn next_frame(&mut self) -> Result<DynamicImage, CaptureError> {
+            let count = self.count.fetch_add(1, Ordering::Relaxed) + 1;
+            let v = if count % 2 == 0 { 130 } else { 5 };
+            Ok(GrayImage::from_pixel(4, 4, Luma([v])).into())
+        }
+
+        fn config(&self) -> &CameraConfig {
+            &self.config
+        }
+
+        fn set_control(&mut self, key: &str, value: &ControlValue) -> Result<(), CaptureError> {
+            match value {
+                ControlValue::Int(v) => self.controls.lock().unwrap().push((key.to_string(), *v)),
+                _ => unreachable!(),
+            }
+            Ok(())
+        }
+
+        fn get_control(&self, key: &str) -> Result<i64, CaptureError> {
+            // Aperture priority (auto) with the driver's default exposure
+            Ok(if key == AUTO_EXPOSURE { 3 } else { 250 })
+        }
+    }
+
+    fn flashing(controls: Arc<Mutex<Vec<(String, i64)>>>) -> Box<Flashing> {
+        Box::new(Flashing {
+            count: Arc::default(),
+            config: Config::default().camera,
+            controls,
+        })
+    }
+
+    #[test]
+    fn test_fuse_prefers_well_exposed() {
+        let lit = RgbImage::from_pixel(2, 2, Rgb([120; 3]));
+        let unlit = RgbImage::from_pixel(2, 2, Rgb([3; 3]));
+        let fused = fuse(&[unlit.into(), lit.into()]).unwrap();
+        assert!(fused.as_rgb8().unwrap().get_pixel(0, 0).0[0] > 110);
+    }
+
+    #[test]
+    fn test_fuse_rejects_mismatched_sizes() {
+        let a = RgbImage::new(2, 2).into();
+        let b = RgbImage::new(3, 2).into();
+        assert!(fuse(&[a, b]).is_err());
+    }
+
+    #[test]
+    fn test_flashing_emitter_passes_quality_check() {
+        let mut config = Config::default().camera;
+        config.fusion_frames = 2;
+
+        let mut fusion = FusionSource::new(flashing(Arc::default()), &config);
+        for _ in 0..3 {
+            // Gray in, gray out
+            let frame = fusion.capture_frame(true).unwrap();
+            assert!(frame.as_luma8().unwrap().get_pixel(0, 0).0[0] > 110);
+        }
+    }
+
+    #[test]
+    fn test_exposure_bracket() {
+        let mut config = Config::default().camera;
+        config.exposure_bracket = vec![100, 400];
+
+        let controls = Arc::default();
+        let mut fusion = FusionSource::new(flashing(Arc::clone(&controls)), &config);
+        let exposure = config.exposure_control.clone();
+        let set = |key: &str, value| (key.to_string(), value);
+
+        let frame = fusion.next_frame().unwrap();
+        assert!(frame.as_luma8().unwrap().get_pixel(0, 0).0[0] > 110);
+        assert_eq!(
+            *controls.lock().unwrap(),
+            vec![set(AUTO_EXPOSURE, MANUAL_EXPOSURE), set(&exposure, 100), set(&exposure, 400)]
+        );
+
+        // The next group stays in manual mode
+        fusion.next_frame().unwrap();
+        assert_eq!(controls.lock().unwrap().len(), 5);
+
+        // Exposure goes back before the auto mode
+        fusion.stop();
+        assert_eq!(
+            controls.lock().unwrap()[5..],
+            [set(&exposure, 250), set(AUTO_EXPOSURE, 3)]
+        );
+    }
+
+    #[test]
+    fn test_exposure_bracket_skips_queued_frames() {
+        let mut config = Config::default().camera;
+        config.exposure_bracket = vec![100, 400];
+
+        let count = Arc::new(AtomicU32::new(0));
+        let inner = Flashing {
+            count: Arc::clone(&count),
+            config: config.clone(),
+            controls: Arc::default(),
+        };
+        let mut fusion = FusionSource::new(Box::new(inner), &config);
+        fusion.next_frame().unwrap();
+        assert_eq!(count.load(Ordering::Relaxed), 2 * (STREAM_BUFFERS + 1));
+    }
+
+    #[test]
+    fn test_exposure_restored_on_drop() {
+        let mut config = Config::default().camera;
+        config.exposure_bracket = vec![100];
+
+        let controls = Arc::default();
+        let mut fusion = FusionSource::new(flashing(Arc::clone(&controls)), &config);
+        fusion.next_frame().unwrap();
+        drop(fusion);
+
+        let controls = controls.lock().unwrap();
+        assert_eq!(controls.last(), Some(&(AUTO_EXPOSURE.to_string(), 3)));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::config::CameraConfig;
+use crate::detect::{DetectedFace, DetectionError, FaceDetector};
+use image::DynamicImage;
+
+/// Rotations tried, clockwise in degrees, when searching for the orientation
+const ORIENTATIONS: [u32; 4] = [0, 90, 180, 270];
+
+/// Crop, rotate and mirror a frame as `[camera]` asks
+///
+/// The crop is in sensor pixels, so it is applied first; rotation and flips
+/// then act on what is left.
+pub fn apply(image: DynamicImage, config: &CameraConfig) -> DynamicImage {
+    if !config.has_geometry() {
+        return image;
+    }
+
+    let image = match config.crop {
+        Some([x, y, width, height]) => image.crop_imm(x, y, width, height),
+        None => image,
+    };
+    let mut image = rotate(image, config.rotate);
+    if config.flip_horizontal {
+        image = image.fliph();
+    }
+    if config.flip_vertical {
+        image = image.flipv();
+    }
+    image
+}
+
+/// Rotate clockwise by a multiple of 90 degrees
+pub fn rotate(image: DynamicImage, degrees: u32) -> DynamicImage {
+    match degrees % 360 {
+        90 => image.rotate90(),
+        180 => image.rotate180(),
+        270 => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// Check the crop rectangle against the negotiated frame size
+pub fn check_crop(crop: Option<[u32; 4]>, width: u32, height: u32) -> Result<(), String> {
+    let outside = |offset: u32, size: u32, limit: u32| offset.checked_add(size).map_or(true, |end| end > limit);
+    match crop {
+        Some([x, y, w, h]) if outside(x, w, width) || outside(y, h, height) => Err(format!(
+            "crop [{}, {}, {}, {}] does not fit the {}x{} frame",
+            x, y, w, h, width, height
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Detect faces, trying rotated copies of the frame until one has a face
+///
+/// With `known` set only that rotation is tried. Returns the faces, in the
+/// coordinates of the rotated frame, and the rotation that found them.
+pub fn detect_oriented(
+    detector: &mut dyn FaceDetector,
+    image: &DynamicImage,
+    known: Option<u32>,
+) -> Result<(Vec<DetectedFace>, u32), DetectionError> {
+    let candidates = match known {
+        Some(degrees) => vec![degrees],
+        None => ORIENTATIONS.to_vec(),
+    };
+
+    for degrees in candidates {
+        let faces = if degrees == 0 {
+            detector.detect(image)
+        } else {
+            detector.detect(&rotate(image.clone(), degrees))
+        };
+        match faces {
+            Ok(faces) if !faces.is_empty() => return Ok((faces, degrees)),
+            Ok(_) | Err(DetectionError::NoFaces) => log::debug!("No face at {}°", degrees),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(DetectionError::NoFaces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use image::{GenericImageView, GrayImage, Luma};
+
+    /// 4x2 frame with a single marked pixel at (3, 0)
+    fn marked() -> DynamicImage {
+        let mut image = GrayImage::new(4, 2);
+        image.put_pixel(3, 0, Luma([255]));
+        image.into()
+    }
+
+    fn is_marked(image: &DynamicImage, x: u32, y: u32) -> bool {
+        image.as_luma8().unwrap().get_pixel(x, y).0 == [255]
+    }
+
+    #[test]
+    fn test_rotation_and_flips() {
+        let mut config = Config::default().camera;
+        config.rotate = 90;
+        let rotated = apply(marked(), &config);
+        assert_eq!(rotated.dimensions(), (2, 4));
+        assert!(is_marked(&rotated, 1, 3));
+
+        config.rotate = 0;
+        config.flip_horizontal = true;
+        assert!(is_marked(&apply(marked(), &config), 0, 0));
+
+        config.flip_horizontal = false;
+        config.flip_vertical = true;
+        assert!(is_marked(&apply(marked(), &config), 3, 1));
+    }
+
+    #[test]
+    fn test_crop_before_rotation() {
+        let mut config = Config::default().camera;
+        config.crop = Some([2, 0, 2, 2]);
+        config.rotate = 180;
+        let out = apply(marked(), &config);
+        assert_eq!(out.dimensions(), (2, 2));
+        assert!(is_marked(&out, 0, 1));
+
+        assert!(check_crop(config.crop, 4, 2).is_ok());
+        assert!(check_crop(config.crop, 3, 2).is_err());
+        assert!(check_crop(None, 1, 1).is_ok());
+        // Offsets that would wrap around
+        assert!(check_crop(Some([u32::MAX, 0, 2, 2]), 4, 2).is_err());
+        assert!(check_crop(Some([0, 1, 2, u32::MAX]), 4, 2).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use crate::align::FaceAligner;
+use crate::config::CameraConfig;
+use crate::detect::{BoundingBox, DetectedFace, FacialLandmarks};
+
+/// Maps pixel coordinates of the RGB (detection) camera into the IR
+/// (embedding) camera
+///
+/// Stored as a 2x3 affine matrix:
+/// x' = m[0][0]*x + m[0][1]*y + m[0][2]
+/// y' = m[1][0]*x + m[1][1]*y + m[1][2]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IrTransform {
+    pub matrix: [[f32; 3]; 2],
+}
+
+/// Result of `nihao calibrate-hybrid`
+#[derive(Debug, Clone, Copy)]
+pub struct Calibration {
+    pub transform: IrTransform,
+    /// Frame pairs where both cameras saw a face
+    pub pairs: usize,
+    /// Mean landmark distance after mapping, in IR pixels
+    pub mean_error: f32,
+}
+
+impl IrTransform {
+    /// Transform for a pair of frames: the configured `ir_transform` if set,
+    /// otherwise a plain rescale between the two resolutions
+    pub fn for_frames(config: &CameraConfig, rgb_size: (u32, u32), ir_size: (u32, u32)) -> Self {
+        if let Some(matrix) = config.ir_transform {
+            return Self { matrix };
+        }
+
+        let sx = ir_size.0 as f32 / rgb_size.0.max(1) as f32;
+        let sy = ir_size.1 as f32 / rgb_size.1.max(1) as f32;
+        Self {
+            matrix: [[sx, 0.0, 0.0], [0.0, sy, 0.0]],
+        }
+    }
+
+    pub fn apply(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let m = &self.matrix;
+        (
+            m[0][0] * x + m[0][1] * y + m[0][2],
+            m[1][0] * x + m[1][1] * y + m[1][2],
+        )
+    }
+
+    /// Map a face detected in the RGB frame into the IR frame
+    pub fn map_face(&self, face: &DetectedFace) -> DetectedFace {
+        let b = &face.bbox;
+        let corners = [
+            self.apply((b.x, b.y)),
+            self.apply((b.x + b.width, b.y)),
+            self.apply((b.x, b.y + b.height)),
+            self.apply((b.x + b.width, b.y + b.height)),
+        ];
+        let min_x = corners.iter().map(|p| p.0).fold(f32::INFINITY, f32::min);
+        let max_x = corners.iter().map(|p| p.0).fold(f32::NEG_INFINITY, f32::max);
+        let min_y = corners.iter().map(|p| p.1).fold(f32::INFINITY, f32::min);
+        let max_y = corners.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max);
+
+        DetectedFace {
+            bbox: BoundingBox {
+                x: min_x,
+                y: min_y,
+                width: max_x - min_x,
+                height: max_y - min_y,
+            },
+            landmarks: face.landmarks.map(|p| self.apply(p)),
+            confidence: face.confidence,
+            track: face.track,
+        }
+    }
+
+    /// Fit a similarity transform to faces seen by both cameras at once
+    ///
+    /// Each pair is (RGB landmarks, IR landmarks) from the same moment. The
+    /// cameras sit side by side, so scale, rotation and offset are enough;
+    /// the residual grows with distance from the calibration depth.
+    pub fn calibrate(pairs: &[(FacialLandmarks, FacialLandmarks)]) -> Option<Calibration> {
+        if pairs.is_empty() {
+            return None;
+        }
+
+        let rgb: Vec<(f32, f32)> = pairs.iter().flat_map(|(rgb, _)| rgb.points()).collect();
+        let ir: Vec<(f32, f32)> = pairs.iter().flat_map(|(_, ir)| ir.points()).collect();
+
+        let [a, b, tx, ty] = FaceAligner::estimate_similarity_transform(&rgb, &ir)?;
+        let transform = Self {
+            matrix: [[a, -b, tx], [b, a, ty]],
+        };
+
+        let mean_error = rgb
+            .iter()
+            .zip(&ir)
+            .map(|(&src, &(u, v))| {
+                let (x, y) = transform.apply(src);
+                ((x - u).powi(2) + (y - v).powi(2)).sqrt()
+            })
+            .sum::<f32>()
+            / rgb.len() as f32;
+
+        Some(Calibration {
+            transform,
+            pairs: pairs.len(),
+            mean_error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn landmarks(offset: (f32, f32), scale: f32) -> FacialLandmarks {
+        let l = FacialLandmarks {
+            left_eye: (100.0, 100.0),
+            right_eye: (140.0, 101.0),
+            nose: (120.0, 125.0),
+            left_mouth: (104.0, 150.0),
+            right_mouth: (136.0, 151.0),
+        };
+        l.map(|(x, y)| (x * scale + offset.0, y * scale + offset.1))
+    }
+
+    #[test]
+    fn test_default_transform_rescales() {
+        let config = Config::default().camera;
+        let t = IrTransform::for_frames(&config, (1280, 720), (640, 360));
+        assert_eq!(t.apply((100.0, 50.0)), (50.0, 25.0));
+    }
+
+    #[test]
+    fn test_map_face() {
+        let mut config = Config::default().camera;
+        config.ir_transform = Some([[1.0, 0.0, -20.0], [0.0, 1.0, 8.0]]);
+        let t = IrTransform::for_frames(&config, (640, 480), (640, 480));
+
+        let face = DetectedFace {
+            bbox: BoundingBox { x: 90.0, y: 80.0, width: 60.0, height: 80.0 },
+            landmarks: landmarks((0.0, 0.0), 1.0),
+            confidence: 0.9,
+            track: Some(3),
+        };
+        let mapped = t.map_face(&face);
+        assert_eq!((mapped.bbox.x, mapped.bbox.y), (70.0, 88.0));
+        assert_eq!((mapped.bbox.width, mapped.bbox.height), (60.0, 80.0));
+        assert_eq!(mapped.landmarks.nose, (100.0, 133.0));
+        assert_eq!(mapped.track, Some(3));
+    }
+
+    #[test]
+    fn test_calibrate_recovers_offset_and_scale() {
+        let pairs = vec![
+            (landmarks((0.0, 0.0), 1.0), landmarks((-25.0, 12.0), 0.5)),
+            (landmarks((200.0, 40.0), 1.0), landmarks((75.0, 32.0), 0.5)),
+        ];
+        let calibration = IrTransform::calibrate(&pairs).unwrap();
+        assert_eq!(calibration.pairs, 2);
+        assert!(calibration.mean_error < 0.5);
+
+        let (x, y) = calibration.transform.apply((300.0, 200.0));
+        assert!((x - 125.0).abs() < 0.5, "x = {}", x);
+        assert!((y - 112.0).abs() < 0.5, "y = {}", y);
+    }
+}
@@ -2,18 +2,40 @@ pub mod align;
 pub mod capture;
 pub mod compare;
 pub mod config;
+pub mod controls;
+pub mod denoise;
 pub mod detect;
+pub mod device;
 pub mod embed;
+pub mod fusion;
+pub mod geometry;
+pub mod hybrid;
+pub mod multiscale;
 pub mod password;
+pub mod pose;
+pub mod preprocess;
+pub mod probe;
+pub mod quality;
+pub mod retinaface;
+pub mod scrfd;
+pub mod session;
+pub mod setup;
 pub mod runtime;
+pub mod source;
 pub mod store;
+pub mod track;
+pub mod yunet;
 
-use image::{Rgb, RgbImage};
+use image::{DynamicImage, Rgb};
 use imageproc::drawing::{draw_hollow_rect_mut, draw_cross_mut};
 use imageproc::rect::Rect;
+use std::collections::HashMap;
 
 use thiserror::Error;
 
+/// Result handed back by the background model loading thread
+type LoadedModels = Result<(Box<dyn detect::FaceDetector>, embed::FaceEmbedder), Error>;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Configuration error: {0}")]
@@ -28,6 +50,8 @@ pub enum Error {
     Embedding(#[from] embed::EmbedError),
     #[error("Storage error: {0}")]
     Storage(#[from] store::StorageError),
+    #[error("Session error: {0}")]
+    Session(#[from] session::SessionError),
     #[error("Authentication timeout")]
     Timeout,
     #[error("No enrolled faces for user: {0}")]
@@ -36,13 +60,42 @@ pub enum Error {
     Other(String),
 }
 
+/// What an authentication attempt decided and what it saw
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AuthOutcome {
+    /// An enrolled face matched
+    pub matched: bool,
+    /// Most faces detected in any one frame
+    pub faces_seen: usize,
+    /// `multi_face_policy = "reject"` ended the attempt on a frame with
+    /// more than one face
+    pub multiple_faces: bool,
+}
+
+impl std::fmt::Display for AuthOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.multiple_faces {
+            return write!(f, "refused: {} faces in frame", self.faces_seen);
+        }
+        write!(f, "{}", if self.matched { "match" } else { "no match" })?;
+        if self.faces_seen > 1 {
+            write!(f, " ({} faces seen)", self.faces_seen)?;
+        }
+        Ok(())
+    }
+}
+
 pub struct FaceRecognizer {
     config: config::Config,
     runtime: runtime::OnnxRuntime,
-    camera: Option<capture::Camera>,
-    detector: Option<detect::FaceDetector>,
+    camera: Option<Box<dyn capture::FrameSource>>,
+    ir_camera: Option<Box<dyn capture::FrameSource>>, // Hybrid mode only
+    detector: Option<Box<dyn detect::FaceDetector>>,
     embedder: Option<embed::FaceEmbedder>,
     store: store::FaceStore,
+    recorder: Option<session::SessionRecorder>,
+    replay_embeddings: Option<Vec<embed::Embedding>>, // Set when replaying a session
+    orientation: Option<u32>, // Rotation that auto_orientation found faces at
 }
 
 impl FaceRecognizer {
@@ -56,22 +109,42 @@ impl FaceRecognizer {
             config,
             runtime,
             camera: None,
+            ir_camera: None,
             detector: None,
             embedder: None,
             store,
+            recorder: None,
+            replay_embeddings: None,
+            orientation: None,
         })
     }
 
+    /// Rerun a recorded session: its config, frames and enrolled faces
+    /// replace the camera and face store
+    pub fn replay(session: session::Session) -> Result<Self, Error> {
+        let (camera, ir_camera) = session.sources();
+        let mut recognizer = Self::new(session.config)?;
+        recognizer.camera = Some(Box::new(camera));
+        recognizer.ir_camera = ir_camera.map(|source| Box::new(source) as Box<dyn capture::FrameSource>);
+        recognizer.replay_embeddings = Some(session.enrolled);
+        Ok(recognizer)
+    }
+
+    /// Record the frames of every following `authenticate` call to `path`
+    pub fn record_session<P: AsRef<std::path::Path>>(&mut self, path: P) {
+        self.recorder = Some(session::SessionRecorder::new(path, &self.config));
+    }
+
+    /// How long auto-exposure took to settle in the last attempt
+    pub fn exposure_settle(&self) -> Option<capture::ExposureSettle> {
+        self.camera.as_ref().and_then(|camera| camera.exposure_settle())
+    }
+
     /// Initialize ML models (lazy initialization)
     fn ensure_models_loaded(&mut self) -> Result<(), Error> {
         if self.detector.is_none() {
             log::info!("Loading face detection model...");
-            let detector = detect::FaceDetector::new(
-                &self.config.detection.model_path,
-                &self.runtime,
-                &self.config.runtime,
-                self.config.detection.confidence_threshold,
-            )?;
+            let detector = detect::load(&self.config.detection, &self.runtime, &self.config.runtime)?;
             self.detector = Some(detector);
         }
 
@@ -92,22 +165,106 @@ impl FaceRecognizer {
     fn ensure_camera_ready(&mut self) -> Result<(), Error> {
         if self.camera.is_none() {
             log::info!("Initializing camera...");
-            let camera = capture::Camera::new(&self.config.camera)?;
+            let camera = source::open(&self.config.camera)?;
             self.camera = Some(camera);
         }
+        if self.ir_camera.is_none() {
+            if let Some(ir_config) = self.config.camera.ir_camera() {
+                log::info!("Initializing IR camera for hybrid mode...");
+                self.ir_camera = Some(source::open(&ir_config)?);
+            }
+        }
         Ok(())
     }
 
-    /// Authenticate a user by face recognition
-    /// Returns true if a match is found within the configured parameters
-    pub fn authenticate(&mut self, username: &str) -> Result<bool, Error> {
-        // Check if user has enrolled faces
-        if !self.store.has_faces(username) {
-            return Err(Error::NoEnrolledFaces(username.to_string()));
+    /// Capture a frame to detect on and, in hybrid mode, the IR frame to embed
+    ///
+    /// Frames come back with their quality reports even when rejected;
+    /// `quality_gate` picks the report that decides.
+    fn capture_frames(
+        camera: &mut dyn capture::FrameSource,
+        ir_camera: &mut Option<Box<dyn capture::FrameSource>>,
+    ) -> Result<(capture::Frame, Option<capture::Frame>), capture::CaptureError> {
+        let frame = camera.capture_with_quality()?;
+        let ir_frame = match ir_camera {
+            Some(ir_camera) => Some(ir_camera.capture_with_quality()?),
+            None => None,
+        };
+        Ok((frame, ir_frame))
+    }
+
+    /// The frame whose quality decides whether a capture is used
+    ///
+    /// Darkness checks are tuned for IR, so in hybrid mode only the IR frame
+    /// is gated.
+    fn quality_gate<'a>(frame: &'a capture::Frame, ir_frame: Option<&'a capture::Frame>) -> &'a capture::Frame {
+        ir_frame.unwrap_or(frame)
+    }
+
+    /// Stop the streams of every open camera
+    fn stop_cameras(&mut self) {
+        for camera in [self.camera.as_mut(), self.ir_camera.as_mut()].into_iter().flatten() {
+            camera.stop();
+        }
+    }
+
+    /// Rotation to try when detecting: any until auto-orientation finds one
+    ///
+    /// Hybrid mode calibrates the RGB→IR mapping for the configured
+    /// orientation, so it never searches.
+    fn detection_orientation(config: &config::CameraConfig, orientation: Option<u32>) -> Option<u32> {
+        if config.auto_orientation && !config.use_hybrid_mode {
+            orientation
+        } else {
+            Some(0)
+        }
+    }
+
+    /// Remember the rotation faces were found at and turn the frame to match
+    fn orient(orientation: &mut Option<u32>, degrees: u32, frame: DynamicImage) -> DynamicImage {
+        if orientation.is_none() {
+            if degrees != 0 {
+                log::info!("Auto-orientation: faces found rotated {}°; set rotate = {} to skip the search", degrees, degrees);
+            }
+            *orientation = Some(degrees);
         }
+        geometry::rotate(frame, degrees)
+    }
 
-        // Load enrolled embeddings
-        let enrolled_embeddings = self.store.load_embeddings(username)?;
+    /// Map a face found in the detection frame into the frame that gets
+    /// embedded: the IR frame in hybrid mode, the same frame otherwise
+    fn embedding_view<'a>(
+        config: &config::CameraConfig,
+        frame: &'a DynamicImage,
+        ir_frame: Option<&'a DynamicImage>,
+        face: &detect::DetectedFace,
+    ) -> (&'a DynamicImage, detect::DetectedFace) {
+        match ir_frame {
+            Some(ir_frame) => {
+                let transform = hybrid::IrTransform::for_frames(
+                    config,
+                    (frame.width(), frame.height()),
+                    (ir_frame.width(), ir_frame.height()),
+                );
+                let face = transform.map_face(face);
+                (ir_frame, face)
+            }
+            None => (frame, face.clone()),
+        }
+    }
+
+    /// Authenticate a user by face recognition
+    /// The outcome says whether a match was found within the configured
+    /// parameters, and how many faces were seen
+    pub fn authenticate(&mut self, username: &str) -> Result<AuthOutcome, Error> {
+        // Load enrolled embeddings (from the session when replaying)
+        let enrolled_embeddings = match &self.replay_embeddings {
+            Some(embeddings) => embeddings.clone(),
+            None if !self.store.has_faces(username) => {
+                return Err(Error::NoEnrolledFaces(username.to_string()));
+            }
+            None => self.store.load_embeddings(username)?,
+        };
         if enrolled_embeddings.is_empty() {
             return Err(Error::NoEnrolledFaces(username.to_string()));
         }
@@ -117,7 +274,8 @@ impl FaceRecognizer {
         log::debug!("Starting parallel initialization (models + camera)");
 
         let models_loaded = self.detector.is_some() && self.embedder.is_some();
-        let camera_ready = self.camera.is_some();
+        let camera_ready = self.camera.is_some()
+            && (self.ir_camera.is_some() || !self.config.camera.use_hybrid_mode);
 
         // If both already loaded, skip parallel init
         if models_loaded && camera_ready {
@@ -127,8 +285,7 @@ impl FaceRecognizer {
             use std::thread;
 
             // Shared state for passing models between threads
-            let model_result: Arc<Mutex<Option<Result<(detect::FaceDetector, embed::FaceEmbedder), Error>>>> =
-                Arc::new(Mutex::new(None));
+            let model_result: Arc<Mutex<Option<LoadedModels>>> = Arc::new(Mutex::new(None));
             let model_result_clone = Arc::clone(&model_result);
 
             let config_clone = self.config.clone();
@@ -147,12 +304,7 @@ impl FaceRecognizer {
                 };
 
                 // Load detector
-                let detector = match detect::FaceDetector::new(
-                    &config_clone.detection.model_path,
-                    &runtime,
-                    &config_clone.runtime,
-                    config_clone.detection.confidence_threshold,
-                ) {
+                let detector = match detect::load(&config_clone.detection, &runtime, &config_clone.runtime) {
                     Ok(d) => d,
                     Err(e) => {
                         *model_result_clone.lock().unwrap() = Some(Err(e.into()));
@@ -197,13 +349,49 @@ impl FaceRecognizer {
             log::debug!("✅ Parallel initialization complete");
         }
 
-        let detector = self.detector.as_mut().unwrap();
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.start(username, &enrolled_embeddings);
+        }
+
+        let result = self.match_frames(username, &enrolled_embeddings);
+
+        // Release the stream between attempts so the camera (and its LED) turns off
+        self.stop_cameras();
+
+        if let Some(recorder) = &self.recorder {
+            let outcome = match &result {
+                Ok(outcome) => outcome.to_string(),
+                Err(e) => e.to_string(),
+            };
+            if let Err(e) = recorder.finish(&outcome) {
+                log::warn!("Failed to write session recording: {}", e);
+            }
+        }
+
+        result
+    }
+
+    /// Capture frames until one matches an enrolled face, the frame budget
+    /// runs out or the timeout expires
+    fn match_frames(
+        &mut self,
+        username: &str,
+        enrolled_embeddings: &[embed::Embedding],
+    ) -> Result<AuthOutcome, Error> {
+        let detector = self.detector.as_deref_mut().unwrap();
         let embedder = self.embedder.as_mut().unwrap();
         let camera = self.camera.as_mut().unwrap();
+        let ir_camera = &mut self.ir_camera;
 
         let start_time = std::time::Instant::now();
         let max_frames = self.config.matching.max_frames;
         let timeout = std::time::Duration::from_secs(self.config.matching.timeout_secs);
+        let mut shutter = quality::ShutterDetector::new(&self.config.camera);
+        let mut denoisers: HashMap<Option<u32>, denoise::TemporalDenoiser> = HashMap::new();
+        let mut screenshot_saved = false;
+        let mut outcome = AuthOutcome::default();
+        let mut tracker = track::FaceTracker::new();
+        let mut evidence = track::Evidence::new(self.config.matching.confirm_frames);
 
         // Try multiple frames
         for frame_idx in 0..max_frames {
@@ -215,50 +403,62 @@ impl FaceRecognizer {
                 return Err(Error::Timeout);
             }
 
-            // Capture frame with quality checks
-            let frame = match camera.capture_frame(true) {
-                Ok(f) => f,
-                Err(capture::CaptureError::BadFrame(reason)) => {
-                    log::debug!("Skipping bad frame ({}), not counted", reason);
-
-                    // Save rejected frame for debugging
-                    if self.config.debug.save_screenshots {
-                        if let Ok(debug_dir) = Self::ensure_debug_dir(&self.config.debug.output_dir) {
-                            // Try to capture a raw frame to show what was rejected
-                            if let Ok(raw_frame) = camera.capture_frame(false) {
-                                let filename = Self::generate_debug_filename(username, "auth_rejected");
-                                let debug_path = debug_dir.join(filename);
-
-                                if let Err(save_err) = raw_frame.save(&debug_path) {
-                                    log::warn!("Failed to save rejected frame: {}", save_err);
-                                } else {
-                                    log::info!("❌ Rejected frame saved: {}", debug_path.display());
-                                }
-                            }
-                        }
-                    }
-
-                    continue;
+            // Capture frame with its quality report
+            let (frame, ir_frame) = match Self::capture_frames(camera.as_mut(), ir_camera) {
+                Ok(frames) => frames,
+                Err(capture::CaptureError::EndOfStream) => {
+                    log::info!("Frame source exhausted after {} frames", frame_idx);
+                    break;
                 }
+                // No point burning through the remaining frames
+                Err(e) if e.is_fatal() => return Err(e.into()),
                 Err(e) => {
                     log::warn!("Frame capture failed: {}", e);
                     continue;
                 }
             };
 
-            // Detect face (optionally on downscaled image for speed)
-            let detection_frame = if self.config.camera.detection_scale < 1.0 {
-                let (width, height) = frame.dimensions();
-                let new_width = (width as f32 * self.config.camera.detection_scale) as u32;
-                let new_height = (height as f32 * self.config.camera.detection_scale) as u32;
-                log::debug!("Downscaling for detection: {}x{} → {}x{}", width, height, new_width, new_height);
-                image::imageops::resize(&frame, new_width, new_height, image::imageops::FilterType::Triangle)
-            } else {
-                frame.clone()
-            };
+            let gate = Self::quality_gate(&frame, ir_frame.as_ref());
+            let recorded = self.recorder.as_mut().map(|recorder| {
+                recorder.record(
+                    &frame.image,
+                    ir_frame.as_ref().map(|f| &f.image),
+                    camera.last_frame_info(),
+                    &gate.quality,
+                )
+            });
+            shutter.check(&gate.quality)?;
+            if !gate.quality.is_acceptable() {
+                log::debug!("Skipping bad frame ({}), not counted", gate.quality);
+
+                // Save rejected frame for debugging
+                if self.config.debug.save_screenshots {
+                    Self::save_rejected_frame(&self.config.debug.output_dir, username, &gate.image);
+                }
+                continue;
+            }
+            let quality = gate.quality.clone();
+            let (mut frame, ir_frame) = (frame.image, ir_frame.map(|f| f.image));
+
+            if let Some(info) = camera.last_frame_info() {
+                log::debug!(
+                    "Frame {}: sequence {}, timestamp {:.3}s",
+                    frame_idx,
+                    info.sequence,
+                    info.timestamp.as_secs_f64()
+                );
+            }
 
-            let mut faces = match detector.detect(&detection_frame) {
-                Ok(f) => f,
+            // Detect face (optionally on downscaled image for speed, retrying
+            // at full resolution when that finds none)
+            let detection_input = preprocess::prepare(&frame, &self.config.camera, preprocess::Stage::Detection);
+            let known = Self::detection_orientation(&self.config.camera, self.orientation);
+            let scale = self.config.camera.detection_scale;
+            let mut faces = match multiscale::detect(detector, &detection_input, scale, &self.config.detection, known) {
+                Ok((f, degrees, _)) => {
+                    frame = Self::orient(&mut self.orientation, degrees, frame);
+                    f
+                }
                 Err(detect::DetectionError::NoFaces) => {
                     log::debug!("No face detected in frame {}", frame_idx);
                     continue;
@@ -269,103 +469,155 @@ impl FaceRecognizer {
                 }
             };
 
-            // Scale bounding boxes and landmarks back to original resolution
-            if self.config.camera.detection_scale < 1.0 {
-                let scale_factor = 1.0 / self.config.camera.detection_scale;
-                for face in &mut faces {
-                    face.bbox.x *= scale_factor;
-                    face.bbox.y *= scale_factor;
-                    face.bbox.width *= scale_factor;
-                    face.bbox.height *= scale_factor;
-
-                    // Scale landmarks
-                    face.landmarks.left_eye.0 *= scale_factor;
-                    face.landmarks.left_eye.1 *= scale_factor;
-                    face.landmarks.right_eye.0 *= scale_factor;
-                    face.landmarks.right_eye.1 *= scale_factor;
-                    face.landmarks.nose.0 *= scale_factor;
-                    face.landmarks.nose.1 *= scale_factor;
-                    face.landmarks.left_mouth.0 *= scale_factor;
-                    face.landmarks.left_mouth.1 *= scale_factor;
-                    face.landmarks.right_mouth.0 *= scale_factor;
-                    face.landmarks.right_mouth.1 *= scale_factor;
+            // Link faces to earlier frames before picking one, so IDs stay stable
+            tracker.update(&mut faces);
+            let count = faces.len();
+            outcome.faces_seen = outcome.faces_seen.max(count);
+            let policy = self.config.detection.multi_face_policy;
+            let faces = match detect::select(faces, policy, (frame.width(), frame.height())) {
+                Some(faces) => faces,
+                None => {
+                    log::warn!(
+                        "{} faces in frame {}, refusing to authenticate (multi_face_policy = reject)",
+                        count,
+                        frame_idx
+                    );
+                    outcome.multiple_faces = true;
+                    return Ok(outcome);
                 }
-            }
-
-            // Use the first (best) detected face
-            let face = &faces[0];
+            };
+            // Each face is averaged with earlier frames of its own track only
+            denoisers.retain(|track, _| faces.iter().any(|face| face.track == *track));
             log::debug!(
-                "Detected face with confidence {:.2} in frame {}",
-                face.confidence,
-                frame_idx
+                "Detected {} face(s) in frame {}, trying {} (best confidence {:.2})",
+                count,
+                frame_idx,
+                faces.len(),
+                faces[0].confidence
             );
 
-            // Save debug screenshot (only for first successful detection)
-            if self.config.debug.save_screenshots && frame_idx == 0 {
-                let debug_dir = match Self::ensure_debug_dir(&self.config.debug.output_dir) {
-                    Ok(dir) => dir,
+            // The embedding frame is preprocessed once, for the first face that passes
+            let mut prepared = None;
+            for (rank, detected) in faces.iter().enumerate() {
+                // Hybrid mode: embed the same face from the IR frame
+                let (view, face) = Self::embedding_view(&self.config.camera, &frame, ir_frame.as_ref(), detected);
+                let face = &face;
+
+                // Face-region, pose and size checks need the box and landmarks,
+                // so they run after detection
+                let mut quality = quality.clone();
+                quality.assess_geometry(face, (view.width(), view.height()), &self.config.camera);
+                quality.assess_face(view, &face.bbox, &self.config.camera);
+                log::debug!("Frame {} face {} quality: {}", frame_idx, rank, quality);
+                if let (Some(recorder), Some(index)) = (self.recorder.as_mut(), recorded) {
+                    recorder.set_verdict(index, &quality);
+                }
+                if !quality.is_acceptable() {
+                    if self.config.debug.save_screenshots {
+                        Self::save_rejected_frame(&self.config.debug.output_dir, username, view);
+                    }
+                    continue;
+                }
+
+                let frame = prepared
+                    .get_or_insert_with(|| preprocess::prepare(view, &self.config.camera, preprocess::Stage::Embedding));
+
+                // Align face
+                let align_start = std::time::Instant::now();
+                let single = match align::FaceAligner::align(frame, &face.landmarks) {
+                    Ok(a) => a,
                     Err(e) => {
-                        log::warn!("Failed to create debug directory: {}", e);
-                        return Err(e);
+                        log::warn!("Face alignment failed: {}", e);
+                        continue;
                     }
                 };
+                log::debug!("⏱️  Alignment: {}ms", align_start.elapsed().as_millis());
+
+                // Average with the previous frames of this track while the face holds still
+                let denoiser = denoisers
+                    .entry(face.track)
+                    .or_insert_with(|| denoise::TemporalDenoiser::new(&self.config.camera));
+                let denoised = denoiser.is_enabled().then(|| {
+                    let average = denoiser.push(single.clone(), face);
+                    log::debug!("Temporal average over {} frames", denoiser.depth());
+                    average
+                });
+
+                // Save debug screenshot (only for the first frame embedded at full denoising depth)
+                if self.config.debug.save_screenshots && !screenshot_saved && denoiser.is_full() {
+                    screenshot_saved = true;
+                    let debug_dir = match Self::ensure_debug_dir(&self.config.debug.output_dir) {
+                        Ok(dir) => dir,
+                        Err(e) => {
+                            log::warn!("Failed to create debug directory: {}", e);
+                            return Err(e);
+                        }
+                    };
 
-                let filename = Self::generate_debug_filename(username, "auth");
-                let debug_path = debug_dir.join(filename);
+                    let filename = Self::generate_debug_filename(username, "auth");
+                    let debug_path = debug_dir.join(filename);
+                    let crops = denoised.as_ref().map(|average| (&single, average));
 
-                if let Err(e) = Self::save_debug_visualization(&frame, face, &debug_path.to_string_lossy()) {
-                    log::warn!("Failed to save debug screenshot: {}", e);
-                } else {
-                    log::info!("Debug screenshot saved: {}", debug_path.display());
+                    if let Err(e) = Self::save_debug_visualization(frame, face, crops, &debug_path.to_string_lossy()) {
+                        log::warn!("Failed to save debug screenshot: {}", e);
+                    } else {
+                        log::info!("Debug screenshot saved: {}", debug_path.display());
+                    }
                 }
-            }
 
-            // Align face
-            let align_start = std::time::Instant::now();
-            let aligned = match align::FaceAligner::align(&frame, &face.landmarks) {
-                Ok(a) => a,
-                Err(e) => {
-                    log::warn!("Face alignment failed: {}", e);
-                    continue;
-                }
-            };
-            log::debug!("⏱️  Alignment: {}ms", align_start.elapsed().as_millis());
+                // Generate embedding
+                let embed_start = std::time::Instant::now();
+                let embedding = match embedder.embed(denoised.as_ref().unwrap_or(&single)) {
+                    Ok(e) => e,
+                    Err(e) => {
+                        log::warn!("Embedding generation failed: {}", e);
+                        continue;
+                    }
+                };
+                log::debug!("⏱️  Embedding: {}ms", embed_start.elapsed().as_millis());
+
+                // Compare with enrolled faces
+                let match_start = std::time::Instant::now();
+                if let Some(match_result) =
+                    compare::find_best_match(&embedding, enrolled_embeddings, self.config.matching.threshold)
+                {
+                    log::debug!("⏱️  Matching: {}ms", match_start.elapsed().as_millis());
+                    log::debug!("⏱️  TOTAL frame {}: {}ms", frame_idx, frame_start.elapsed().as_millis());
+
+                    // Every confirming frame must come from the same subject
+                    if !evidence.add(face.track) {
+                        log::debug!(
+                            "Match {} of {} on track {:?} (similarity {:.3})",
+                            evidence.count(),
+                            self.config.matching.confirm_frames,
+                            face.track,
+                            match_result.similarity
+                        );
+                        continue;
+                    }
 
-            // Generate embedding
-            let embed_start = std::time::Instant::now();
-            let embedding = match embedder.embed(&aligned) {
-                Ok(e) => e,
-                Err(e) => {
-                    log::warn!("Embedding generation failed: {}", e);
-                    continue;
+                    log::info!(
+                        "Face matched! Similarity: {:.3}, Face ID: {} ({} of {} faces in frame, track {:?})",
+                        match_result.similarity,
+                        match_result.face_id,
+                        rank + 1,
+                        count,
+                        face.track
+                    );
+                    outcome.matched = true;
+                    return Ok(outcome);
+                } else {
+                    log::debug!(
+                        "No match found for face {} in frame {} (best similarity below threshold)",
+                        rank,
+                        frame_idx
+                    );
                 }
-            };
-            log::debug!("⏱️  Embedding: {}ms", embed_start.elapsed().as_millis());
-
-            // Compare with enrolled faces
-            let match_start = std::time::Instant::now();
-            if let Some(match_result) =
-                compare::find_best_match(&embedding, &enrolled_embeddings, self.config.matching.threshold)
-            {
-                log::debug!("⏱️  Matching: {}ms", match_start.elapsed().as_millis());
-                log::debug!("⏱️  TOTAL frame {}: {}ms", frame_idx, frame_start.elapsed().as_millis());
-
-                log::info!(
-                    "Face matched! Similarity: {:.3}, Face ID: {}",
-                    match_result.similarity,
-                    match_result.face_id
-                );
-                return Ok(true);
-            } else {
-                log::debug!(
-                    "No match found in frame {} (best similarity below threshold)",
-                    frame_idx
-                );
             }
         }
 
         log::info!("No match found after {} frames", max_frames);
-        Ok(false)
+        Ok(outcome)
     }
 
     /// Enroll a new face for a user
@@ -385,9 +637,10 @@ impl FaceRecognizer {
         self.ensure_models_loaded()?;
         self.ensure_camera_ready()?;
 
-        let detector = self.detector.as_mut().unwrap();
+        let detector = self.detector.as_deref_mut().unwrap();
         let embedder = self.embedder.as_mut().unwrap();
         let camera = self.camera.as_mut().unwrap();
+        let ir_camera = &mut self.ir_camera;
 
         // Howdy's approach: Loop up to 60 frames, stop at first good frame with face
         const MAX_ENROLLMENT_FRAMES: u32 = 60;
@@ -398,54 +651,124 @@ impl FaceRecognizer {
             MAX_ENROLLMENT_FRAMES
         );
 
-        let (frame_for_embedding, face) = 'frame_loop: loop {
-            for attempt in 0..MAX_ENROLLMENT_FRAMES {
-                match camera.capture_frame(true) {
-                    Ok(f) => {
-                        // Got a good frame, try to detect face
-                        match detector.detect(&f) {
-                            Ok(faces) if !faces.is_empty() => {
-                                log::info!(
-                                    "Found face on frame {} with confidence {:.2}",
-                                    attempt + 1,
-                                    faces[0].confidence
-                                );
-                                break 'frame_loop (f, faces[0].clone());
-                            }
-                            Ok(_) => {
-                                log::debug!("No face in frame {}, retrying...", attempt + 1);
+        let mut shutter = quality::ShutterDetector::new(&self.config.camera);
+        let mut denoiser = denoise::TemporalDenoiser::new(&self.config.camera);
+        let mut tracker = track::FaceTracker::new();
+        let mut found = None;
+        let mut fatal = None;
+        for attempt in 0..MAX_ENROLLMENT_FRAMES {
+            match Self::capture_frames(camera.as_mut(), ir_camera) {
+                Ok((f, ir_f)) => {
+                    let mut quality = Self::quality_gate(&f, ir_f.as_ref()).quality.clone();
+                    if let Err(e) = shutter.check(&quality) {
+                        fatal = Some(e);
+                        break;
+                    }
+                    if !quality.is_acceptable() {
+                        log::debug!("Bad frame {} ({}), skipping...", attempt + 1, quality);
+                        continue;
+                    }
+                    let (f, ir_f) = (f.image, ir_f.map(|f| f.image));
+
+                    // Got a good frame, try to detect face
+                    let detection_input = preprocess::prepare(&f, &self.config.camera, preprocess::Stage::Detection);
+                    let known = Self::detection_orientation(&self.config.camera, self.orientation);
+                    match multiscale::detect(detector, &detection_input, 1.0, &self.config.detection, known) {
+                        Ok((mut faces, degrees, _)) => {
+                            let f = Self::orient(&mut self.orientation, degrees, f);
+                            tracker.update(&mut faces);
+                            let count = faces.len();
+                            let policy = self.config.detection.multi_face_policy;
+                            let Some(faces) = detect::select(faces, policy, (f.width(), f.height())) else {
+                                log::info!("{} faces in frame {}, waiting for one...", count, attempt + 1);
+                                continue;
+                            };
+                            // Only one person can be enrolled from a frame
+                            let (frame, face) =
+                                Self::embedding_view(&self.config.camera, &f, ir_f.as_ref(), &faces[0]);
+                            // Same pose and size limits as authentication, so no
+                            // profile view is ever stored
+                            quality.assess_geometry(&face, (frame.width(), frame.height()), &self.config.camera);
+                            quality.assess_face(frame, &face.bbox, &self.config.camera);
+                            if !quality.is_acceptable() {
+                                log::debug!("Face in frame {} rejected ({}), retrying...", attempt + 1, quality);
                                 continue;
                             }
-                            Err(e) => {
-                                log::debug!("Detection failed on frame {}: {}", attempt + 1, e);
+
+                            let frame =
+                                preprocess::prepare(frame, &self.config.camera, preprocess::Stage::Embedding)
+                                    .into_owned();
+                            let single = match align::FaceAligner::align(&frame, &face.landmarks) {
+                                Ok(aligned) => aligned,
+                                Err(e) => {
+                                    log::debug!("Alignment failed on frame {}: {}", attempt + 1, e);
+                                    continue;
+                                }
+                            };
+                            let denoised = denoiser.is_enabled().then(|| denoiser.push(single.clone(), &face));
+                            log::debug!("Frame quality: {}", quality);
+                            found = Some((frame, face, single, denoised));
+
+                            // Keep still while the temporal average fills up
+                            if !denoiser.is_full() {
+                                log::debug!("Averaged {} frames, collecting more...", denoiser.depth());
                                 continue;
                             }
+
+                            log::info!(
+                                "Found face on frame {} with confidence {:.2}",
+                                attempt + 1,
+                                faces[0].confidence
+                            );
+                            break;
+                        }
+                        Err(detect::DetectionError::NoFaces) => {
+                            log::debug!("No face in frame {}, retrying...", attempt + 1);
+                            continue;
+                        }
+                        Err(e) => {
+                            log::debug!("Detection failed on frame {}: {}", attempt + 1, e);
+                            continue;
                         }
                     }
-                    Err(capture::CaptureError::BadFrame(reason)) => {
-                        log::debug!("Bad frame {} ({}), skipping...", attempt + 1, reason);
-                        continue;
-                    }
-                    Err(e) => {
-                        log::warn!("Frame capture failed: {}", e);
-                        continue;
-                    }
+                }
+                Err(capture::CaptureError::EndOfStream) => {
+                    log::info!("Frame source exhausted after {} frames", attempt);
+                    break;
+                }
+                Err(e) if e.is_fatal() => {
+                    fatal = Some(e);
+                    break;
+                }
+                Err(e) => {
+                    log::warn!("Frame capture failed: {}", e);
+                    continue;
                 }
             }
+        }
+        camera.stop();
+        if let Some(ir_camera) = ir_camera.as_mut() {
+            ir_camera.stop();
+        }
+        if let Some(e) = fatal {
+            return Err(e.into());
+        }
 
-            // If we get here, we exhausted all attempts
-            return Err(Error::Other(format!(
+        // If we get here without a face, we exhausted all attempts
+        let (frame, face, single, denoised) = found.ok_or_else(|| {
+            Error::Other(format!(
                 "Could not find a clear face frame after {} attempts. Try:\n\
                  - Ensuring good lighting\n\
                  - Looking directly at camera\n\
                  - Moving closer",
                 MAX_ENROLLMENT_FRAMES
-            )));
-        };
+            ))
+        })?;
 
         log::info!("Using frame with face confidence: {:.2}", face.confidence);
-
-        let frame = frame_for_embedding;
+        if denoiser.is_enabled() {
+            log::info!("Embedding the average of {} aligned frames", denoiser.depth());
+        }
 
         // Save debug visualization (automatic or explicit path)
         let should_save = self.config.debug.save_screenshots || debug_path.is_some();
@@ -458,8 +781,9 @@ impl FaceRecognizer {
                 debug_dir.join(filename)
             };
 
+            let crops = denoised.as_ref().map(|average| (&single, average));
             if let Err(e) =
-                Self::save_debug_visualization(&frame, &face, &save_path.to_string_lossy())
+                Self::save_debug_visualization(&frame, &face, crops, &save_path.to_string_lossy())
             {
                 log::warn!("Failed to save debug screenshot: {}", e);
                 // Continue enrollment even if screenshot fails
@@ -468,13 +792,9 @@ impl FaceRecognizer {
             }
         }
 
-        // Align face
-        log::debug!("Aligning face...");
-        let aligned = align::FaceAligner::align(&frame, &face.landmarks)?;
-
         // Generate embedding
         log::debug!("Generating embedding...");
-        let embedding = embedder.embed(&aligned)?;
+        let embedding = embedder.embed(denoised.as_ref().unwrap_or(&single))?;
 
         // Save embedding
         log::debug!("Saving embedding...");
@@ -484,6 +804,61 @@ impl FaceRecognizer {
         Ok(face_id)
     }
 
+    /// Estimate `[camera] ir_transform` from faces seen by both cameras at once
+    pub fn calibrate_hybrid(&mut self, pairs: usize) -> Result<hybrid::Calibration, Error> {
+        if !self.config.camera.use_hybrid_mode {
+            return Err(Error::Other(
+                "Hybrid mode is off; set use_hybrid_mode and ir_device under [camera]".to_string(),
+            ));
+        }
+
+        self.ensure_models_loaded()?;
+        self.ensure_camera_ready()?;
+
+        let detector = self.detector.as_deref_mut().unwrap();
+        let camera = self.camera.as_mut().unwrap();
+        let ir_camera = self.ir_camera.as_mut().unwrap();
+
+        log::info!("Calibrating hybrid mode from {} frame pairs...", pairs);
+
+        let mut collected = Vec::new();
+        for attempt in 0..pairs * 10 {
+            if collected.len() >= pairs {
+                break;
+            }
+
+            let frames = camera
+                .capture_frame(false)
+                .and_then(|frame| Ok((frame, ir_camera.capture_frame(true)?)));
+            let (frame, ir_frame) = match frames {
+                Ok(frames) => frames,
+                Err(capture::CaptureError::EndOfStream) => break,
+                Err(e) => {
+                    log::debug!("Skipping frame pair {}: {}", attempt + 1, e);
+                    continue;
+                }
+            };
+
+            // Both cameras must see the face for the pair to count
+            match (detector.detect(&frame), detector.detect(&ir_frame)) {
+                (Ok(faces), Ok(ir_faces)) if !faces.is_empty() && !ir_faces.is_empty() => {
+                    log::debug!("Frame pair {}: face in both cameras", attempt + 1);
+                    collected.push((faces[0].landmarks.clone(), ir_faces[0].landmarks.clone()));
+                }
+                _ => log::debug!("Frame pair {}: face not found in both cameras", attempt + 1),
+            }
+        }
+        self.stop_cameras();
+
+        if collected.len() < pairs {
+            log::warn!("Only {} of {} frame pairs had a face in both cameras", collected.len(), pairs);
+        }
+
+        hybrid::IrTransform::calibrate(&collected).ok_or_else(|| {
+            Error::Other("No frame had a face visible to both cameras; face the cameras and retry".to_string())
+        })
+    }
+
     /// Get the face store for direct access
     pub fn store(&self) -> &store::FaceStore {
         &self.store
@@ -514,6 +889,20 @@ impl FaceRecognizer {
         Ok(expanded_path)
     }
 
+    /// Save a frame that failed the quality checks, for tuning thresholds
+    fn save_rejected_frame(output_dir: &std::path::Path, username: &str, frame: &DynamicImage) {
+        let Ok(debug_dir) = Self::ensure_debug_dir(output_dir) else {
+            return;
+        };
+        let debug_path = debug_dir.join(Self::generate_debug_filename(username, "auth_rejected"));
+
+        if let Err(save_err) = frame.save(&debug_path) {
+            log::warn!("Failed to save rejected frame: {}", save_err);
+        } else {
+            log::info!("❌ Rejected frame saved: {}", debug_path.display());
+        }
+    }
+
     /// Generate a debug screenshot filename with timestamp
     fn generate_debug_filename(username: &str, operation: &str) -> String {
         use chrono::Local;
@@ -522,12 +911,18 @@ impl FaceRecognizer {
     }
 
     /// Save debug visualization with detected face overlay
+    ///
+    /// With temporal denoising, `crops` holds the single-frame aligned face
+    /// and the average that was embedded; they are drawn side by side in the
+    /// top-left corner.
     fn save_debug_visualization(
-        frame: &RgbImage,
+        frame: &DynamicImage,
         face: &detect::DetectedFace,
+        crops: Option<(&DynamicImage, &DynamicImage)>,
         path: &str,
     ) -> Result<(), Error> {
-        let mut debug_img = frame.clone();
+        // Color overlay, even on gray frames
+        let mut debug_img = capture::rgb(frame).into_owned();
 
         // Draw bounding box in green
         let bbox = &face.bbox;
@@ -544,6 +939,12 @@ impl FaceRecognizer {
         draw_cross_mut(&mut debug_img, red, landmarks.left_mouth.0 as i32, landmarks.left_mouth.1 as i32);
         draw_cross_mut(&mut debug_img, red, landmarks.right_mouth.0 as i32, landmarks.right_mouth.1 as i32);
 
+        // Single frame, then the temporal average
+        if let Some((single, average)) = crops {
+            image::imageops::overlay(&mut debug_img, &*capture::rgb(single), 0, 0);
+            image::imageops::overlay(&mut debug_img, &*capture::rgb(average), single.width() as i64, 0);
+        }
+
         // Save the image
         debug_img.save(path)
             .map_err(|e| Error::Other(format!("Failed to save debug image: {}", e)))?;
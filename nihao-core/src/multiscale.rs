@@ -0,0 +1,267 @@
+use crate::config::{DetectionConfig, DetectionRanking, DetectionRetry};
+use crate::detect::{self, BoundingBox, DetectedFace, DetectionError, FaceDetector};
+use crate::geometry;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use std::fmt;
+
+/// Share of a tile's width or height that overlaps its neighbor; a face up
+/// to this size fits whole inside at least one tile
+const TILE_OVERLAP: f32 = 0.3;
+
+/// Which detection pass found the faces
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pass {
+    /// The frame downscaled by `detection_scale`
+    Scaled(f32),
+    /// The full-resolution frame
+    Full,
+    /// 2x2 overlapping tiles of the full-resolution frame
+    Tiles,
+}
+
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Scaled(scale) => write!(f, "{}x scale", scale),
+            Self::Full => f.write_str("full resolution"),
+            Self::Tiles => f.write_str("2x2 tiles"),
+        }
+    }
+}
+
+/// Detect faces at `scale`, retrying a frame without faces at full
+/// resolution and then on tiles as `[detection] retry` allows
+///
+/// Downscaling speeds detection up but loses the few pixels a distant face
+/// has; tiles go further and give each quarter of the frame the whole
+/// detector input. Returns the faces in full-resolution coordinates of the
+/// rotated frame, the rotation that found them and the pass that did.
+pub fn detect(
+    detector: &mut dyn FaceDetector,
+    frame: &DynamicImage,
+    scale: f32,
+    config: &DetectionConfig,
+    known: Option<u32>,
+) -> Result<(Vec<DetectedFace>, u32, Pass), DetectionError> {
+    let passes = passes(scale, config.retry);
+
+    for (index, &pass) in passes.iter().enumerate() {
+        let found = match pass {
+            Pass::Scaled(scale) => {
+                let (width, height) = (frame.width(), frame.height());
+                let (new_width, new_height) = ((width as f32 * scale) as u32, (height as f32 * scale) as u32);
+                log::debug!("Downscaling for detection: {}x{} → {}x{}", width, height, new_width, new_height);
+                let scaled = frame.resize_exact(new_width, new_height, FilterType::Triangle);
+                geometry::detect_oriented(detector, &scaled, known).map(|(faces, degrees)| {
+                    let faces = faces.iter().map(|face| transform(face, 1.0 / scale, (0.0, 0.0))).collect();
+                    (faces, degrees)
+                })
+            }
+            Pass::Full => geometry::detect_oriented(detector, frame, known),
+            Pass::Tiles => {
+                let mut tiled = Tiled {
+                    detector: &mut *detector,
+                    nms_threshold: config.nms_threshold,
+                    ranking: config.ranking,
+                };
+                geometry::detect_oriented(&mut tiled, frame, known)
+            }
+        };
+
+        match found {
+            Ok((faces, degrees)) => {
+                if index > 0 {
+                    log::info!("Face found at {} after none at {}", pass, passes[0]);
+                } else {
+                    log::debug!("Face found at {}", pass);
+                }
+                return Ok((faces, degrees, pass));
+            }
+            Err(DetectionError::NoFaces) => log::debug!("No face at {}", pass),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(DetectionError::NoFaces)
+}
+
+/// Passes to try, in order
+fn passes(scale: f32, retry: DetectionRetry) -> Vec<Pass> {
+    let mut passes = Vec::new();
+    if scale < 1.0 {
+        passes.push(Pass::Scaled(scale));
+    }
+    if scale >= 1.0 || retry != DetectionRetry::Off {
+        passes.push(Pass::Full);
+    }
+    if retry == DetectionRetry::Tiles {
+        passes.push(Pass::Tiles);
+    }
+    passes
+}
+
+/// A detector run on overlapping 2x2 tiles, with the results merged
+struct Tiled<'a> {
+    detector: &'a mut dyn FaceDetector,
+    nms_threshold: f32,
+    ranking: DetectionRanking,
+}
+
+impl FaceDetector for Tiled<'_> {
+    fn detect(&mut self, image: &DynamicImage) -> Result<Vec<DetectedFace>, DetectionError> {
+        let (width, height) = (image.width(), image.height());
+        let mut faces = Vec::new();
+
+        for tile in tiles(width, height) {
+            let crop = image.crop_imm(tile.0, tile.1, tile.2, tile.3);
+            let found = match self.detector.detect(&crop) {
+                Ok(found) => found,
+                Err(DetectionError::NoFaces) => continue,
+                Err(e) => return Err(e),
+            };
+            // A face cut by a tile edge inside the frame is whole in a neighbor
+            faces.extend(
+                found
+                    .iter()
+                    .filter(|face| !cut_by_tile(&face.bbox, tile, (width, height)))
+                    .map(|face| transform(face, 1.0, (tile.0 as f32, tile.1 as f32))),
+            );
+        }
+
+        detect::finish(faces, self.nms_threshold, self.ranking)
+    }
+
+    fn set_confidence_threshold(&mut self, threshold: f32) {
+        self.detector.set_confidence_threshold(threshold);
+    }
+}
+
+/// `(x, y, width, height)` of the four tiles covering a frame
+fn tiles(width: u32, height: u32) -> [(u32, u32, u32, u32); 4] {
+    let side = |size: u32| ((size as f32 / (2.0 - TILE_OVERLAP)).ceil() as u32).clamp(1, size.max(1));
+    let (tile_width, tile_height) = (side(width), side(height));
+    let (right, bottom) = (width.saturating_sub(tile_width), height.saturating_sub(tile_height));
+    [
+        (0, 0, tile_width, tile_height),
+        (right, 0, tile_width, tile_height),
+        (0, bottom, tile_width, tile_height),
+        (right, bottom, tile_width, tile_height),
+    ]
+}
+
+/// Whether a box in tile coordinates touches a tile edge that isn't also a
+/// frame edge
+fn cut_by_tile(bbox: &BoundingBox, (x, y, width, height): (u32, u32, u32, u32), frame: (u32, u32)) -> bool {
+    const MARGIN: f32 = 1.0;
+    (x > 0 && bbox.x <= MARGIN)
+        || (y > 0 && bbox.y <= MARGIN)
+        || (x + width < frame.0 && bbox.x + bbox.width >= width as f32 - MARGIN)
+        || (y + height < frame.1 && bbox.y + bbox.height >= height as f32 - MARGIN)
+}
+
+/// Scale a face's coordinates, then shift them
+fn transform(face: &DetectedFace, scale: f32, (dx, dy): (f32, f32)) -> DetectedFace {
+    DetectedFace {
+        bbox: BoundingBox {
+            x: face.bbox.x * scale + dx,
+            y: face.bbox.y * scale + dy,
+            width: face.bbox.width * scale,
+            height: face.bbox.height * scale,
+        },
+        landmarks: face.landmarks.map(|(x, y)| (x * scale + dx, y * scale + dy)),
+        confidence: face.confidence,
+        track: face.track,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::detect::FacialLandmarks;
+    use image::{GrayImage, Luma};
+
+    /// Finds the box around bright pixels, if it is at least `min_pixels`
+    /// wide and `min_share` of the image width, like a detector with a
+    /// fixed input that needs a few pixels per face
+    struct BrightSpot {
+        min_pixels: f32,
+        min_share: f32,
+    }
+
+    impl FaceDetector for BrightSpot {
+        fn detect(&mut self, image: &DynamicImage) -> Result<Vec<DetectedFace>, DetectionError> {
+            let gray = image.to_luma8();
+            let bright: Vec<(u32, u32)> = gray.enumerate_pixels().filter(|p| p.2 .0[0] > 128).map(|p| (p.0, p.1)).collect();
+            let (Some(min_x), Some(max_x)) = (bright.iter().map(|p| p.0).min(), bright.iter().map(|p| p.0).max()) else {
+                return Err(DetectionError::NoFaces);
+            };
+            let min_y = bright.iter().map(|p| p.1).min().unwrap();
+            let max_y = bright.iter().map(|p| p.1).max().unwrap();
+            let size = (max_x - min_x + 1) as f32;
+            if size < self.min_pixels || size < self.min_share * image.width() as f32 {
+                return Err(DetectionError::NoFaces);
+            }
+            let bbox = BoundingBox {
+                x: min_x as f32,
+                y: min_y as f32,
+                width: size,
+                height: (max_y - min_y + 1) as f32,
+            };
+            Ok(vec![DetectedFace { landmarks: FacialLandmarks::estimate(&bbox), bbox, confidence: 0.9, track: None }])
+        }
+
+        fn set_confidence_threshold(&mut self, _: f32) {}
+    }
+
+    fn frame(size: u32) -> DynamicImage {
+        let mut image = GrayImage::new(640, 480);
+        for y in 300..300 + size {
+            for x in 400..400 + size {
+                image.put_pixel(x, y, Luma([255]));
+            }
+        }
+        image.into()
+    }
+
+    fn run(size: u32, retry: DetectionRetry) -> Result<(Vec<DetectedFace>, u32, Pass), DetectionError> {
+        let mut config = Config::default().detection;
+        config.retry = retry;
+        let mut detector = BrightSpot { min_pixels: 20.0, min_share: 0.045 };
+        detect(&mut detector, &frame(size), 0.5, &config, Some(0))
+    }
+
+    #[test]
+    fn test_retry_passes() {
+        // Big enough at half scale
+        let (faces, _, pass) = run(40, DetectionRetry::Tiles).unwrap();
+        assert_eq!(pass, Pass::Scaled(0.5));
+        assert!((faces[0].bbox.x - 400.0).abs() <= 2.0 && (faces[0].bbox.width - 40.0).abs() <= 4.0);
+
+        // Too few pixels at half scale
+        assert_eq!(run(32, DetectionRetry::Full).unwrap().2, Pass::Full);
+        assert!(matches!(run(32, DetectionRetry::Off), Err(DetectionError::NoFaces)));
+
+        // Too small a share of the full frame, but not of a tile
+        assert!(matches!(run(24, DetectionRetry::Full), Err(DetectionError::NoFaces)));
+        let (faces, _, pass) = run(24, DetectionRetry::Tiles).unwrap();
+        assert_eq!(pass, Pass::Tiles);
+        assert_eq!(faces.len(), 1);
+        assert_eq!((faces[0].bbox.x, faces[0].bbox.y, faces[0].bbox.width), (400.0, 300.0, 24.0));
+    }
+
+    #[test]
+    fn test_tiles_cover_frame_with_overlap() {
+        let tiles = tiles(640, 480);
+        assert_eq!(tiles[0], (0, 0, 377, 283));
+        assert_eq!(tiles[3], (263, 197, 377, 283));
+        // Right tiles end at the frame edge and overlap the left ones
+        assert_eq!(tiles[1].0 + tiles[1].2, 640);
+        assert!(tiles[1].0 < tiles[0].2);
+
+        let tile = tiles[0];
+        let at = |x: f32| BoundingBox { x, y: 10.0, width: 30.0, height: 30.0 };
+        assert!(cut_by_tile(&at(350.0), tile, (640, 480)));
+        assert!(!cut_by_tile(&at(0.0), tile, (640, 480)));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::align::CANONICAL_LANDMARKS;
+use crate::detect::FacialLandmarks;
+use std::fmt;
+
+/// Depth of the nose tip in front of the eyes, as a fraction of the distance
+/// between them (about 32mm against 63mm for an adult face)
+const NOSE_DEPTH: f32 = 0.5;
+
+/// Head orientation in degrees, estimated from the five detector landmarks
+///
+/// Five points can't give a full 3D fit, so the face is treated as flat with
+/// the nose tip standing out by `NOSE_DEPTH`: turning the head moves the nose
+/// sideways off the eye midpoint, nodding moves it up or down between the eyes
+/// and the mouth. Good to a few degrees near frontal, which is all a gate
+/// needs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeadPose {
+    /// Positive when the nose points to the right of the image
+    pub yaw: f32,
+    /// Positive when looking down
+    pub pitch: f32,
+    /// Positive when the eye line turns clockwise in the image
+    pub roll: f32,
+}
+
+impl HeadPose {
+    pub fn estimate(landmarks: &FacialLandmarks) -> Self {
+        let (left, right) = (landmarks.left_eye, landmarks.right_eye);
+        let roll = (right.1 - left.1).atan2(right.0 - left.0);
+        let eye_distance = landmarks.eye_distance().max(f32::EPSILON);
+
+        // Level the eye line, with the eye midpoint at the origin
+        let center = ((left.0 + right.0) / 2.0, (left.1 + right.1) / 2.0);
+        let (sin, cos) = (-roll).sin_cos();
+        let upright = |(x, y): (f32, f32)| {
+            let (dx, dy) = (x - center.0, y - center.1);
+            (dx * cos - dy * sin, dx * sin + dy * cos)
+        };
+        let nose = upright(landmarks.nose);
+        let mouth = upright(midpoint(landmarks.left_mouth, landmarks.right_mouth));
+
+        // The nose shifts by depth·sin(yaw) while the eyes close up by cos(yaw)
+        let yaw = (nose.0 / (NOSE_DEPTH * eye_distance)).atan();
+
+        // Same for pitch, measured against the eye-to-mouth height
+        let (nose_height, face_height) = canonical_proportions();
+        let pitch = if mouth.1 > f32::EPSILON {
+            ((nose.1 - nose_height * mouth.1) * face_height / (NOSE_DEPTH * mouth.1)).atan()
+        } else {
+            // Mouth level with or above the eyes: upside down or garbage
+            std::f32::consts::FRAC_PI_2
+        };
+
+        Self {
+            yaw: yaw.to_degrees(),
+            pitch: pitch.to_degrees(),
+            roll: roll.to_degrees(),
+        }
+    }
+}
+
+impl fmt::Display for HeadPose {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "yaw {:.0}°, pitch {:.0}°, roll {:.0}°", self.yaw, self.pitch, self.roll)
+    }
+}
+
+fn midpoint(a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+    ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0)
+}
+
+/// Nose height between the eyes and the mouth, and eye-to-mouth height over
+/// eye distance, for a frontal face (from the ArcFace alignment template)
+fn canonical_proportions() -> (f32, f32) {
+    let [left_eye, right_eye, nose, left_mouth, right_mouth] = CANONICAL_LANDMARKS;
+    let eyes = midpoint(left_eye, right_eye);
+    let mouth = midpoint(left_mouth, right_mouth);
+    let height = mouth.1 - eyes.1;
+    ((nose.1 - eyes.1) / height, height / (right_eye.0 - left_eye.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical() -> FacialLandmarks {
+        FacialLandmarks::from_points(CANONICAL_LANDMARKS)
+    }
+
+    #[test]
+    fn test_frontal_face() {
+        let pose = HeadPose::estimate(&canonical());
+        assert!(pose.yaw.abs() < 1.0, "{}", pose);
+        assert!(pose.pitch.abs() < 1.0, "{}", pose);
+        assert!(pose.roll.abs() < 1.0, "{}", pose);
+    }
+
+    #[test]
+    fn test_turned_and_nodding() {
+        // Nose moved right by depth·tan(30°) of the eye distance
+        let mut landmarks = canonical();
+        landmarks.nose.0 = 55.9 + NOSE_DEPTH * 35.24 * 30f32.to_radians().tan();
+        let pose = HeadPose::estimate(&landmarks);
+        assert!((pose.yaw - 30.0).abs() < 1.0, "{}", pose);
+        assert!(pose.pitch.abs() < 1.0, "{}", pose);
+
+        // Nose dropped towards the mouth: looking down
+        let mut landmarks = canonical();
+        landmarks.nose.1 += 10.0;
+        assert!(HeadPose::estimate(&landmarks).pitch > 20.0);
+    }
+
+    #[test]
+    fn test_roll_leaves_yaw_and_pitch() {
+        let (sin, cos) = 20f32.to_radians().sin_cos();
+        let landmarks = canonical().map(|(x, y)| (x * cos - y * sin, x * sin + y * cos));
+        let pose = HeadPose::estimate(&landmarks);
+        assert!((pose.roll - 20.0).abs() < 0.5, "{}", pose);
+        assert!(pose.yaw.abs() < 1.0 && pose.pitch.abs() < 1.0, "{}", pose);
+    }
+}
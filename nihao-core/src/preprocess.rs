@@ -0,0 +1,322 @@
+use crate::config::{CameraConfig, PreprocessConfig};
+use crate::capture::{luma, rgb};
+use image::{DynamicImage, GrayImage, Luma, RgbImage};
+use std::borrow::Cow;
+
+/// Auto gamma never corrects further than this when `gamma_value` is 1.0
+const MAX_AUTO_GAMMA: f32 = 3.0;
+
+/// Fraction of pixels clipped at each end by histogram stretching
+const STRETCH_PERCENTILE: f32 = 0.01;
+
+/// Which model a preprocessed frame feeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Detection,
+    Embedding,
+}
+
+/// Frame as the given model should see it, borrowed when nothing is configured
+pub fn prepare<'a>(frame: &'a DynamicImage, config: &CameraConfig, stage: Stage) -> Cow<'a, DynamicImage> {
+    let settings = config.preprocess_for(stage);
+    if !settings.is_enabled() {
+        return Cow::Borrowed(frame);
+    }
+
+    let start = std::time::Instant::now();
+    let out = apply(frame, settings);
+    log::debug!("⏱️  Preprocessing ({:?}): {}ms", stage, start.elapsed().as_millis());
+    Cow::Owned(out)
+}
+
+/// Run the configured histogram stretch, gamma and CLAHE steps, in that order
+///
+/// Work happens on luminance. Gray frames come back gray; color frames keep
+/// their hue by scaling each channel with the luminance change.
+pub fn apply(image: &DynamicImage, config: &PreprocessConfig) -> DynamicImage {
+    let mut luma = luma(image).into_owned();
+
+    if config.stretch_histogram {
+        stretch_histogram(&mut luma);
+    }
+
+    let gamma = if config.auto_gamma_correction {
+        auto_gamma(&luma, config.gamma_value)
+    } else {
+        config.gamma_value
+    };
+    if (gamma - 1.0).abs() > f32::EPSILON {
+        apply_gamma(&mut luma, gamma);
+    }
+
+    if config.apply_clahe {
+        luma = clahe(&luma, config.clahe_clip_limit, config.clahe_tile_size);
+    }
+
+    match image {
+        DynamicImage::ImageLuma8(_) => DynamicImage::ImageLuma8(luma),
+        other => DynamicImage::ImageRgb8(recolor(&rgb(other), &luma)),
+    }
+}
+
+/// Carry the luminance change back to the RGB frame
+fn recolor(original: &RgbImage, luma: &GrayImage) -> RgbImage {
+    let mut out = original.clone();
+    for (pixel, new) in out.pixels_mut().zip(luma.pixels()) {
+        let [r, g, b] = pixel.0;
+        if r == g && g == b {
+            pixel.0 = [new.0[0]; 3];
+            continue;
+        }
+
+        let old = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+        let gain = new.0[0] as f32 / old.max(1.0);
+        pixel.0 = [r, g, b].map(|c| (c as f32 * gain).round().clamp(0.0, 255.0) as u8);
+    }
+    out
+}
+
+fn apply_lut(image: &mut GrayImage, lut: &[u8; 256]) {
+    for pixel in image.pixels_mut() {
+        pixel.0[0] = lut[pixel.0[0] as usize];
+    }
+}
+
+/// Map the 1st..99th percentile of luminance onto the full 0..255 range
+fn stretch_histogram(image: &mut GrayImage) {
+    let mut hist = [0u32; 256];
+    for pixel in image.pixels() {
+        hist[pixel.0[0] as usize] += 1;
+    }
+
+    let total = image.width() * image.height();
+    let clip = (total as f32 * STRETCH_PERCENTILE) as u32;
+    let percentile = |from_top: bool| {
+        let mut seen = 0;
+        let mut bins: Box<dyn Iterator<Item = usize>> =
+            if from_top { Box::new((0..256).rev()) } else { Box::new(0..256) };
+        bins.find(|&i| {
+            seen += hist[i];
+            seen > clip
+        })
+        .unwrap_or(0)
+    };
+    let (low, high) = (percentile(false), percentile(true));
+    if high <= low {
+        return; // Flat frame, nothing to stretch
+    }
+
+    let mut lut = [0u8; 256];
+    for (i, v) in lut.iter_mut().enumerate() {
+        let t = (i as f32 - low as f32) / (high - low) as f32;
+        *v = (t * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+    apply_lut(image, &lut);
+}
+
+/// Gamma that brings the mean luminance to mid-gray
+///
+/// Bright frames get gamma > 1 (darker), dim ones gamma < 1 (brighter).
+/// `limit` caps the correction in both directions.
+fn auto_gamma(image: &GrayImage, limit: f32) -> f32 {
+    let pixels = (image.width() * image.height()).max(1) as f32;
+    let mean = image.pixels().map(|p| p.0[0] as f32).sum::<f32>() / pixels / 255.0;
+    let limit = if limit > 1.0 { limit } else { MAX_AUTO_GAMMA };
+
+    let gamma = 0.5f32.ln() / mean.clamp(0.01, 0.99).ln();
+    let gamma = gamma.clamp(1.0 / limit, limit);
+    log::debug!("Auto gamma: mean {:.2} → gamma {:.2}", mean, gamma);
+    gamma
+}
+
+/// out = 255 * (in / 255) ^ gamma
+fn apply_gamma(image: &mut GrayImage, gamma: f32) {
+    let mut lut = [0u8; 256];
+    for (i, v) in lut.iter_mut().enumerate() {
+        *v = (255.0 * (i as f32 / 255.0).powf(gamma)).round() as u8;
+    }
+    apply_lut(image, &lut);
+}
+
+/// Contrast Limited Adaptive Histogram Equalization
+///
+/// The frame is split into a `tiles` x `tiles` grid. Each tile gets its own
+/// equalization curve, with histogram bins clipped at `clip_limit` times the
+/// average bin height and the excess spread evenly. Pixels blend the curves
+/// of the four nearest tile centers.
+fn clahe(image: &GrayImage, clip_limit: f32, tiles: u32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let tiles_x = tiles.clamp(1, width.max(1));
+    let tiles_y = tiles.clamp(1, height.max(1));
+    let tile_w = width.div_ceil(tiles_x);
+    let tile_h = height.div_ceil(tiles_y);
+
+    let mut luts = Vec::with_capacity((tiles_x * tiles_y) as usize);
+    for ty in 0..tiles_y {
+        for tx in 0..tiles_x {
+            let (x0, y0) = (tx * tile_w, ty * tile_h);
+            let (x1, y1) = ((x0 + tile_w).min(width), (y0 + tile_h).min(height));
+
+            let mut hist = [0u32; 256];
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    hist[image.get_pixel(x, y).0[0] as usize] += 1;
+                }
+            }
+            let count = (x1.saturating_sub(x0) * y1.saturating_sub(y0)).max(1);
+
+            // Clip and redistribute
+            let limit = ((clip_limit * count as f32 / 256.0) as u32).max(1);
+            let mut excess = 0;
+            for bin in hist.iter_mut() {
+                if *bin > limit {
+                    excess += *bin - limit;
+                    *bin = limit;
+                }
+            }
+            let (share, remainder) = (excess / 256, (excess % 256) as usize);
+            for (i, bin) in hist.iter_mut().enumerate() {
+                *bin += share + (i < remainder) as u32;
+            }
+
+            let mut lut = [0u8; 256];
+            let mut cdf = 0;
+            for (i, v) in lut.iter_mut().enumerate() {
+                cdf += hist[i];
+                *v = (cdf as f32 * 255.0 / count as f32).round().min(255.0) as u8;
+            }
+            luts.push(lut);
+        }
+    }
+
+    let lut_at = |tx: u32, ty: u32| &luts[(ty * tiles_x + tx) as usize];
+    GrayImage::from_fn(width, height, |x, y| {
+        // Position relative to tile centers
+        let fx = (x as f32 + 0.5) / tile_w as f32 - 0.5;
+        let fy = (y as f32 + 0.5) / tile_h as f32 - 0.5;
+        let tx0 = fx.floor().clamp(0.0, (tiles_x - 1) as f32) as u32;
+        let ty0 = fy.floor().clamp(0.0, (tiles_y - 1) as f32) as u32;
+        let tx1 = (tx0 + 1).min(tiles_x - 1);
+        let ty1 = (ty0 + 1).min(tiles_y - 1);
+        let ax = (fx - tx0 as f32).clamp(0.0, 1.0);
+        let ay = (fy - ty0 as f32).clamp(0.0, 1.0);
+
+        let v = image.get_pixel(x, y).0[0] as usize;
+        let top = lut_at(tx0, ty0)[v] as f32 * (1.0 - ax) + lut_at(tx1, ty0)[v] as f32 * ax;
+        let bottom = lut_at(tx0, ty1)[v] as f32 * (1.0 - ax) + lut_at(tx1, ty1)[v] as f32 * ax;
+        Luma([(top * (1.0 - ay) + bottom * ay).round() as u8])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    fn gradient(low: u8, high: u8) -> DynamicImage {
+        RgbImage::from_fn(64, 64, |x, _| {
+            let v = low as u32 + (high - low) as u32 * x / 63;
+            Rgb([v as u8; 3])
+        })
+        .into()
+    }
+
+    fn to_luma(image: &DynamicImage) -> GrayImage {
+        luma(image).into_owned()
+    }
+
+    #[test]
+    fn test_disabled_is_identity() {
+        let frame = gradient(40, 200);
+        assert_eq!(apply(&frame, &PreprocessConfig::default()), frame);
+    }
+
+    #[test]
+    fn test_per_stage_settings() {
+        let mut config = crate::config::Config::default().camera;
+        config.preprocess.apply_clahe = true;
+        config.detection_preprocess = Some(PreprocessConfig::default());
+
+        let frame = gradient(110, 130);
+        assert!(matches!(prepare(&frame, &config, Stage::Detection), Cow::Borrowed(_)));
+        assert!(matches!(prepare(&frame, &config, Stage::Embedding), Cow::Owned(_)));
+    }
+
+    #[test]
+    fn test_config_tables() {
+        let config: CameraConfig = toml::from_str(
+            r#"
+            device = "/dev/video2"
+            width = 640
+            height = 480
+            apply_clahe = true
+            clahe_clip_limit = 3.0
+            gamma_value = 2.0
+
+            [embedding_preprocess]
+            stretch_histogram = true
+            "#,
+        )
+        .unwrap();
+
+        let detection = config.preprocess_for(Stage::Detection);
+        assert!(detection.apply_clahe);
+        assert_eq!(detection.clahe_clip_limit, 3.0);
+        assert_eq!(detection.gamma_value, 2.0);
+
+        let embedding = config.preprocess_for(Stage::Embedding);
+        assert!(embedding.stretch_histogram);
+        assert!(!embedding.apply_clahe);
+    }
+
+    #[test]
+    fn test_stretch_histogram_fills_range() {
+        let config = PreprocessConfig {
+            stretch_histogram: true,
+            ..PreprocessConfig::default()
+        };
+        let out = apply(&gradient(100, 150), &config).into_rgb8();
+        assert!(out.get_pixel(0, 0).0[0] < 10);
+        assert!(out.get_pixel(63, 0).0[0] > 245);
+
+        // Gray in, gray out
+        let gray = DynamicImage::ImageLuma8(to_luma(&gradient(100, 150)));
+        let out = apply(&gray, &config);
+        assert_eq!(out.as_luma8().map(|g| g.get_pixel(63, 0).0[0] > 245), Some(true));
+    }
+
+    #[test]
+    fn test_gamma() {
+        let mut dark = to_luma(&gradient(128, 128));
+        apply_gamma(&mut dark, 2.0);
+        assert_eq!(dark.get_pixel(0, 0).0[0], 64);
+
+        // Bright frames are darkened, dim ones brightened
+        assert!(auto_gamma(&to_luma(&gradient(200, 240)), 2.0) > 1.0);
+        assert!(auto_gamma(&to_luma(&gradient(10, 40)), 2.0) < 1.0);
+        assert_eq!(auto_gamma(&to_luma(&gradient(250, 255)), 2.0), 2.0);
+    }
+
+    #[test]
+    fn test_clahe_boosts_low_contrast() {
+        let luma = to_luma(&gradient(110, 130));
+        let out = clahe(&luma, 4.0, 2);
+        let spread = |img: &GrayImage| {
+            let values: Vec<u8> = img.pixels().map(|p| p.0[0]).collect();
+            values.iter().max().unwrap() - values.iter().min().unwrap()
+        };
+        assert!(spread(&out) > spread(&luma));
+    }
+
+    #[test]
+    fn test_color_keeps_hue() {
+        let frame = RgbImage::from_pixel(8, 8, Rgb([100, 50, 25])).into();
+        let config = PreprocessConfig {
+            gamma_value: 0.5,
+            ..PreprocessConfig::default()
+        };
+        let [r, g, b] = apply(&frame, &config).into_rgb8().get_pixel(0, 0).0;
+        assert!(r > 100 && g > 50 && b > 25);
+        assert!(r > g && g > b);
+    }
+}
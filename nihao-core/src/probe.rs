@@ -0,0 +1,309 @@
+use crate::capture::{Camera, CaptureError, FrameSource};
+use crate::config::Config;
+use crate::device::{self, DeviceInfo};
+use image::RgbImage;
+use serde::Serialize;
+use std::path::PathBuf;
+use v4l::framesize::FrameSizeEnum;
+use v4l::frameinterval::FrameIntervalEnum;
+use v4l::video::Capture as _;
+use v4l::{Device, FourCC};
+
+/// Frames read when sampling content for IR detection
+const SAMPLE_FRAMES: usize = 8;
+
+/// Mean per-pixel channel spread below which a frame counts as grayscale
+const GRAY_CHROMA: f32 = 3.0;
+
+/// Frames darker than this say nothing about color
+const MIN_SAMPLE_BRIGHTNESS: f32 = 10.0;
+
+/// Frame-to-frame brightness swing, relative to the mean, that counts as
+/// an emitter lighting every other frame
+const FLICKER_RATIO: f32 = 0.25;
+
+/// Pixel formats only monochrome sensors offer
+const GRAY_FOURCCS: [&str; 4] = ["GREY", "Y10 ", "Y12 ", "Y16 "];
+
+/// Everything `nihao devices` reports about one capture node
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceReport {
+    pub path: PathBuf,
+    /// `/dev/v4l/by-id` and `by-path` links to this node, stable across reboots
+    pub links: Vec<PathBuf>,
+    pub card: String,
+    pub driver: String,
+    pub bus_info: String,
+    pub usb_id: Option<String>,
+    pub formats: Vec<FormatInfo>,
+    pub ir: IrAssessment,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FormatInfo {
+    pub fourcc: String,
+    pub description: String,
+    pub sizes: Vec<FrameSizeInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameSizeInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Discrete frame rates; empty if the driver reports none
+    pub fps: Vec<f32>,
+    /// Driver accepts any size up to this one in steps (stepwise sizes)
+    pub stepwise: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IrLikelihood {
+    Unlikely,
+    Possible,
+    Likely,
+}
+
+/// Evidence that a node is an infrared camera
+///
+/// `grayscale` and `flicker` are `None` when frames were not sampled or were
+/// too dark to judge.
+#[derive(Debug, Clone, Serialize)]
+pub struct IrAssessment {
+    pub likelihood: IrLikelihood,
+    /// Card name mentions IR, infrared or depth
+    pub name: bool,
+    /// Only monochrome pixel formats are offered
+    pub gray_formats: bool,
+    pub grayscale: Option<bool>,
+    /// Brightness alternates between frames, as with a pulsed emitter
+    pub flicker: Option<bool>,
+    /// Why sampling was skipped or failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_error: Option<String>,
+}
+
+impl IrAssessment {
+    fn new(name: bool, gray_formats: bool, grayscale: Option<bool>, flicker: Option<bool>) -> Self {
+        let score = [Some(name), Some(gray_formats), grayscale, flicker]
+            .into_iter()
+            .filter(|e| *e == Some(true))
+            .count();
+        let likelihood = match score {
+            0 => IrLikelihood::Unlikely,
+            1 => IrLikelihood::Possible,
+            _ => IrLikelihood::Likely,
+        };
+        Self {
+            likelihood,
+            name,
+            gray_formats,
+            grayscale,
+            flicker,
+            sample_error: None,
+        }
+    }
+}
+
+/// Probe every capture node, optionally streaming a few frames from each
+pub fn probe_all(sample: bool) -> Result<Vec<DeviceReport>, CaptureError> {
+    Ok(device::list_capture_devices()?
+        .iter()
+        .map(|info| probe(info, sample))
+        .collect())
+}
+
+/// Describe one capture node
+///
+/// Format enumeration failures leave the list empty rather than failing, so
+/// a half-broken node still shows up with its identity.
+pub fn probe(info: &DeviceInfo, sample: bool) -> DeviceReport {
+    let formats = match Device::with_path(&info.path) {
+        Ok(device) => list_formats(&device),
+        Err(e) => {
+            log::debug!("{}: {}", info.path.display(), e);
+            Vec::new()
+        }
+    };
+
+    let gray_formats = !formats.is_empty() && formats.iter().all(|f| GRAY_FOURCCS.contains(&f.fourcc.as_str()));
+    let (grayscale, flicker, sample_error) = if sample {
+        match sample_frames(info) {
+            Ok(frames) => (is_grayscale(&frames), has_flicker(&frames), None),
+            Err(e) => (None, None, Some(e.to_string())),
+        }
+    } else {
+        (None, None, Some("not sampled".to_string()))
+    };
+
+    let mut ir = IrAssessment::new(info.looks_ir(), gray_formats, grayscale, flicker);
+    ir.sample_error = sample_error;
+
+    DeviceReport {
+        path: info.path.clone(),
+        links: stable_links(&info.path),
+        card: info.card.clone(),
+        driver: info.driver.clone(),
+        bus_info: info.bus_info.clone(),
+        usb_id: info.usb_id.map(|id| id.to_string()),
+        formats,
+        ir,
+    }
+}
+
+fn list_formats(device: &Device) -> Vec<FormatInfo> {
+    let descriptions = device.enum_formats().unwrap_or_default();
+    descriptions
+        .into_iter()
+        .map(|desc| FormatInfo {
+            fourcc: desc.fourcc.str().unwrap_or("????").to_string(),
+            description: desc.description,
+            sizes: list_sizes(device, desc.fourcc),
+        })
+        .collect()
+}
+
+fn list_sizes(device: &Device, fourcc: FourCC) -> Vec<FrameSizeInfo> {
+    let sizes = device.enum_framesizes(fourcc).unwrap_or_default();
+    sizes
+        .into_iter()
+        .map(|size| {
+            let (width, height, stepwise) = match size.size {
+                FrameSizeEnum::Discrete(d) => (d.width, d.height, false),
+                FrameSizeEnum::Stepwise(s) => (s.max_width, s.max_height, true),
+            };
+            FrameSizeInfo {
+                width,
+                height,
+                fps: list_rates(device, fourcc, width, height),
+                stepwise,
+            }
+        })
+        .collect()
+}
+
+fn list_rates(device: &Device, fourcc: FourCC, width: u32, height: u32) -> Vec<f32> {
+    let intervals = device.enum_frameintervals(fourcc, width, height).unwrap_or_default();
+    intervals
+        .into_iter()
+        .filter_map(|interval| match interval.interval {
+            FrameIntervalEnum::Discrete(f) if f.numerator > 0 => Some(f.denominator as f32 / f.numerator as f32),
+            // Stepwise: report the fastest rate
+            FrameIntervalEnum::Stepwise(s) if s.min.numerator > 0 => {
+                Some(s.min.denominator as f32 / s.min.numerator as f32)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// `/dev/v4l/by-id` and `/dev/v4l/by-path` symlinks resolving to `path`
+pub fn stable_links(path: &std::path::Path) -> Vec<PathBuf> {
+    let Ok(target) = std::fs::canonicalize(path) else {
+        return Vec::new();
+    };
+    let mut links: Vec<PathBuf> = ["/dev/v4l/by-id", "/dev/v4l/by-path"]
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|link| std::fs::canonicalize(link).is_ok_and(|t| t == target))
+        .collect();
+    links.sort();
+    links
+}
+
+/// Stream a few frames at the default resolution, in RGB to judge color
+fn sample_frames(info: &DeviceInfo) -> Result<Vec<RgbImage>, CaptureError> {
+    let mut config = Config::default().camera;
+    config.device = info.path.to_string_lossy().into_owned();
+
+    let mut camera = Camera::new(&config)?;
+    let frames = (0..SAMPLE_FRAMES)
+        .map(|_| camera.next_frame().map(|frame| frame.into_rgb8()))
+        .collect::<Result<Vec<_>, _>>();
+    camera.stop();
+    frames
+}
+
+fn mean_brightness(frame: &RgbImage) -> f32 {
+    let total: u64 = frame.pixels().map(|p| p.0.iter().map(|&c| c as u64).sum::<u64>()).sum();
+    total as f32 / (frame.width() * frame.height() * 3).max(1) as f32
+}
+
+/// Whether the frames carry no color, `None` when all are too dark to tell
+fn is_grayscale(frames: &[RgbImage]) -> Option<bool> {
+    let lit: Vec<&RgbImage> = frames.iter().filter(|f| mean_brightness(f) >= MIN_SAMPLE_BRIGHTNESS).collect();
+    if lit.is_empty() {
+        return None;
+    }
+
+    let chroma = lit
+        .iter()
+        .map(|frame| {
+            let spread: u64 = frame
+                .pixels()
+                .map(|p| (p.0.iter().max().unwrap() - p.0.iter().min().unwrap()) as u64)
+                .sum();
+            spread as f32 / (frame.width() * frame.height()).max(1) as f32
+        })
+        .sum::<f32>()
+        / lit.len() as f32;
+    Some(chroma < GRAY_CHROMA)
+}
+
+/// Whether brightness alternates frame to frame, `None` with too few frames
+fn has_flicker(frames: &[RgbImage]) -> Option<bool> {
+    if frames.len() < 4 {
+        return None;
+    }
+
+    let means: Vec<f32> = frames.iter().map(mean_brightness).collect();
+    let average = means.iter().sum::<f32>() / means.len() as f32;
+    if average < 1.0 {
+        return None;
+    }
+
+    let steps: Vec<f32> = means.windows(2).map(|w| w[1] - w[0]).collect();
+    let alternating = steps.windows(2).all(|s| s[0] * s[1] < 0.0);
+    let swing = steps.iter().map(|s| s.abs()).sum::<f32>() / steps.len() as f32;
+    Some(alternating && swing > FLICKER_RATIO * average)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    fn frames(values: &[[u8; 3]]) -> Vec<RgbImage> {
+        values.iter().map(|&v| RgbImage::from_pixel(4, 4, Rgb(v))).collect()
+    }
+
+    #[test]
+    fn test_grayscale() {
+        assert_eq!(is_grayscale(&frames(&[[90, 90, 90], [91, 90, 92]])), Some(true));
+        assert_eq!(is_grayscale(&frames(&[[120, 90, 60]])), Some(false));
+        // Black frames from a covered RGB camera prove nothing
+        assert_eq!(is_grayscale(&frames(&[[2, 2, 2], [0, 0, 0]])), None);
+    }
+
+    #[test]
+    fn test_flicker() {
+        let lit = [120, 120, 120];
+        let dark = [15, 15, 15];
+        assert_eq!(has_flicker(&frames(&[lit, dark, lit, dark, lit, dark])), Some(true));
+        assert_eq!(has_flicker(&frames(&[lit, lit, lit, lit])), Some(false));
+        // Slow drift is auto-exposure, not an emitter
+        assert_eq!(has_flicker(&frames(&[[100; 3], [110; 3], [120; 3], [130; 3]])), Some(false));
+        assert_eq!(has_flicker(&frames(&[lit, dark])), None);
+    }
+
+    #[test]
+    fn test_likelihood() {
+        assert_eq!(IrAssessment::new(false, false, None, None).likelihood, IrLikelihood::Unlikely);
+        assert_eq!(IrAssessment::new(true, false, None, None).likelihood, IrLikelihood::Possible);
+        assert_eq!(IrAssessment::new(false, true, Some(true), Some(false)).likelihood, IrLikelihood::Likely);
+        assert_eq!(IrAssessment::new(false, false, Some(false), Some(false)).likelihood, IrLikelihood::Unlikely);
+    }
+}
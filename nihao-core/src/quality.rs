@@ -0,0 +1,511 @@
+use crate::capture::CaptureError;
+use crate::config::CameraConfig;
+use crate::detect::{BoundingBox, DetectedFace};
+use crate::pose::HeadPose;
+use image::{DynamicImage, GrayImage, Luma};
+use std::borrow::Cow;
+use std::fmt;
+
+/// Why a frame was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityIssue {
+    /// Every pixel is 0, a bad camera read
+    Black,
+    TooDark,
+    Overexposed,
+    Noisy,
+    /// Face region below `min_sharpness`, usually motion blur
+    Blurry,
+    FaceTooDark,
+    FaceTooBright,
+    /// A landmark falls outside the frame
+    FaceCutOff,
+    /// Face box below `min_face_fraction` of the frame
+    FaceTooSmall,
+    /// Eyes closer than `min_eye_distance` pixels, too few for a good crop
+    EyesTooClose,
+    /// Yaw beyond `max_yaw`
+    HeadTurned,
+    /// Pitch beyond `max_pitch`
+    HeadPitched,
+    /// Roll beyond `max_roll`
+    HeadTilted,
+}
+
+impl QualityIssue {
+    /// Short reason code for logs
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Black => "black",
+            Self::TooDark => "too_dark",
+            Self::Overexposed => "overexposed",
+            Self::Noisy => "noisy",
+            Self::Blurry => "blurry",
+            Self::FaceTooDark => "face_too_dark",
+            Self::FaceTooBright => "face_too_bright",
+            Self::FaceCutOff => "face_cut_off",
+            Self::FaceTooSmall => "face_too_small",
+            Self::EyesTooClose => "eyes_too_close",
+            Self::HeadTurned => "head_turned",
+            Self::HeadPitched => "head_pitched",
+            Self::HeadTilted => "head_tilted",
+        }
+    }
+}
+
+impl fmt::Display for QualityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// Quality measurements for one frame, with the thresholds it failed
+///
+/// Frame-level metrics are filled in at capture. Face brightness and
+/// sharpness are added by `assess_face` once a face has been detected, since
+/// a sharp face in front of a flat background should not count as blurry;
+/// head pose and face size by `assess_geometry`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameQuality {
+    /// Pixels in the darkest eighth of the range, percent
+    pub darkness_pct: f32,
+    /// Pixels above `overexposure_level`, percent
+    pub overexposed_pct: f32,
+    /// Estimated noise standard deviation in gray levels
+    pub noise: f32,
+    /// Mean luminance inside the face box
+    pub face_brightness: Option<f32>,
+    /// Variance of the Laplacian inside the face box
+    pub face_sharpness: Option<f32>,
+    /// Head pose estimated from the landmarks
+    pub pose: Option<HeadPose>,
+    /// Pixels between the eyes
+    pub eye_distance: Option<f32>,
+    /// Face box area over frame area
+    pub face_fraction: Option<f32>,
+    pub issues: Vec<QualityIssue>,
+}
+
+impl FrameQuality {
+    /// Measure a frame and check it against the `[camera]` thresholds
+    pub fn assess(image: &DynamicImage, config: &CameraConfig) -> Self {
+        let gray = luma(image);
+        let mut issues = Vec::new();
+
+        let (darkness_pct, black) = darkness(&gray);
+        if black {
+            log::warn!("Frame is 100% black - bad camera read, skipping");
+            issues.push(QualityIssue::Black);
+        } else if darkness_pct > config.dark_threshold {
+            issues.push(QualityIssue::TooDark);
+        }
+
+        let overexposed_pct = overexposure(&gray, config.overexposure_level);
+        if overexposed_pct > config.overexposure_threshold {
+            issues.push(QualityIssue::Overexposed);
+        }
+
+        // Temporal denoising averages the face over `denoise_frames`, which
+        // divides the noise by their square root; judge what gets embedded
+        let noise = noise_sigma(&gray);
+        let max_noise = config.max_noise * (config.denoise_frames.max(1) as f32).sqrt();
+        if noise > max_noise {
+            issues.push(QualityIssue::Noisy);
+        }
+
+        Self {
+            darkness_pct,
+            overexposed_pct,
+            noise,
+            face_brightness: None,
+            face_sharpness: None,
+            pose: None,
+            eye_distance: None,
+            face_fraction: None,
+            issues,
+        }
+    }
+
+    /// Add face-region brightness and sharpness and check them
+    pub fn assess_face(&mut self, image: &DynamicImage, bbox: &BoundingBox, config: &CameraConfig) {
+        let x = bbox.x.max(0.0) as u32;
+        let y = bbox.y.max(0.0) as u32;
+        if x >= image.width() || y >= image.height() {
+            return;
+        }
+        let width = (bbox.width.max(0.0) as u32).min(image.width() - x);
+        let height = (bbox.height.max(0.0) as u32).min(image.height() - y);
+        if width < 3 || height < 3 {
+            return;
+        }
+
+        let face = luma(image);
+        let face = image::imageops::crop_imm(&*face, x, y, width, height).to_image();
+        let brightness = face.pixels().map(|p| p.0[0] as f32).sum::<f32>() / (width * height) as f32;
+        let sharpness = laplacian_variance(&face);
+
+        if sharpness < config.min_sharpness {
+            self.issues.push(QualityIssue::Blurry);
+        }
+        if brightness < config.min_face_brightness {
+            self.issues.push(QualityIssue::FaceTooDark);
+        } else if brightness > config.max_face_brightness {
+            self.issues.push(QualityIssue::FaceTooBright);
+        }
+
+        self.face_brightness = Some(brightness);
+        self.face_sharpness = Some(sharpness);
+    }
+
+    /// Add head pose, eye distance and face size and check them; `frame` is
+    /// the size of the image the face was found in
+    pub fn assess_geometry(&mut self, face: &DetectedFace, (width, height): (u32, u32), config: &CameraConfig) {
+        let pose = HeadPose::estimate(&face.landmarks);
+        let eye_distance = face.landmarks.eye_distance();
+        let face_fraction = face.bbox.area() / (width as f32 * height as f32).max(1.0);
+
+        let inside = |&(x, y): &(f32, f32)| x >= 0.0 && y >= 0.0 && x < width as f32 && y < height as f32;
+        if !face.landmarks.points().iter().all(inside) {
+            self.issues.push(QualityIssue::FaceCutOff);
+        }
+        if face_fraction < config.min_face_fraction {
+            self.issues.push(QualityIssue::FaceTooSmall);
+        }
+        if eye_distance < config.min_eye_distance {
+            self.issues.push(QualityIssue::EyesTooClose);
+        }
+        if pose.yaw.abs() > config.max_yaw {
+            self.issues.push(QualityIssue::HeadTurned);
+        }
+        if pose.pitch.abs() > config.max_pitch {
+            self.issues.push(QualityIssue::HeadPitched);
+        }
+        if pose.roll.abs() > config.max_roll {
+            self.issues.push(QualityIssue::HeadTilted);
+        }
+
+        self.pose = Some(pose);
+        self.eye_distance = Some(eye_distance);
+        self.face_fraction = Some(face_fraction);
+    }
+
+    pub fn is_acceptable(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// `BadFrame` carrying this report if any threshold failed
+    pub fn check(&self) -> Result<(), CaptureError> {
+        if self.is_acceptable() {
+            Ok(())
+        } else {
+            Err(CaptureError::BadFrame(self.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for FrameQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "dark {:.1}%, blown {:.1}%, noise {:.1}",
+            self.darkness_pct, self.overexposed_pct, self.noise
+        )?;
+        if let (Some(brightness), Some(sharpness)) = (self.face_brightness, self.face_sharpness) {
+            write!(f, ", face brightness {:.0}, face sharpness {:.1}", brightness, sharpness)?;
+        }
+        if let (Some(pose), Some(eyes), Some(fraction)) = (self.pose, self.eye_distance, self.face_fraction) {
+            write!(f, ", {}, eyes {:.0}px, face {:.1}% of frame", pose, eyes, fraction * 100.0)?;
+        }
+        if !self.issues.is_empty() {
+            let codes: Vec<&str> = self.issues.iter().map(|i| i.code()).collect();
+            write!(f, " [{}]", codes.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Tells a closed privacy shutter from an occasional bad read by counting
+/// fully black frames in a row
+#[derive(Debug, Clone)]
+pub struct ShutterDetector {
+    limit: u32,
+    run: u32,
+}
+
+impl ShutterDetector {
+    pub fn new(config: &CameraConfig) -> Self {
+        Self {
+            limit: config.shutter_frames,
+            run: 0,
+        }
+    }
+
+    /// `ShutterClosed` once `shutter_frames` black frames have arrived in a row
+    pub fn check(&mut self, quality: &FrameQuality) -> Result<(), CaptureError> {
+        if !quality.issues.contains(&QualityIssue::Black) {
+            self.run = 0;
+            return Ok(());
+        }
+
+        self.run += 1;
+        if self.limit > 0 && self.run >= self.limit {
+            return Err(CaptureError::ShutterClosed(self.run));
+        }
+        Ok(())
+    }
+}
+
+/// Channel average (as Howdy measures), borrowed for gray frames
+fn luma(image: &DynamicImage) -> Cow<'_, GrayImage> {
+    match image {
+        DynamicImage::ImageLuma8(gray) => Cow::Borrowed(gray),
+        other => {
+            let rgb = crate::capture::rgb(other);
+            Cow::Owned(GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+                let p = rgb.get_pixel(x, y);
+                Luma([((p[0] as u16 + p[1] as u16 + p[2] as u16) / 3) as u8])
+            }))
+        }
+    }
+}
+
+/// Share of pixels in the darkest of 8 histogram bins, and whether the
+/// frame is entirely black
+/// Based on Howdy's approach: compare.py:254-274
+fn darkness(gray: &GrayImage) -> (f32, bool) {
+    let total = gray.pixels().len().max(1);
+    let dark = gray.pixels().filter(|p| p.0[0] < 32).count();
+    let black = gray.pixels().all(|p| p.0[0] == 0);
+    (dark as f32 / total as f32 * 100.0, black)
+}
+
+/// Share of pixels brighter than `level`, percent
+fn overexposure(gray: &GrayImage, level: u8) -> f32 {
+    let total = gray.pixels().len().max(1);
+    let bright = gray.pixels().filter(|p| p.0[0] > level).count();
+    bright as f32 / total as f32 * 100.0
+}
+
+/// Variance of the 4-neighbour Laplacian; low for blurred images
+fn laplacian_variance(gray: &GrayImage) -> f32 {
+    let (width, height) = gray.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+
+    let at = |x: u32, y: u32| gray.get_pixel(x, y).0[0] as f32;
+    let mut sum = 0.0f64;
+    let mut sum_sq = 0.0f64;
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let v = (at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4.0 * at(x, y)) as f64;
+            sum += v;
+            sum_sq += v * v;
+        }
+    }
+
+    let n = ((width - 2) * (height - 2)) as f64;
+    let mean = sum / n;
+    (sum_sq / n - mean * mean) as f32
+}
+
+/// Noise standard deviation (Immerkær, "Fast Noise Variance Estimation")
+///
+/// The kernel is the difference of two Laplacians, which cancels image
+/// structure and leaves mostly sensor noise.
+fn noise_sigma(gray: &GrayImage) -> f32 {
+    let (width, height) = gray.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+
+    const KERNEL: [[f32; 3]; 3] = [[1.0, -2.0, 1.0], [-2.0, 4.0, -2.0], [1.0, -2.0, 1.0]];
+    let mut sum = 0.0f64;
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let mut v = 0.0;
+            for (ky, row) in KERNEL.iter().enumerate() {
+                for (kx, k) in row.iter().enumerate() {
+                    v += k * gray.get_pixel(x + kx as u32 - 1, y + ky as u32 - 1).0[0] as f32;
+                }
+            }
+            sum += v.abs() as f64;
+        }
+    }
+
+    let n = ((width - 2) * (height - 2)) as f64;
+    ((std::f64::consts::PI / 2.0).sqrt() * sum / (6.0 * n)) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::detect::FacialLandmarks;
+    use image::{Rgb, RgbImage};
+
+    /// Sharp vertical stripes
+    fn stripes() -> DynamicImage {
+        RgbImage::from_fn(64, 64, |x, _| if (x / 4) % 2 == 0 { Rgb([60; 3]) } else { Rgb([180; 3]) }).into()
+    }
+
+    #[test]
+    fn test_dark_and_black_frames() {
+        let config = Config::default().camera;
+
+        let black = FrameQuality::assess(&RgbImage::new(8, 8).into(), &config);
+        assert_eq!(black.issues, vec![QualityIssue::Black]);
+        assert!(black.check().is_err());
+
+        let dim = FrameQuality::assess(&RgbImage::from_pixel(8, 8, Rgb([10; 3])).into(), &config);
+        assert_eq!(dim.issues, vec![QualityIssue::TooDark]);
+        assert_eq!(dim.darkness_pct, 100.0);
+    }
+
+    #[test]
+    fn test_overexposure_thresholds() {
+        let mut config = Config::default().camera;
+        let bright = RgbImage::from_pixel(8, 8, Rgb([235; 3])).into();
+        assert!(FrameQuality::assess(&bright, &config).is_acceptable());
+
+        config.overexposure_level = 230;
+        let quality = FrameQuality::assess(&bright, &config);
+        assert_eq!(quality.issues, vec![QualityIssue::Overexposed]);
+        assert_eq!(quality.overexposed_pct, 100.0);
+    }
+
+    #[test]
+    fn test_blurred_face_is_rejected() {
+        let config = Config::default().camera;
+        let sharp = stripes();
+        let blurred = sharp.blur(4.0);
+        let bbox = BoundingBox { x: 8.0, y: 8.0, width: 48.0, height: 48.0 };
+
+        let mut quality = FrameQuality::assess(&sharp, &config);
+        quality.assess_face(&sharp, &bbox, &config);
+        assert!(quality.is_acceptable(), "{}", quality);
+
+        let mut quality = FrameQuality::assess(&blurred, &config);
+        quality.assess_face(&blurred, &bbox, &config);
+        assert!(quality.issues.contains(&QualityIssue::Blurry), "{}", quality);
+    }
+
+    #[test]
+    fn test_noise_estimate() {
+        let flat = luma(&RgbImage::from_pixel(32, 32, Rgb([128; 3])).into()).into_owned();
+        assert_eq!(noise_sigma(&flat), 0.0);
+
+        // Deterministic pseudo-random noise of roughly ±20 levels
+        let noisy = GrayImage::from_fn(32, 32, |x, y| {
+            let n = (x * 7919 + y * 104729) % 41;
+            Luma([(108 + n) as u8])
+        });
+        assert!(noise_sigma(&noisy) > 5.0);
+    }
+
+    #[test]
+    fn test_denoising_relaxes_noise_gate() {
+        let grainy: DynamicImage = GrayImage::from_fn(32, 32, |x, y| {
+            let n = (x * 7919 + y * 104729) % 41;
+            Luma([(108 + n) as u8])
+        })
+        .into();
+        let mut config = Config::default().camera;
+        config.max_noise = noise_sigma(&luma(&grainy)) * 0.8;
+
+        let single = FrameQuality::assess(&grainy, &config);
+        assert!(single.issues.contains(&QualityIssue::Noisy), "{}", single);
+
+        // Averaged over 4 frames the noise halves, so the frame goes through
+        config.denoise_frames = 4;
+        let averaged = FrameQuality::assess(&grainy, &config);
+        assert!(!averaged.issues.contains(&QualityIssue::Noisy), "{}", averaged);
+    }
+
+    #[test]
+    fn test_shutter_detector() {
+        let mut config = Config::default().camera;
+        config.shutter_frames = 3;
+        let black = FrameQuality::assess(&RgbImage::new(8, 8).into(), &config);
+        let lit = FrameQuality::assess(&stripes(), &config);
+
+        let mut shutter = ShutterDetector::new(&config);
+        assert!(shutter.check(&black).is_ok());
+        assert!(shutter.check(&black).is_ok());
+        assert!(shutter.check(&lit).is_ok());
+        assert!(shutter.check(&black).is_ok());
+        assert!(shutter.check(&black).is_ok());
+        assert!(matches!(shutter.check(&black), Err(CaptureError::ShutterClosed(3))));
+
+        config.shutter_frames = 0;
+        let mut never = ShutterDetector::new(&config);
+        assert!((0..10).all(|_| never.check(&black).is_ok()));
+    }
+
+    #[test]
+    fn test_face_brightness() {
+        let config = Config::default().camera;
+        let mut frame = stripes().into_rgb8();
+        for y in 20..40 {
+            for x in 20..40 {
+                frame.put_pixel(x, y, Rgb([if (x / 2) % 2 == 0 { 5 } else { 30 }; 3]));
+            }
+        }
+        let frame = frame.into();
+
+        let mut quality = FrameQuality::assess(&frame, &config);
+        quality.assess_face(&frame, &BoundingBox { x: 20.0, y: 20.0, width: 20.0, height: 20.0 }, &config);
+        assert!(quality.issues.contains(&QualityIssue::FaceTooDark));
+        assert!(quality.face_brightness.unwrap() < 20.0);
+        assert!(quality.to_string().contains("face_too_dark"));
+    }
+
+    #[test]
+    fn test_face_geometry() {
+        let config = Config::default().camera;
+        let face = |x: f32, size: f32| {
+            let bbox = BoundingBox { x, y: 4.0, width: size, height: size };
+            DetectedFace { landmarks: FacialLandmarks::estimate(&bbox), bbox, confidence: 0.9, track: None }
+        };
+        let check = |face: &DetectedFace, frame: (u32, u32)| {
+            let mut quality = FrameQuality::assess(&stripes(), &config);
+            quality.assess_geometry(face, frame, &config);
+            quality
+        };
+
+        let good = check(&face(100.0, 200.0), (640, 480));
+        assert!(good.is_acceptable(), "{}", good);
+        assert!(good.pose.unwrap().yaw.abs() < 5.0);
+
+        // Eyes 14px apart in a face covering 0.5% of the frame
+        let small = check(&face(100.0, 40.0), (640, 480));
+        assert_eq!(small.issues, vec![QualityIssue::FaceTooSmall, QualityIssue::EyesTooClose]);
+
+        // Hanging off the right edge
+        let edge = check(&face(560.0, 200.0), (640, 480));
+        assert!(edge.issues.contains(&QualityIssue::FaceCutOff), "{}", edge);
+
+        // Nose over the right eye
+        let mut turned = face(100.0, 200.0);
+        turned.landmarks.nose = turned.landmarks.right_eye;
+        turned.landmarks.nose.1 += 36.0;
+        let turned = check(&turned, (640, 480));
+        assert_eq!(turned.issues, vec![QualityIssue::HeadTurned]);
+        assert!(turned.to_string().contains("head_turned"));
+    }
+
+    #[test]
+    fn test_gray_frames_match_rgb() {
+        let config = Config::default().camera;
+        let rgb = stripes();
+        let gray = DynamicImage::ImageLuma8(rgb.to_luma8());
+        let bbox = BoundingBox { x: 8.0, y: 8.0, width: 48.0, height: 48.0 };
+
+        let mut from_rgb = FrameQuality::assess(&rgb, &config);
+        from_rgb.assess_face(&rgb, &bbox, &config);
+        let mut from_gray = FrameQuality::assess(&gray, &config);
+        from_gray.assess_face(&gray, &bbox, &config);
+        assert_eq!(from_rgb.to_string(), from_gray.to_string());
+        assert_eq!(from_rgb.face_brightness, from_gray.face_brightness);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::config::{DetectionConfig, DetectionRanking, DetectionScores};
+use crate::detect::{self, Decoded, DetectedFace, DetectionError, FaceDetector, FacialLandmarks, Outlet, BGR};
+use image::DynamicImage;
+use ort::session::Session;
+use ort::value::Value;
+
+/// Prior box strides and the two box sizes at each, in input pixels
+const STEPS: [usize; 3] = [8, 16, 32];
+const MIN_SIZES: [[f32; 2]; 3] = [[16.0, 32.0], [64.0, 128.0], [256.0, 512.0]];
+
+/// Box regression variances the model was trained with
+const VARIANCE: [f32; 2] = [0.1, 0.2];
+
+/// Per-channel mean subtracted from BGR input (ImageNet, 0-255)
+const BGR_MEAN: [f32; 3] = [104.0, 117.0, 123.0];
+
+/// A prior box: center and size, relative to the input size
+type Prior = [f32; 4];
+
+/// RetinaFace detector (MobileNet-0.25 or ResNet-50 ONNX exports of
+/// Pytorch_Retinaface)
+///
+/// Outputs are told apart by width: 4 box offsets, 2 class scores and 10
+/// landmark offsets per prior. Whether the class scores still need softmax
+/// comes from `scores`, since both forms have the same shape.
+pub struct RetinaFaceDetector {
+    session: Session,
+    input_name: String,
+    input_size: (u32, u32),
+    priors: Vec<Prior>,
+    /// Output index of the boxes, scores and landmarks
+    outputs: [usize; 3],
+    /// Class scores are logits rather than probabilities
+    logits: bool,
+    confidence_threshold: f32,
+    letterbox: bool,
+    nms_threshold: f32,
+    ranking: DetectionRanking,
+}
+
+impl RetinaFaceDetector {
+    /// Wrap a loaded RetinaFace model, checking its layout
+    pub fn new(session: Session, config: &DetectionConfig) -> Result<Self, DetectionError> {
+        let (inputs, outputs) = detect::outlets(&session);
+        let (input_name, input_size) = detect::image_input(&inputs)?;
+        let input_size = detect::input_size(input_size, config);
+        let priors = Self::priors(input_size);
+        let outputs = Self::find_outputs(&outputs, priors.len())?;
+
+        Ok(Self {
+            session,
+            input_name,
+            input_size,
+            priors,
+            outputs,
+            logits: config.scores == DetectionScores::Logits,
+            confidence_threshold: config.confidence_threshold,
+            letterbox: config.letterbox,
+            nms_threshold: config.nms_threshold,
+            ranking: config.ranking,
+        })
+    }
+
+    /// Output index of the box, score and landmark tensors
+    fn find_outputs(outputs: &[Outlet], priors: usize) -> Result<[usize; 3], DetectionError> {
+        let mut found = [None; 3];
+        for (index, (name, shape)) in outputs.iter().enumerate() {
+            let slot = match shape.last() {
+                Some(4) => 0,
+                Some(2) => 1,
+                Some(10) => 2,
+                _ => continue,
+            };
+            let count = if shape.len() >= 2 { shape[shape.len() - 2] } else { -1 };
+            if count > 0 && count as usize != priors {
+                return Err(DetectionError::UnsupportedModel(format!(
+                    "output {:?} has {} priors, expected {}",
+                    name, count, priors
+                )));
+            }
+            found[slot].get_or_insert(index);
+        }
+
+        match found {
+            [Some(boxes), Some(scores), Some(landmarks)] => Ok([boxes, scores, landmarks]),
+            _ => Err(DetectionError::UnsupportedModel(
+                "expected box [priors, 4], score [priors, 2] and landmark [priors, 10] outputs; not a RetinaFace model"
+                    .to_string(),
+            )),
+        }
+    }
+
+    /// Prior boxes for an input size, in the order the model predicts them
+    fn priors((width, height): (u32, u32)) -> Vec<Prior> {
+        let (w, h) = (width as f32, height as f32);
+        let mut priors = Vec::new();
+        for (&step, sizes) in STEPS.iter().zip(&MIN_SIZES) {
+            for row in 0..detect::feature_size(height, step) {
+                for col in 0..detect::feature_size(width, step) {
+                    let cx = (col as f32 + 0.5) * step as f32 / w;
+                    let cy = (row as f32 + 0.5) * step as f32 / h;
+                    for &size in sizes {
+                        priors.push([cx, cy, size / w, size / h]);
+                    }
+                }
+            }
+        }
+        priors
+    }
+
+    /// Face probability from the (background, face) class outputs
+    fn score(pair: &[f32], logits: bool) -> f32 {
+        if logits {
+            // Softmax over two classes
+            1.0 / (1.0 + (pair[0] - pair[1]).exp())
+        } else {
+            pair[1]
+        }
+    }
+
+    /// Decode one prior; `size` is the input size the priors are relative to
+    fn decode(prior: &Prior, loc: &[f32], landms: &[f32], size: (f32, f32)) -> Decoded {
+        let (w, h) = size;
+        let [px, py, pw, ph] = *prior;
+        let cx = px + loc[0] * VARIANCE[0] * pw;
+        let cy = py + loc[1] * VARIANCE[0] * ph;
+        let bw = pw * (loc[2] * VARIANCE[1]).exp();
+        let bh = ph * (loc[3] * VARIANCE[1]).exp();
+
+        let point = |i: usize| {
+            (
+                (px + landms[i * 2] * VARIANCE[0] * pw) * w,
+                (py + landms[i * 2 + 1] * VARIANCE[0] * ph) * h,
+            )
+        };
+        (
+            ((cx - bw / 2.0) * w, (cy - bh / 2.0) * h, bw * w, bh * h),
+            [point(0), point(1), point(2), point(3), point(4)],
+        )
+    }
+}
+
+impl FaceDetector for RetinaFaceDetector {
+    fn detect(&mut self, image: &DynamicImage) -> Result<Vec<DetectedFace>, DetectionError> {
+        // BGR with the training mean subtracted, no scaling
+        let (input_tensor, mapping) = detect::input_tensor(image, self.input_size, self.letterbox, BGR, |value, c| {
+            value as f32 - BGR_MEAN[c]
+        });
+
+        let input_value = Value::from_array(input_tensor)
+            .map_err(|e| DetectionError::Inference(format!("Failed to create input tensor: {}", e)))?;
+
+        let outputs = self
+            .session
+            .run(ort::inputs![self.input_name.as_str() => input_value])
+            .map_err(|e| DetectionError::Inference(e.to_string()))?;
+
+        let loc = detect::tensor(&outputs[self.outputs[0]], "bboxes")?;
+        let conf = detect::tensor(&outputs[self.outputs[1]], "scores")?;
+        let landms = detect::tensor(&outputs[self.outputs[2]], "landmarks")?;
+
+        let count = self.priors.len();
+        if loc.len() < count * 4 || conf.len() < count * 2 || landms.len() < count * 10 {
+            return Err(DetectionError::Inference(format!(
+                "Outputs are smaller than the {} prior boxes",
+                count
+            )));
+        }
+
+        let size = (self.input_size.0 as f32, self.input_size.1 as f32);
+        let mut detections = Vec::new();
+        for (idx, prior) in self.priors.iter().enumerate() {
+            let score = Self::score(&conf[idx * 2..idx * 2 + 2], self.logits);
+            if score < self.confidence_threshold {
+                continue;
+            }
+
+            let (decoded, points) = Self::decode(prior, &loc[idx * 4..idx * 4 + 4], &landms[idx * 10..idx * 10 + 10], size);
+            detections.push(DetectedFace {
+                bbox: mapping.unmap_box(decoded),
+                landmarks: FacialLandmarks::from_points(points).map(|point| mapping.unmap(point)),
+                confidence: score,
+                track: None,
+            });
+        }
+
+        detect::finish(detections, self.nms_threshold, self.ranking)
+    }
+
+    fn set_confidence_threshold(&mut self, threshold: f32) {
+        self.confidence_threshold = threshold;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priors() {
+        // 2 priors per location over 80x80, 40x40 and 20x20 maps
+        let priors = RetinaFaceDetector::priors((640, 640));
+        assert_eq!(priors.len(), 16800);
+        assert_eq!(priors[0], [4.0 / 640.0, 4.0 / 640.0, 16.0 / 640.0, 16.0 / 640.0]);
+        assert_eq!(priors[1][2], 32.0 / 640.0);
+        assert_eq!(priors[12800][3], 64.0 / 640.0);
+    }
+
+    #[test]
+    fn test_find_outputs() {
+        let outputs = vec![
+            ("output0".to_string(), vec![1, 16800, 4]),
+            ("586".to_string(), vec![1, 16800, 10]),
+            ("585".to_string(), vec![1, 16800, 2]),
+        ];
+        assert_eq!(RetinaFaceDetector::find_outputs(&outputs, 16800).unwrap(), [0, 2, 1]);
+        // Priors for another input size
+        assert!(RetinaFaceDetector::find_outputs(&outputs, 4200).is_err());
+        assert!(RetinaFaceDetector::find_outputs(&outputs[..2], 16800).is_err());
+    }
+
+    #[test]
+    fn test_decode_and_score() {
+        // Zero offsets give the prior itself
+        let prior = [0.5, 0.5, 0.25, 0.5];
+        let (bbox, points) = RetinaFaceDetector::decode(&prior, &[0.0; 4], &[0.0; 10], (100.0, 100.0));
+        assert_eq!(bbox, (37.5, 25.0, 25.0, 50.0));
+        assert_eq!(points[3], (50.0, 50.0));
+    }
+
+    #[test]
+    fn test_score_layouts() {
+        // Softmax applied in the export
+        assert_eq!(RetinaFaceDetector::score(&[0.1, 0.9], false), 0.9);
+        assert_eq!(RetinaFaceDetector::score(&[1.0, 0.0], false), 0.0);
+
+        // Raw logits, including a pair that happens to sum to one
+        assert!((RetinaFaceDetector::score(&[0.0, 2.0], true) - 0.881).abs() < 1e-3);
+        assert!((RetinaFaceDetector::score(&[0.1, 0.9], true) - 0.690).abs() < 1e-3);
+        assert!(RetinaFaceDetector::score(&[3.0, -3.0], true) < 0.01);
+    }
+}
@@ -68,8 +68,8 @@ mod tests {
     #[test]
     #[ignore] // Requires model file
     fn test_session_creation() {
-        let runtime = OnnxRuntime::new().unwrap();
-        let config = RuntimeConfig {};
+        let _runtime = OnnxRuntime::new().unwrap();
+        let _config = RuntimeConfig {};
 
         // This would need an actual model file to test
         // let session = runtime.create_session("test_model.onnx", &config);
@@ -0,0 +1,400 @@
+use crate::config::{DetectionConfig, DetectionRanking};
+use crate::detect::{self, BoundingBox, DetectedFace, DetectionError, FaceDetector, FacialLandmarks, Outlet, RGB};
+use image::DynamicImage;
+use ort::session::Session;
+use ort::value::Value;
+
+/// Feature pyramid strides of the 3-level (500m, 2.5g, 10g, 34g) and
+/// 5-level SCRFD exports
+const STRIDES_3: [usize; 3] = [8, 16, 32];
+const STRIDES_5: [usize; 5] = [8, 16, 32, 64, 128];
+
+/// SCRFD training normalization: (pixel - 127.5) / 128
+const INPUT_MEAN: f32 = 127.5;
+const INPUT_STD: f32 = 128.0;
+
+/// Values per anchor in each SCRFD head
+const SCORE_WIDTH: i64 = 1;
+const BBOX_WIDTH: i64 = 4;
+const KPS_WIDTH: i64 = 10;
+
+/// Output tensors of one feature pyramid level
+#[derive(Debug, Clone, PartialEq)]
+struct Level {
+    stride: usize,
+    score: usize,
+    bbox: usize,
+    kps: Option<usize>,
+}
+
+/// How an SCRFD export lays out its tensors, read from the model at load time
+///
+/// insightface exports differ in input name, whether sizes are fixed, how
+/// many pyramid levels and anchors they use, whether a keypoint head is
+/// present and in which order the outputs come. Heads are told apart by
+/// their width (1 score, 4 box, 10 keypoint values per anchor) and levels
+/// by their anchor count, so any order works.
+#[derive(Debug, Clone, PartialEq)]
+struct ModelLayout {
+    input_name: String,
+    /// Fixed `(width, height)`, `None` when the model takes any size
+    input_size: Option<(u32, u32)>,
+    levels: Vec<Level>,
+    anchors: usize,
+}
+
+impl ModelLayout {
+    fn from_outlets(inputs: &[Outlet], outputs: &[Outlet]) -> Result<Self, DetectionError> {
+        let unsupported = |reason: String| DetectionError::UnsupportedModel(reason);
+        let (input_name, input_size) = detect::image_input(inputs)?;
+
+        // Group the heads by width, keeping model order within a group
+        let mut heads: [Vec<(usize, i64)>; 3] = Default::default();
+        for (index, (name, shape)) in outputs.iter().enumerate() {
+            let group = match shape.last() {
+                Some(&SCORE_WIDTH) => 0,
+                Some(&BBOX_WIDTH) => 1,
+                Some(&KPS_WIDTH) => 2,
+                _ => {
+                    return Err(unsupported(format!(
+                        "output {:?} has shape {:?}, expected [anchors, 1|4|10]",
+                        name, shape
+                    )))
+                }
+            };
+            let count = if shape.len() >= 2 { shape[shape.len() - 2] } else { -1 };
+            heads[group].push((index, count));
+        }
+        let [scores, bboxes, kps] = heads;
+
+        let strides: &[usize] = match scores.len() {
+            3 => &STRIDES_3,
+            5 => &STRIDES_5,
+            n => return Err(unsupported(format!("{} score outputs, expected 3 or 5", n))),
+        };
+        if bboxes.len() != strides.len() || !(kps.is_empty() || kps.len() == strides.len()) {
+            return Err(unsupported(format!(
+                "{} score, {} box and {} keypoint outputs do not form {} pyramid levels",
+                scores.len(),
+                bboxes.len(),
+                kps.len(),
+                strides.len()
+            )));
+        }
+
+        // Finest level (most anchors) first, when the counts are known
+        let by_level = |mut group: Vec<(usize, i64)>| {
+            if group.iter().all(|&(_, count)| count > 0) {
+                group.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+            }
+            group
+        };
+        let (scores, bboxes, kps) = (by_level(scores), by_level(bboxes), by_level(kps));
+
+        // Two anchors per location on 3-level exports, one on 5-level ones,
+        // unless a fixed input size lets the counts say otherwise
+        let mut anchors = if strides.len() == 3 { 2 } else { 1 };
+        if let Some((width, height)) = input_size {
+            let locations = detect::feature_size(width, strides[0]) * detect::feature_size(height, strides[0]);
+            if scores[0].1 > 0 && locations > 0 {
+                anchors = scores[0].1 as usize / locations;
+            }
+            for (level, &stride) in strides.iter().enumerate() {
+                let expected = detect::feature_size(width, stride) * detect::feature_size(height, stride) * anchors;
+                for group in [&scores, &bboxes, &kps] {
+                    let Some(&(index, count)) = group.get(level) else {
+                        continue;
+                    };
+                    if count > 0 && count as usize != expected {
+                        return Err(unsupported(format!(
+                            "output {:?} has {} anchors, expected {} for stride {} at {}x{}",
+                            outputs[index].0, count, expected, stride, width, height
+                        )));
+                    }
+                }
+            }
+        }
+        if anchors == 0 {
+            return Err(unsupported("no anchors per location".to_string()));
+        }
+
+        let levels = strides
+            .iter()
+            .enumerate()
+            .map(|(level, &stride)| Level {
+                stride,
+                score: scores[level].0,
+                bbox: bboxes[level].0,
+                kps: kps.get(level).map(|&(index, _)| index),
+            })
+            .collect();
+
+        Ok(Self {
+            input_name,
+            input_size,
+            levels,
+            anchors,
+        })
+    }
+
+    fn has_keypoints(&self) -> bool {
+        self.levels.iter().all(|level| level.kps.is_some())
+    }
+}
+
+/// SCRFD detector from insightface
+pub struct ScrfdDetector {
+    session: Session,
+    layout: ModelLayout,
+    input_size: (u32, u32),
+    confidence_threshold: f32,
+    letterbox: bool,
+    nms_threshold: f32,
+    ranking: DetectionRanking,
+}
+
+impl ScrfdDetector {
+    /// Wrap a loaded SCRFD model, checking its layout
+    pub fn new(session: Session, config: &DetectionConfig) -> Result<Self, DetectionError> {
+        let (inputs, outputs) = detect::outlets(&session);
+        let layout = ModelLayout::from_outlets(&inputs, &outputs)?;
+        log::debug!(
+            "SCRFD layout: input {:?} at {}, strides {:?}, {} anchors per location, keypoints {}",
+            layout.input_name,
+            layout.input_size.map_or("any size".to_string(), |(w, h)| format!("{}x{}", w, h)),
+            layout.levels.iter().map(|level| level.stride).collect::<Vec<_>>(),
+            layout.anchors,
+            if layout.has_keypoints() { "yes" } else { "no" }
+        );
+        if !layout.has_keypoints() {
+            log::warn!("Detection model has no keypoint head; landmarks are estimated from the face box");
+        }
+
+        Ok(Self {
+            session,
+            input_size: detect::input_size(layout.input_size, config),
+            layout,
+            confidence_threshold: config.confidence_threshold,
+            letterbox: config.letterbox,
+            nms_threshold: config.nms_threshold,
+            ranking: config.ranking,
+        })
+    }
+
+    /// Generate anchor centers for a given stride
+    fn generate_anchors(stride: usize, feat_width: usize, feat_height: usize) -> Vec<(f32, f32)> {
+        let mut anchors = Vec::with_capacity(feat_width * feat_height);
+        for i in 0..feat_height {
+            for j in 0..feat_width {
+                let cx = (j as f32 + 0.5) * stride as f32;
+                let cy = (i as f32 + 0.5) * stride as f32;
+                anchors.push((cx, cy));
+            }
+        }
+        anchors
+    }
+
+    /// Decode SCRFD bounding box predictions from anchor-relative format
+    ///
+    /// Predictions are distances from the anchor center to each edge, in
+    /// units of the level's stride.
+    fn decode_bbox(anchor: (f32, f32), pred: &[f32], stride: f32) -> (f32, f32, f32, f32) {
+        let (cx, cy) = anchor;
+
+        let x1 = cx - pred[0] * stride;
+        let y1 = cy - pred[1] * stride;
+        let x2 = cx + pred[2] * stride;
+        let y2 = cy + pred[3] * stride;
+
+        (x1, y1, x2 - x1, y2 - y1)
+    }
+
+    /// Decode SCRFD landmark predictions from anchor-relative format
+    fn decode_landmarks(anchor: (f32, f32), pred: &[f32], stride: f32) -> FacialLandmarks {
+        let (cx, cy) = anchor;
+        let point = |i: usize| (cx + pred[i * 2] * stride, cy + pred[i * 2 + 1] * stride);
+        FacialLandmarks::from_points([point(0), point(1), point(2), point(3), point(4)])
+    }
+}
+
+impl FaceDetector for ScrfdDetector {
+    fn detect(&mut self, image: &DynamicImage) -> Result<Vec<DetectedFace>, DetectionError> {
+        // RGB, normalized as in training
+        let (input_width, input_height) = self.input_size;
+        let (input_tensor, mapping) = detect::input_tensor(
+            image,
+            (input_width, input_height),
+            self.letterbox,
+            RGB,
+            |value, _| (value as f32 - INPUT_MEAN) / INPUT_STD,
+        );
+
+        // Convert to Value
+        let input_value = Value::from_array(input_tensor)
+            .map_err(|e| DetectionError::Inference(format!("Failed to create input tensor: {}", e)))?;
+
+        let outputs = self
+            .session
+            .run(ort::inputs![self.layout.input_name.as_str() => input_value])
+            .map_err(|e| DetectionError::Inference(e.to_string()))?;
+
+        let mut detections = Vec::new();
+
+        for level in &self.layout.levels {
+            let stride = level.stride;
+            let anchors = Self::generate_anchors(
+                stride,
+                detect::feature_size(input_width, stride),
+                detect::feature_size(input_height, stride),
+            );
+
+            let score_data = detect::tensor(&outputs[level.score], "scores")?;
+            let bbox_data = detect::tensor(&outputs[level.bbox], "bboxes")?;
+            let kps_data = level.kps.map(|index| detect::tensor(&outputs[index], "landmarks")).transpose()?;
+
+            let count = anchors.len() * self.layout.anchors;
+            if score_data.len() < count || bbox_data.len() < count * 4 || kps_data.is_some_and(|k| k.len() < count * 10) {
+                return Err(DetectionError::Inference(format!(
+                    "Stride {} outputs are smaller than its {} anchors",
+                    stride, count
+                )));
+            }
+            log::trace!("Stride {}: {} anchors", stride, count);
+
+            // Process each anchor location
+            for (anchor_idx, &anchor) in anchors.iter().enumerate() {
+                for anchor_num in 0..self.layout.anchors {
+                    let idx = anchor_idx * self.layout.anchors + anchor_num;
+
+                    // Apply sigmoid to convert logits to probabilities [0, 1]
+                    let score = 1.0 / (1.0 + (-score_data[idx]).exp());
+                    if score < self.confidence_threshold {
+                        continue;
+                    }
+
+                    // Decode bounding box (4 values: dx1, dy1, dx2, dy2)
+                    let bbox_pred = &bbox_data[idx * 4..idx * 4 + 4];
+                    let decoded = Self::decode_bbox(anchor, bbox_pred, stride as f32);
+
+                    log::trace!(
+                        "Detection: stride={}, anchor=({:.1},{:.1}), bbox_pred={:?}, decoded={:?}, score={:.3}",
+                        stride, anchor.0, anchor.1, bbox_pred, decoded, score
+                    );
+
+                    // Undo padding and scaling to get back to frame coordinates
+                    let bbox: BoundingBox = mapping.unmap_box(decoded);
+
+                    // Decode landmarks (10 values: 5 points x 2 coords)
+                    let landmarks = match kps_data {
+                        Some(kps_data) => Self::decode_landmarks(anchor, &kps_data[idx * 10..idx * 10 + 10], stride as f32)
+                            .map(|point| mapping.unmap(point)),
+                        None => FacialLandmarks::estimate(&bbox),
+                    };
+
+                    detections.push(DetectedFace {
+                        bbox,
+                        landmarks,
+                        confidence: score,
+                        track: None,
+                    });
+                }
+            }
+        }
+
+        detect::finish(detections, self.nms_threshold, self.ranking)
+    }
+
+    fn set_confidence_threshold(&mut self, threshold: f32) {
+        self.confidence_threshold = threshold;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outlets(shapes: &[&[i64]]) -> Vec<Outlet> {
+        shapes.iter().enumerate().map(|(i, shape)| (i.to_string(), shape.to_vec())).collect()
+    }
+
+    fn input(shape: &[i64]) -> Vec<Outlet> {
+        vec![("input.1".to_string(), shape.to_vec())]
+    }
+
+    #[test]
+    fn test_layout_scrfd_500m() {
+        // Scores, boxes, keypoints for strides 8, 16, 32 at 640x640
+        let outputs = outlets(&[
+            &[12800, 1], &[3200, 1], &[800, 1],
+            &[12800, 4], &[3200, 4], &[800, 4],
+            &[12800, 10], &[3200, 10], &[800, 10],
+        ]);
+        let layout = ModelLayout::from_outlets(&input(&[1, 3, 640, 640]), &outputs).unwrap();
+        assert_eq!(layout.input_size, Some((640, 640)));
+        assert_eq!(layout.anchors, 2);
+        assert!(layout.has_keypoints());
+        assert_eq!(layout.levels[1], Level { stride: 16, score: 1, bbox: 4, kps: Some(7) });
+    }
+
+    #[test]
+    fn test_layout_grouped_by_level_without_keypoints() {
+        // Batched 5-level export, heads interleaved per level, one anchor
+        let outputs = outlets(&[
+            &[1, 100, 1], &[1, 100, 4],
+            &[1, 1600, 1], &[1, 1600, 4],
+            &[1, 400, 1], &[1, 400, 4],
+            &[1, 25, 1], &[1, 25, 4],
+            &[1, 7, 1], &[1, 7, 4],
+        ]);
+        let err = ModelLayout::from_outlets(&input(&[1, 3, 320, 320]), &outputs);
+        assert!(matches!(err, Err(DetectionError::UnsupportedModel(_))));
+
+        let outputs = outlets(&[
+            &[1, 100, 1], &[1, 100, 4],
+            &[1, 1600, 1], &[1, 1600, 4],
+            &[1, 400, 1], &[1, 400, 4],
+            &[1, 25, 1], &[1, 25, 4],
+            &[1, 9, 1], &[1, 9, 4],
+        ]);
+        let layout = ModelLayout::from_outlets(&input(&[1, 3, 320, 320]), &outputs).unwrap();
+        assert_eq!(layout.anchors, 1);
+        assert!(!layout.has_keypoints());
+        assert_eq!(layout.levels[0], Level { stride: 8, score: 2, bbox: 3, kps: None });
+        assert_eq!(layout.levels[4], Level { stride: 128, score: 8, bbox: 9, kps: None });
+    }
+
+    #[test]
+    fn test_layout_dynamic_input() {
+        let outputs = outlets(&[&[-1, 1], &[-1, 1], &[-1, 1], &[-1, 4], &[-1, 4], &[-1, 4]]);
+        let layout = ModelLayout::from_outlets(&input(&[1, 3, -1, -1]), &outputs).unwrap();
+        assert_eq!(layout.input_size, None);
+        assert_eq!(layout.anchors, 2);
+        assert_eq!(layout.levels[2].score, 2);
+    }
+
+    #[test]
+    fn test_layout_rejects_other_models() {
+        // A classifier, not SCRFD
+        let err = ModelLayout::from_outlets(&input(&[1, 3, 224, 224]), &outlets(&[&[1, 1000]]));
+        assert!(matches!(err, Err(DetectionError::UnsupportedModel(_))));
+        // Keypoints for only some levels
+        let outputs = outlets(&[&[-1, 1], &[-1, 1], &[-1, 1], &[-1, 4], &[-1, 4], &[-1, 4], &[-1, 10]]);
+        assert!(ModelLayout::from_outlets(&input(&[1, 3, -1, -1]), &outputs).is_err());
+        // Single-channel input
+        assert!(ModelLayout::from_outlets(&input(&[1, 1, 640, 640]), &outlets(&[])).is_err());
+    }
+
+    #[test]
+    fn test_decode_bbox_uses_stride() {
+        let (x, y, w, h) = ScrfdDetector::decode_bbox((100.0, 100.0), &[1.0, 2.0, 3.0, 4.0], 16.0);
+        assert_eq!((x, y, w, h), (84.0, 68.0, 64.0, 96.0));
+    }
+
+    #[test]
+    #[ignore] // Requires model file
+    fn test_face_detection() {
+        // This test requires the SCRFD model
+        // let runtime = OnnxRuntime::new().unwrap();
+        // let detector = detect::load(&Config::default().detection, &runtime, &RuntimeConfig {}).unwrap();
+    }
+}
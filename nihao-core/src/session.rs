@@ -0,0 +1,431 @@
+use crate::capture::{CaptureError, FrameInfo, FrameSource};
+use crate::config::{CameraConfig, Config};
+use crate::embed::Embedding;
+use crate::quality::FrameQuality;
+use chrono::{DateTime, Utc};
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{self, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Bumped when the archive layout changes
+const SESSION_VERSION: u32 = 1;
+
+const MANIFEST: &str = "session.json";
+const CONFIG: &str = "config.toml";
+
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid session archive: {0}")]
+    Format(String),
+}
+
+/// One captured frame as listed in `session.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameRecord {
+    pub file: String,
+    /// IR frame captured alongside, in hybrid mode
+    pub ir_file: Option<String>,
+    pub sequence: Option<u32>,
+    /// V4L2 capture timestamp in microseconds
+    pub timestamp_us: Option<u64>,
+    pub accepted: bool,
+    /// Quality report, including the reason codes of a rejected frame
+    pub verdict: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    username: String,
+    recorded_at: DateTime<Utc>,
+    /// "match", "no match" or the error that ended the attempt
+    outcome: String,
+    /// The user's enrolled embeddings, so replay matches against the same faces
+    enrolled: Vec<Vec<f32>>,
+    frames: Vec<FrameRecord>,
+}
+
+/// Collects the frames of one `authenticate` call and writes them, with the
+/// config in use, to a tar archive
+///
+/// Frames are stored as PNG exactly as capture returned them (after exposure
+/// fusion, before preprocessing, gray or RGB), so replay runs every later
+/// step again.
+pub struct SessionRecorder {
+    path: PathBuf,
+    config: Config,
+    username: String,
+    enrolled: Vec<Vec<f32>>,
+    frames: Vec<(DynamicImage, Option<DynamicImage>, FrameRecord)>,
+}
+
+impl SessionRecorder {
+    pub fn new<P: AsRef<Path>>(path: P, config: &Config) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            config: config.clone(),
+            username: String::new(),
+            enrolled: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Begin a new attempt, dropping frames from an earlier one
+    pub fn start(&mut self, username: &str, enrolled: &[Embedding]) {
+        self.username = username.to_string();
+        self.enrolled = enrolled.iter().map(|e| e.to_vec()).collect();
+        self.frames.clear();
+    }
+
+    /// Add a frame and return its index for `set_verdict`
+    pub fn record(
+        &mut self,
+        frame: &DynamicImage,
+        ir_frame: Option<&DynamicImage>,
+        info: Option<FrameInfo>,
+        quality: &FrameQuality,
+    ) -> usize {
+        let index = self.frames.len();
+        let record = FrameRecord {
+            file: format!("frames/{:04}.png", index),
+            ir_file: ir_frame.map(|_| format!("frames/{:04}_ir.png", index)),
+            sequence: info.map(|i| i.sequence),
+            timestamp_us: info.map(|i| i.timestamp.as_micros() as u64),
+            accepted: quality.is_acceptable(),
+            verdict: quality.to_string(),
+        };
+        self.frames.push((frame.clone(), ir_frame.cloned(), record));
+        index
+    }
+
+    /// Replace a frame's verdict once face-region checks have run
+    pub fn set_verdict(&mut self, index: usize, quality: &FrameQuality) {
+        if let Some((_, _, record)) = self.frames.get_mut(index) {
+            record.accepted = quality.is_acceptable();
+            record.verdict = quality.to_string();
+        }
+    }
+
+    /// Write the archive
+    pub fn finish(&self, outcome: &str) -> Result<(), SessionError> {
+        let manifest = Manifest {
+            version: SESSION_VERSION,
+            username: self.username.clone(),
+            recorded_at: Utc::now(),
+            outcome: outcome.to_string(),
+            enrolled: self.enrolled.clone(),
+            frames: self.frames.iter().map(|(_, _, record)| record.clone()).collect(),
+        };
+
+        let mut tar = TarWriter::new(create_private(&self.path)?);
+        tar.append(MANIFEST, &serde_json::to_vec_pretty(&manifest).map_err(io::Error::other)?)?;
+        tar.append(CONFIG, toml::to_string_pretty(&self.config).map_err(io::Error::other)?.as_bytes())?;
+        for (frame, ir_frame, record) in &self.frames {
+            tar.append(&record.file, &encode_png(frame)?)?;
+            if let (Some(ir_frame), Some(ir_file)) = (ir_frame, &record.ir_file) {
+                tar.append(ir_file, &encode_png(ir_frame)?)?;
+            }
+        }
+        tar.finish()?;
+
+        log::info!("Session with {} frames recorded to {}", self.frames.len(), self.path.display());
+        Ok(())
+    }
+}
+
+/// A recorded session loaded for replay
+pub struct Session {
+    pub config: Config,
+    pub username: String,
+    pub outcome: String,
+    pub enrolled: Vec<Embedding>,
+    pub frames: Vec<FrameRecord>,
+    images: HashMap<String, DynamicImage>,
+}
+
+impl Session {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SessionError> {
+        let data = fs::read(path)?;
+        let mut entries: HashMap<String, Vec<u8>> = read_tar(&data)?.into_iter().collect();
+
+        let manifest: Manifest = entries
+            .remove(MANIFEST)
+            .ok_or_else(|| SessionError::Format(format!("no {}", MANIFEST)))
+            .and_then(|json| serde_json::from_slice(&json).map_err(|e| SessionError::Format(e.to_string())))?;
+        if manifest.version != SESSION_VERSION {
+            return Err(SessionError::Format(format!("unsupported version {}", manifest.version)));
+        }
+
+        let config = entries
+            .remove(CONFIG)
+            .ok_or_else(|| SessionError::Format(format!("no {}", CONFIG)))
+            .and_then(|toml| {
+                let toml = String::from_utf8(toml).map_err(|e| SessionError::Format(e.to_string()))?;
+                toml::from_str(&toml).map_err(|e| SessionError::Format(e.to_string()))
+            })?;
+
+        let mut images = HashMap::new();
+        for name in manifest.frames.iter().flat_map(|f| std::iter::once(&f.file).chain(&f.ir_file)) {
+            let png = entries
+                .get(name)
+                .ok_or_else(|| SessionError::Format(format!("missing {}", name)))?;
+            let image = image::load_from_memory_with_format(png, ImageFormat::Png)
+                .map_err(|e| SessionError::Format(format!("{}: {}", name, e)))?;
+            images.insert(name.clone(), crate::capture::normalize(image));
+        }
+
+        Ok(Self {
+            config,
+            username: manifest.username,
+            outcome: manifest.outcome,
+            enrolled: manifest.enrolled.into_iter().map(Embedding::from).collect(),
+            frames: manifest.frames,
+            images,
+        })
+    }
+
+    /// Frame sources replaying the main and, in hybrid mode, the IR stream
+    pub fn sources(&self) -> (ReplaySource, Option<ReplaySource>) {
+        let info = |record: &FrameRecord| {
+            Some(FrameInfo {
+                sequence: record.sequence?,
+                timestamp: Duration::from_micros(record.timestamp_us?),
+            })
+        };
+
+        let main = self
+            .frames
+            .iter()
+            .map(|record| (self.images[&record.file].clone(), info(record)))
+            .collect();
+        let ir = self
+            .frames
+            .iter()
+            .map(|record| Some((self.images[record.ir_file.as_ref()?].clone(), info(record))))
+            .collect::<Option<VecDeque<_>>>()
+            .filter(|frames| !frames.is_empty());
+
+        let ir_config = self.config.camera.ir_camera().unwrap_or_else(|| self.config.camera.clone());
+        (
+            ReplaySource::new(main, &self.config.camera),
+            ir.map(|frames| ReplaySource::new(frames, &ir_config)),
+        )
+    }
+}
+
+/// Frame source yielding recorded frames with their original timestamps
+pub struct ReplaySource {
+    frames: VecDeque<(DynamicImage, Option<FrameInfo>)>,
+    last_info: Option<FrameInfo>,
+    config: CameraConfig,
+}
+
+impl ReplaySource {
+    fn new(frames: VecDeque<(DynamicImage, Option<FrameInfo>)>, config: &CameraConfig) -> Self {
+        Self {
+            frames,
+            last_info: None,
+            config: config.clone(),
+        }
+    }
+}
+
+impl FrameSource for ReplaySource {
+    fn next_frame(&mut self) -> Result<DynamicImage, CaptureError> {
+        let (frame, info) = self.frames.pop_front().ok_or(CaptureError::EndOfStream)?;
+        self.last_info = info;
+        Ok(frame)
+    }
+
+    fn config(&self) -> &CameraConfig {
+        &self.config
+    }
+
+    fn last_frame_info(&self) -> Option<FrameInfo> {
+        self.last_info
+    }
+}
+
+fn encode_png(image: &DynamicImage) -> io::Result<Vec<u8>> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(io::Error::other)?;
+    Ok(png)
+}
+
+/// Minimal ustar writer: regular files only, names under 100 bytes
+struct TarWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TarWriter<W> {
+    fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    fn append(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        if name.len() >= 100 {
+            return Err(io::Error::other(format!("tar entry name too long: {}", name)));
+        }
+
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        write_octal(&mut header[100..108], 0o600);
+        write_octal(&mut header[108..116], 0);
+        write_octal(&mut header[116..124], 0);
+        write_octal(&mut header[124..136], data.len() as u64);
+        write_octal(&mut header[136..148], Utc::now().timestamp().max(0) as u64);
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+
+        // Checksum is computed with its own field set to spaces
+        header[148..156].fill(b' ');
+        let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+
+        self.out.write_all(&header)?;
+        self.out.write_all(data)?;
+        self.out.write_all(&[0u8; 512][..padding(data.len())])
+    }
+
+    /// Two zero blocks end the archive
+    fn finish(mut self) -> io::Result<()> {
+        self.out.write_all(&[0u8; 1024])?;
+        self.out.flush()
+    }
+}
+
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}\0", value, width = field.len() - 1);
+    field.copy_from_slice(digits.as_bytes());
+}
+
+fn padding(len: usize) -> usize {
+    (512 - len % 512) % 512
+}
+
+/// Read the regular files of a tar archive as (name, contents)
+fn read_tar(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, SessionError> {
+    let field = |bytes: &[u8]| {
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+    };
+
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while offset + 512 <= data.len() {
+        let header = &data[offset..offset + 512];
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+
+        let size = u64::from_str_radix(&field(&header[124..136]), 8)
+            .map_err(|_| SessionError::Format(format!("bad entry size at offset {}", offset)))? as usize;
+        let name = match field(&header[345..500]) {
+            prefix if !prefix.is_empty() => format!("{}/{}", prefix, field(&header[..100])),
+            _ => field(&header[..100]),
+        };
+        let start = offset + 512;
+        let end = start + size;
+        if end > data.len() {
+            return Err(SessionError::Format(format!("{} is truncated", name)));
+        }
+
+        if matches!(header[156], b'0' | 0) {
+            entries.push((name, data[start..end].to_vec()));
+        }
+        offset = end + padding(size);
+    }
+    Ok(entries)
+}
+
+/// Create (or truncate) a file only the owner can read: sessions hold the
+/// enrolled embeddings and raw face frames, like the face store
+fn create_private(path: &Path) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        let file = options.open(path)?;
+        // `mode` only applies to new files
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        Ok(file)
+    }
+    #[cfg(not(unix))]
+    options.open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma, Rgb, RgbImage};
+
+    #[test]
+    fn test_tar_round_trip() {
+        let mut archive = Vec::new();
+        let mut tar = TarWriter::new(&mut archive);
+        tar.append("a.txt", b"hello").unwrap();
+        tar.append("frames/0000.png", &[7u8; 600]).unwrap();
+        tar.finish().unwrap();
+        assert_eq!(archive.len() % 512, 0);
+
+        let entries = read_tar(&archive).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], ("a.txt".to_string(), b"hello".to_vec()));
+        assert_eq!(entries[1].0, "frames/0000.png");
+        assert_eq!(entries[1].1.len(), 600);
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let path = std::env::temp_dir().join(format!("nihao_session_{}.tar", std::process::id()));
+        let config = Config::default();
+
+        // Gray and color frames both come back as they went in
+        let lit = DynamicImage::from(RgbImage::from_pixel(8, 8, Rgb([120; 3])));
+        let dark = DynamicImage::from(GrayImage::from_pixel(8, 8, Luma([3])));
+        let mut recorder = SessionRecorder::new(&path, &config);
+        recorder.start("alice", &[Embedding::from(vec![0.6, 0.8])]);
+        for (i, frame) in [&dark, &lit].into_iter().enumerate() {
+            let info = FrameInfo {
+                sequence: i as u32 + 10,
+                timestamp: Duration::from_millis(1000 + 33 * i as u64),
+            };
+            recorder.record(frame, None, Some(info), &FrameQuality::assess(frame, &config.camera));
+        }
+        recorder.finish("no match").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let session = Session::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(session.username, "alice");
+        assert_eq!(session.outcome, "no match");
+        assert_eq!(session.enrolled[0].to_vec(), vec![0.6, 0.8]);
+        assert!(!session.frames[0].accepted);
+        assert!(session.frames[0].verdict.contains("too_dark"));
+        assert!(session.frames[1].accepted);
+
+        let (mut main, ir) = session.sources();
+        assert!(ir.is_none());
+        assert_eq!(main.next_frame().unwrap(), dark);
+        assert_eq!(main.next_frame().unwrap(), lit);
+        assert_eq!(main.last_frame_info().unwrap().sequence, 11);
+        assert_eq!(main.last_frame_info().unwrap().timestamp, Duration::from_millis(1033));
+        assert!(matches!(main.next_frame(), Err(CaptureError::EndOfStream)));
+    }
+}
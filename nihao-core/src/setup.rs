@@ -0,0 +1,269 @@
+use crate::capture::CaptureError;
+use crate::config::{CameraConfig, Config};
+use crate::detect::FaceDetector;
+use crate::device::DeviceInfo;
+use crate::preprocess::{self, Stage};
+use std::path::{Path, PathBuf};
+
+/// Where `install.sh` puts the models, then the source tree
+const MODEL_DIRS: [&str; 2] = ["/usr/share/nihao/models", "models"];
+
+/// Smallest face height, in detector input pixels, SCRFD finds reliably
+const MIN_DETECTION_FACE: f32 = 80.0;
+
+/// Headroom added to the darkest accepted frame when suggesting `dark_threshold`
+const DARK_MARGIN: f32 = 10.0;
+
+/// Outcome of a capture-and-detect trial on one device
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub path: PathBuf,
+    pub card: String,
+    pub looks_ir: bool,
+    /// Frames captured
+    pub frames: usize,
+    /// Frames with a face
+    pub faces: usize,
+    pub mean_confidence: f32,
+    /// Darkness of frames with a face, percent (see `FrameQuality`)
+    pub darkness: Vec<f32>,
+    /// Height of the smallest face found, in camera pixels
+    pub min_face_height: Option<f32>,
+    /// Why the device could not be tried
+    pub error: Option<String>,
+}
+
+impl Candidate {
+    fn new(info: &DeviceInfo) -> Self {
+        Self {
+            path: info.path.clone(),
+            card: info.card.clone(),
+            looks_ir: info.looks_ir(),
+            frames: 0,
+            faces: 0,
+            mean_confidence: 0.0,
+            darkness: Vec::new(),
+            min_face_height: None,
+            error: None,
+        }
+    }
+
+    /// Share of frames with a face
+    pub fn hit_rate(&self) -> f32 {
+        if self.frames == 0 {
+            0.0
+        } else {
+            self.faces as f32 / self.frames as f32
+        }
+    }
+
+    /// Ranking key: detection rate weighted by confidence, IR breaking ties
+    pub fn score(&self) -> f32 {
+        self.hit_rate() * self.mean_confidence + if self.looks_ir { 0.05 } else { 0.0 }
+    }
+
+    /// `dark_threshold` that accepts every frame with a face seen here
+    pub fn suggested_dark_threshold(&self) -> Option<f32> {
+        let darkest = self.darkness.iter().copied().reduce(f32::max)?;
+        Some((darkest + DARK_MARGIN).clamp(50.0, 95.0).round())
+    }
+
+    /// Largest downscale that keeps the smallest face above `MIN_DETECTION_FACE`,
+    /// in quarter steps
+    pub fn suggested_detection_scale(&self) -> Option<f32> {
+        let height = self.min_face_height?;
+        let scale = (MIN_DETECTION_FACE / height.max(1.0)).clamp(0.25, 1.0);
+        Some((scale * 4.0).ceil() / 4.0)
+    }
+}
+
+/// Capture `frames` frames from a device and run the detector on each
+///
+/// The device is opened with the `[camera]` settings in `base`, so controls
+/// and preprocessing already in the config are part of the trial.
+pub fn trial(info: &DeviceInfo, base: &CameraConfig, detector: &mut dyn FaceDetector, frames: usize) -> Candidate {
+    let mut candidate = Candidate::new(info);
+    let mut config = base.clone();
+    config.device = info.path.to_string_lossy().into_owned();
+    config.device_name = None;
+    config.bus_info = None;
+    config.usb_id = None;
+    config.use_hybrid_mode = false;
+
+    let mut camera = match crate::source::open(&config) {
+        Ok(camera) => camera,
+        Err(e) => {
+            candidate.error = Some(e.to_string());
+            return candidate;
+        }
+    };
+
+    let mut confidence = 0.0;
+    for _ in 0..frames {
+        let frame = match camera.capture_with_quality() {
+            Ok(frame) => frame,
+            Err(CaptureError::EndOfStream) => break,
+            Err(e) => {
+                candidate.error = Some(e.to_string());
+                break;
+            }
+        };
+        candidate.frames += 1;
+
+        let input = preprocess::prepare(&frame.image, &config, Stage::Detection);
+        let Some(face) = detector.detect(&input).ok().and_then(|faces| faces.into_iter().next()) else {
+            continue;
+        };
+
+        candidate.faces += 1;
+        confidence += face.confidence;
+        candidate.darkness.push(frame.quality.darkness_pct);
+        candidate.min_face_height = Some(candidate.min_face_height.map_or(face.bbox.height, |h| h.min(face.bbox.height)));
+    }
+    camera.stop();
+
+    if candidate.faces > 0 {
+        candidate.mean_confidence = confidence / candidate.faces as f32;
+    }
+    candidate
+}
+
+/// Best candidates first
+pub fn rank(candidates: &mut [Candidate]) {
+    candidates.sort_by(|a, b| b.score().total_cmp(&a.score()));
+}
+
+/// First existing copy of a model, checking the configured path and then the
+/// install and source-tree locations
+pub fn find_model(configured: &Path) -> Option<PathBuf> {
+    if configured.exists() {
+        return Some(configured.to_path_buf());
+    }
+    let name = configured.file_name()?;
+    MODEL_DIRS.iter().map(|dir| Path::new(dir).join(name)).find(|path| path.exists())
+}
+
+/// Point both model paths at files that exist, or list the missing ones
+pub fn locate_models(config: &mut Config) -> Result<(), Vec<PathBuf>> {
+    let detection = find_model(&config.detection.model_path);
+    let embedding = find_model(&config.embedding.model_path);
+
+    let mut missing = Vec::new();
+    match detection {
+        Some(path) => config.detection.model_path = absolute(path),
+        None => missing.push(config.detection.model_path.clone()),
+    }
+    match embedding {
+        Some(path) => config.embedding.model_path = absolute(path),
+        None => missing.push(config.embedding.model_path.clone()),
+    }
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(missing)
+    }
+}
+
+/// PAM runs from an arbitrary working directory, so written paths must be absolute
+fn absolute(path: PathBuf) -> PathBuf {
+    std::fs::canonicalize(&path).unwrap_or(path)
+}
+
+/// Stable name for a device: its `/dev/v4l/by-id` link when there is one
+pub fn stable_device(path: &Path) -> String {
+    crate::probe::stable_links(path)
+        .into_iter()
+        .find(|link| link.starts_with("/dev/v4l/by-id"))
+        .unwrap_or_else(|| path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Apply a chosen candidate to the config, naming it `device` (usually
+/// `stable_device` of its path)
+pub fn configure(config: &mut Config, candidate: &Candidate, device: String) {
+    let camera = &mut config.camera;
+    camera.device = device;
+    camera.device_name = None;
+    camera.bus_info = None;
+    camera.usb_id = None;
+
+    // Only ever loosen: the trial saw few frames, the user may have tuned this
+    if let Some(threshold) = candidate.suggested_dark_threshold() {
+        camera.dark_threshold = threshold.max(camera.dark_threshold);
+    }
+    if let Some(scale) = candidate.suggested_detection_scale() {
+        camera.detection_scale = scale;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(faces: usize, confidence: f32, looks_ir: bool) -> Candidate {
+        Candidate {
+            path: PathBuf::from("/dev/video0"),
+            card: "Camera".to_string(),
+            looks_ir,
+            frames: 10,
+            faces,
+            mean_confidence: confidence,
+            darkness: vec![40.0, 62.3],
+            min_face_height: Some(240.0),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_rank() {
+        let mut candidates = vec![candidate(2, 0.9, true), candidate(9, 0.8, false), candidate(9, 0.8, true)];
+        rank(&mut candidates);
+        assert_eq!((candidates[0].faces, candidates[0].looks_ir), (9, true));
+        assert_eq!((candidates[1].faces, candidates[1].looks_ir), (9, false));
+        assert_eq!(candidates[2].faces, 2);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let mut c = candidate(9, 0.8, true);
+        assert_eq!(c.suggested_dark_threshold(), Some(72.0));
+        assert_eq!(c.suggested_detection_scale(), Some(0.5));
+
+        c.min_face_height = Some(90.0);
+        assert_eq!(c.suggested_detection_scale(), Some(1.0));
+
+        c.darkness.clear();
+        c.min_face_height = None;
+        assert_eq!(c.suggested_dark_threshold(), None);
+        assert_eq!(c.suggested_detection_scale(), None);
+    }
+
+    #[test]
+    fn test_written_config_loads_back() {
+        let mut config = Config::default();
+        config.camera.preprocess.apply_clahe = true;
+        configure(&mut config, &candidate(9, 0.8, true), "/dev/video0".to_string());
+
+        let written = toml::to_string_pretty(&config).unwrap();
+        let loaded: Config = toml::from_str(&written).unwrap();
+        assert!(loaded.camera.preprocess.apply_clahe);
+        assert_eq!(loaded.camera.detection_scale, 0.5);
+        assert!(loaded.validate().is_ok());
+    }
+
+    #[test]
+    fn test_configure_keeps_looser_threshold() {
+        let mut config = Config::default();
+        config.camera.dark_threshold = 90.0;
+        config.camera.usb_id = Some("04f2:b6d9".to_string());
+        configure(&mut config, &candidate(9, 0.8, true), "/dev/v4l/by-id/usb-Camera-video-index0".to_string());
+
+        assert_eq!(config.camera.device, "/dev/v4l/by-id/usb-Camera-video-index0");
+        assert_eq!(config.camera.usb_id, None);
+        assert_eq!(config.camera.dark_threshold, 90.0);
+        assert_eq!(config.camera.detection_scale, 0.5);
+        assert!(config.validate().is_ok());
+    }
+}
@@ -0,0 +1,236 @@
+use crate::capture::{self, Camera, CaptureError, FrameSource};
+use crate::config::{CameraConfig, CaptureBackend, RawFormat};
+use crate::fusion::FusionSource;
+use image::DynamicImage;
+use std::fs::{self, File};
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+/// Open the frame source selected by `[camera] backend`, wrapped for
+/// exposure fusion when `force_exposure_fusion` is set
+pub fn open(config: &CameraConfig) -> Result<Box<dyn FrameSource>, CaptureError> {
+    let mut source: Box<dyn FrameSource> = match config.backend {
+        CaptureBackend::V4l2 => Box::new(Camera::new(config)?),
+        CaptureBackend::Image => Box::new(ImageSource::new(config)?),
+        CaptureBackend::Directory => Box::new(DirectorySource::new(config)?),
+        CaptureBackend::Raw => Box::new(RawSource::new(config)?),
+    };
+
+    log::info!("Frame source: {:?} ({})", config.backend, config.device);
+
+    if config.force_exposure_fusion {
+        source = Box::new(FusionSource::new(source, config));
+    }
+    Ok(source)
+}
+
+/// Load a JPEG/PNG file as a gray or RGB frame, as it was saved
+fn load_image(path: &Path) -> Result<DynamicImage, CaptureError> {
+    let img = image::open(path)
+        .map_err(|e| CaptureError::Conversion(format!("{}: {}", path.display(), e)))?;
+    Ok(capture::normalize(img))
+}
+
+/// Replays a single image file as every frame
+pub struct ImageSource {
+    frame: DynamicImage,
+    config: CameraConfig,
+}
+
+impl ImageSource {
+    pub fn new(config: &CameraConfig) -> Result<Self, CaptureError> {
+        let frame = load_image(Path::new(&config.device))
+            .map_err(|e| CaptureError::DeviceOpen(e.to_string()))?;
+
+        log::info!("Image source loaded: {}x{}", frame.width(), frame.height());
+
+        Ok(Self {
+            frame,
+            config: config.clone(),
+        })
+    }
+}
+
+impl FrameSource for ImageSource {
+    fn next_frame(&mut self) -> Result<DynamicImage, CaptureError> {
+        Ok(self.frame.clone())
+    }
+
+    fn config(&self) -> &CameraConfig {
+        &self.config
+    }
+}
+
+/// Replays a directory of JPEG/PNG frames in file name order
+pub struct DirectorySource {
+    frames: Vec<PathBuf>,
+    next: usize,
+    config: CameraConfig,
+}
+
+impl DirectorySource {
+    pub fn new(config: &CameraConfig) -> Result<Self, CaptureError> {
+        let dir = Path::new(&config.device);
+        let entries = fs::read_dir(dir)
+            .map_err(|e| CaptureError::DeviceOpen(format!("{}: {}", dir.display(), e)))?;
+
+        let mut frames = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| CaptureError::DeviceOpen(e.to_string()))?.path();
+            if path.is_file() && Self::is_frame_file(&path) {
+                frames.push(path);
+            }
+        }
+        frames.sort();
+
+        if frames.is_empty() {
+            return Err(CaptureError::DeviceOpen(format!(
+                "{}: no JPEG or PNG frames found",
+                dir.display()
+            )));
+        }
+
+        log::info!("Directory source: {} frames in {}", frames.len(), dir.display());
+
+        Ok(Self {
+            frames,
+            next: 0,
+            config: config.clone(),
+        })
+    }
+
+    fn is_frame_file(path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| matches!(ext.to_ascii_lowercase().as_str(), "jpg" | "jpeg" | "png"))
+            .unwrap_or(false)
+    }
+}
+
+impl FrameSource for DirectorySource {
+    fn next_frame(&mut self) -> Result<DynamicImage, CaptureError> {
+        let path = self.frames.get(self.next).ok_or(CaptureError::EndOfStream)?;
+        self.next += 1;
+
+        log::debug!("Replaying frame {}", path.display());
+        load_image(path)
+    }
+
+    fn config(&self) -> &CameraConfig {
+        &self.config
+    }
+}
+
+/// Reads raw YUYV or GREY frames back-to-back from a file or FIFO
+pub struct RawSource {
+    reader: Box<dyn Read + Send>,
+    buffer: Vec<u8>,
+    config: CameraConfig,
+}
+
+impl RawSource {
+    pub fn new(config: &CameraConfig) -> Result<Self, CaptureError> {
+        // Opening a FIFO blocks until a writer connects
+        let file = File::open(&config.device)
+            .map_err(|e| CaptureError::DeviceOpen(format!("{}: {}", config.device, e)))?;
+
+        Ok(Self::from_reader(Box::new(BufReader::new(file)), config))
+    }
+
+    /// Read frames from any byte stream
+    pub fn from_reader(reader: Box<dyn Read + Send>, config: &CameraConfig) -> Self {
+        let pixels = config.width as usize * config.height as usize;
+        let frame_size = match config.raw_format {
+            RawFormat::Yuyv => pixels * 2,
+            RawFormat::Grey => pixels,
+        };
+
+        Self {
+            reader,
+            buffer: vec![0u8; frame_size],
+            config: config.clone(),
+        }
+    }
+}
+
+impl FrameSource for RawSource {
+    fn next_frame(&mut self) -> Result<DynamicImage, CaptureError> {
+        match self.reader.read_exact(&mut self.buffer) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Err(CaptureError::EndOfStream),
+            Err(e) => return Err(CaptureError::Capture(format!("Failed to read raw frame: {}", e))),
+        }
+
+        let (width, height) = (self.config.width, self.config.height);
+        let yuyv_stride = width as usize * 2;
+        Ok(match self.config.raw_format {
+            RawFormat::Yuyv if self.config.grayscale => {
+                capture::decode_yuyv_luma(&self.buffer, width, height, yuyv_stride)?.into()
+            }
+            RawFormat::Yuyv => capture::decode_yuyv(&self.buffer, width, height, yuyv_stride)?.into(),
+            RawFormat::Grey => capture::decode_grey(&self.buffer, width, height, width as usize)?.into(),
+        })
+    }
+
+    fn config(&self) -> &CameraConfig {
+        &self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::io::Cursor;
+
+    fn raw_config(format: RawFormat) -> CameraConfig {
+        CameraConfig {
+            device: String::new(),
+            width: 4,
+            height: 2,
+            backend: CaptureBackend::Raw,
+            raw_format: format,
+            ..Config::default().camera
+        }
+    }
+
+    #[test]
+    fn test_raw_grey_frames() {
+        let config = raw_config(RawFormat::Grey);
+        let data: Vec<u8> = (0..16).collect();
+        let mut source = RawSource::from_reader(Box::new(Cursor::new(data)), &config);
+
+        let first = source.next_frame().unwrap().into_luma8();
+        assert_eq!(first.dimensions(), (4, 2));
+        assert_eq!(first.get_pixel(3, 1).0, [7]);
+
+        let second = source.next_frame().unwrap().into_luma8();
+        assert_eq!(second.get_pixel(0, 0).0, [8]);
+
+        assert!(matches!(source.next_frame(), Err(CaptureError::EndOfStream)));
+    }
+
+    #[test]
+    fn test_raw_yuyv_partial_frame_ends_stream() {
+        let config = raw_config(RawFormat::Yuyv);
+        // One full 4x2 YUYV frame (16 bytes) followed by a truncated one
+        let data = vec![128u8; 16 + 5];
+        let mut source = RawSource::from_reader(Box::new(Cursor::new(data)), &config);
+
+        let frame = source.next_frame().unwrap();
+        assert_eq!(frame.as_rgb8().unwrap().get_pixel(0, 0).0, [128, 128, 128]);
+        assert!(matches!(source.next_frame(), Err(CaptureError::EndOfStream)));
+
+        let config = CameraConfig { grayscale: true, ..config };
+        let mut source = RawSource::from_reader(Box::new(Cursor::new(vec![128u8; 16])), &config);
+        assert_eq!(source.next_frame().unwrap().as_luma8().unwrap().as_raw(), &vec![128u8; 8]);
+    }
+
+    #[test]
+    fn test_directory_frame_filter() {
+        assert!(DirectorySource::is_frame_file(Path::new("frame_001.jpg")));
+        assert!(DirectorySource::is_frame_file(Path::new("frame_001.PNG")));
+        assert!(!DirectorySource::is_frame_file(Path::new("session.toml")));
+        assert!(!DirectorySource::is_frame_file(Path::new("README")));
+    }
+}
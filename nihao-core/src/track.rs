@@ -0,0 +1,208 @@
+use crate::detect::{BoundingBox, DetectedFace, FacialLandmarks};
+
+/// Least box overlap for a detection to continue a track
+const MIN_IOU: f32 = 0.3;
+
+/// Most the landmarks may move between frames, on average, in eye distances
+const MAX_MOTION: f32 = 0.75;
+
+/// Frames a track survives without a detection before it is dropped
+const MAX_MISSED: u32 = 5;
+
+#[derive(Debug, Clone)]
+struct Track {
+    id: u32,
+    bbox: BoundingBox,
+    landmarks: FacialLandmarks,
+    missed: u32,
+}
+
+/// Links detections across frames so evidence can be tied to one subject
+///
+/// Each face is matched to the track whose last box it overlaps most, as
+/// long as the overlap is at least `MIN_IOU` and its landmarks moved less
+/// than `MAX_MOTION` eye distances. Faces matching no track start a new one.
+/// Frames rejected before detection don't advance the tracker, so a track
+/// only has to bridge the frames that were actually looked at.
+#[derive(Debug, Clone, Default)]
+pub struct FaceTracker {
+    tracks: Vec<Track>,
+    next_id: u32,
+}
+
+impl FaceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Assign track IDs to the faces of the next frame
+    pub fn update(&mut self, faces: &mut [DetectedFace]) {
+        // Every acceptable pairing, best overlap first, taken greedily
+        let mut pairs = Vec::new();
+        for (t, track) in self.tracks.iter().enumerate() {
+            for (f, face) in faces.iter().enumerate() {
+                let iou = track.bbox.iou(&face.bbox);
+                if iou >= MIN_IOU && motion(&track.landmarks, &face.landmarks) <= MAX_MOTION {
+                    pairs.push((iou, t, f));
+                }
+            }
+        }
+        pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut seen = vec![false; self.tracks.len()];
+        let mut assigned = vec![None; faces.len()];
+        for (_, t, f) in pairs {
+            if seen[t] || assigned[f].is_some() {
+                continue;
+            }
+            seen[t] = true;
+            assigned[f] = Some(self.tracks[t].id);
+        }
+
+        for (track, seen) in self.tracks.iter_mut().zip(&seen) {
+            if !seen {
+                track.missed += 1;
+            }
+        }
+        self.tracks.retain(|track| track.missed <= MAX_MISSED);
+
+        for (face, id) in faces.iter_mut().zip(assigned) {
+            face.track = id;
+            match id.and_then(|id| self.tracks.iter_mut().find(|track| track.id == id)) {
+                Some(track) => {
+                    track.bbox = face.bbox.clone();
+                    track.landmarks = face.landmarks.clone();
+                    track.missed = 0;
+                }
+                None => {
+                    let id = self.next_id;
+                    self.next_id += 1;
+                    log::debug!("New face track {}", id);
+                    face.track = Some(id);
+                    self.tracks.push(Track {
+                        id,
+                        bbox: face.bbox.clone(),
+                        landmarks: face.landmarks.clone(),
+                        missed: 0,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Mean landmark displacement, in eye distances of the earlier face
+fn motion(from: &FacialLandmarks, to: &FacialLandmarks) -> f32 {
+    let shift: f32 = from
+        .points()
+        .iter()
+        .zip(to.points())
+        .map(|(a, b)| (b.0 - a.0).hypot(b.1 - a.1))
+        .sum();
+    shift / 5.0 / from.eye_distance().max(1.0)
+}
+
+/// Counts matching frames toward a decision, all from one track
+///
+/// A match from another track, or from a face with no track, starts the
+/// count over, so `required` frames can never be pieced together from
+/// different people.
+#[derive(Debug, Clone)]
+pub struct Evidence {
+    required: u32,
+    track: Option<u32>,
+    count: u32,
+}
+
+impl Evidence {
+    pub fn new(required: u32) -> Self {
+        Self {
+            required: required.max(1),
+            track: None,
+            count: 0,
+        }
+    }
+
+    /// Record a matching frame from `track`; true once enough have come in
+    pub fn add(&mut self, track: Option<u32>) -> bool {
+        if track.is_none() || track != self.track {
+            self.track = track;
+            self.count = 0;
+        }
+        self.count += 1;
+        self.count >= self.required
+    }
+
+    /// Matching frames so far from the current track
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn face(x: f32, y: f32) -> DetectedFace {
+        let bbox = BoundingBox { x, y, width: 100.0, height: 120.0 };
+        DetectedFace { landmarks: FacialLandmarks::estimate(&bbox), bbox, confidence: 0.9, track: None }
+    }
+
+    fn ids(faces: &[DetectedFace]) -> Vec<u32> {
+        faces.iter().map(|face| face.track.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_tracks_follow_faces() {
+        let mut tracker = FaceTracker::new();
+        let mut first = [face(100.0, 100.0), face(400.0, 100.0)];
+        tracker.update(&mut first);
+        assert_eq!(ids(&first), vec![0, 1]);
+
+        // Both moved a little, listed in the other order, plus a newcomer
+        let mut second = [face(410.0, 105.0), face(300.0, 300.0), face(95.0, 100.0)];
+        tracker.update(&mut second);
+        assert_eq!(ids(&second), vec![1, 2, 0]);
+
+        // A jump of most of a face width is someone else
+        let mut third = [face(160.0, 100.0)];
+        tracker.update(&mut third);
+        assert_eq!(ids(&third), vec![3]);
+    }
+
+    #[test]
+    fn test_missed_frames() {
+        let mut tracker = FaceTracker::new();
+        tracker.update(&mut [face(100.0, 100.0)]);
+        for _ in 0..MAX_MISSED {
+            tracker.update(&mut []);
+        }
+        let mut back = [face(100.0, 100.0)];
+        tracker.update(&mut back);
+        assert_eq!(ids(&back), vec![0]);
+
+        for _ in 0..=MAX_MISSED {
+            tracker.update(&mut []);
+        }
+        let mut gone = [face(100.0, 100.0)];
+        tracker.update(&mut gone);
+        assert_eq!(ids(&gone), vec![1]);
+    }
+
+    #[test]
+    fn test_evidence_from_one_track() {
+        let mut evidence = Evidence::new(3);
+        assert!(!evidence.add(Some(0)));
+        assert!(!evidence.add(Some(0)));
+        // Another subject starts over
+        assert!(!evidence.add(Some(1)));
+        assert_eq!(evidence.count(), 1);
+        assert!(!evidence.add(Some(1)));
+        assert!(evidence.add(Some(1)));
+
+        assert!(Evidence::new(0).add(None));
+        let mut untracked = Evidence::new(2);
+        assert!(!untracked.add(None));
+        assert!(!untracked.add(None));
+    }
+}
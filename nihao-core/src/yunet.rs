@@ -0,0 +1,190 @@
+use crate::config::{DetectionConfig, DetectionRanking};
+use crate::detect::{self, Decoded, DetectedFace, DetectionError, FaceDetector, FacialLandmarks, Outlet, BGR};
+use image::DynamicImage;
+use ort::session::Session;
+use ort::value::Value;
+
+/// YuNet predicts at three strides, one prediction per location
+const STRIDES: [usize; 3] = [8, 16, 32];
+
+/// Output heads, named `<head>_<stride>` in the OpenCV zoo exports
+const HEADS: [&str; 4] = ["cls", "obj", "bbox", "kps"];
+
+/// Values per location in each head
+const HEAD_WIDTHS: [i64; 4] = [1, 1, 4, 10];
+
+/// YuNet detector from the OpenCV model zoo (`face_detection_yunet_2023mar.onnx`)
+///
+/// Anchor-free: every feature map location predicts a box center offset,
+/// log-scaled size and five landmarks, with separate class and objectness
+/// scores. The model takes raw BGR pixel values.
+pub struct YuNetDetector {
+    session: Session,
+    input_name: String,
+    input_size: (u32, u32),
+    /// Output index of each head, per stride
+    heads: Vec<[usize; 4]>,
+    confidence_threshold: f32,
+    letterbox: bool,
+    nms_threshold: f32,
+    ranking: DetectionRanking,
+}
+
+impl YuNetDetector {
+    /// Wrap a loaded YuNet model, checking its layout
+    pub fn new(session: Session, config: &DetectionConfig) -> Result<Self, DetectionError> {
+        let (inputs, outputs) = detect::outlets(&session);
+        let (input_name, input_size) = detect::image_input(&inputs)?;
+        let heads = Self::find_heads(&outputs)?;
+
+        Ok(Self {
+            session,
+            input_name,
+            input_size: detect::input_size(input_size, config),
+            heads,
+            confidence_threshold: config.confidence_threshold,
+            letterbox: config.letterbox,
+            nms_threshold: config.nms_threshold,
+            ranking: config.ranking,
+        })
+    }
+
+    /// Output index of each head at each stride, by name
+    fn find_heads(outputs: &[Outlet]) -> Result<Vec<[usize; 4]>, DetectionError> {
+        STRIDES
+            .iter()
+            .map(|stride| {
+                let mut indices = [0; 4];
+                for (i, head) in HEADS.iter().enumerate() {
+                    let name = format!("{}_{}", head, stride);
+                    let (index, (_, shape)) = outputs
+                        .iter()
+                        .enumerate()
+                        .find(|(_, (output, _))| *output == name)
+                        .ok_or_else(|| DetectionError::UnsupportedModel(format!("no {:?} output; not a YuNet model", name)))?;
+                    if shape.last() != Some(&HEAD_WIDTHS[i]) {
+                        return Err(DetectionError::UnsupportedModel(format!(
+                            "output {:?} has shape {:?}, expected {} values per location",
+                            name, shape, HEAD_WIDTHS[i]
+                        )));
+                    }
+                    indices[i] = index;
+                }
+                Ok(indices)
+            })
+            .collect()
+    }
+
+    /// Decode one location
+    fn decode(col: usize, row: usize, stride: f32, bbox: &[f32], kps: &[f32]) -> Decoded {
+        let (c, r) = (col as f32, row as f32);
+        let cx = (c + bbox[0]) * stride;
+        let cy = (r + bbox[1]) * stride;
+        let w = bbox[2].exp() * stride;
+        let h = bbox[3].exp() * stride;
+
+        // YuNet names points from the subject's side: its "right eye" is the
+        // one on the left of the image, which is our `left_eye`
+        let point = |i: usize| ((kps[i * 2] + c) * stride, (kps[i * 2 + 1] + r) * stride);
+        ((cx - w / 2.0, cy - h / 2.0, w, h), [point(0), point(1), point(2), point(3), point(4)])
+    }
+}
+
+impl FaceDetector for YuNetDetector {
+    fn detect(&mut self, image: &DynamicImage) -> Result<Vec<DetectedFace>, DetectionError> {
+        // Raw BGR values, as OpenCV's blobFromImage hands them over
+        let (input_width, input_height) = self.input_size;
+        let (input_tensor, mapping) =
+            detect::input_tensor(image, self.input_size, self.letterbox, BGR, |value, _| value as f32);
+
+        let input_value = Value::from_array(input_tensor)
+            .map_err(|e| DetectionError::Inference(format!("Failed to create input tensor: {}", e)))?;
+
+        let outputs = self
+            .session
+            .run(ort::inputs![self.input_name.as_str() => input_value])
+            .map_err(|e| DetectionError::Inference(e.to_string()))?;
+
+        let mut detections = Vec::new();
+        for (&stride, heads) in STRIDES.iter().zip(&self.heads) {
+            let cols = detect::feature_size(input_width, stride);
+            let rows = detect::feature_size(input_height, stride);
+
+            let cls = detect::tensor(&outputs[heads[0]], "class scores")?;
+            let obj = detect::tensor(&outputs[heads[1]], "objectness")?;
+            let bbox = detect::tensor(&outputs[heads[2]], "bboxes")?;
+            let kps = detect::tensor(&outputs[heads[3]], "landmarks")?;
+
+            let count = cols * rows;
+            if cls.len() < count || obj.len() < count || bbox.len() < count * 4 || kps.len() < count * 10 {
+                return Err(DetectionError::Inference(format!(
+                    "Stride {} outputs are smaller than its {} locations",
+                    stride, count
+                )));
+            }
+
+            for idx in 0..count {
+                let score = (cls[idx].clamp(0.0, 1.0) * obj[idx].clamp(0.0, 1.0)).sqrt();
+                if score < self.confidence_threshold {
+                    continue;
+                }
+
+                let (decoded, points) = Self::decode(
+                    idx % cols,
+                    idx / cols,
+                    stride as f32,
+                    &bbox[idx * 4..idx * 4 + 4],
+                    &kps[idx * 10..idx * 10 + 10],
+                );
+                detections.push(DetectedFace {
+                    bbox: mapping.unmap_box(decoded),
+                    landmarks: FacialLandmarks::from_points(points).map(|point| mapping.unmap(point)),
+                    confidence: score,
+                    track: None,
+                });
+            }
+        }
+
+        detect::finish(detections, self.nms_threshold, self.ranking)
+    }
+
+    fn set_confidence_threshold(&mut self, threshold: f32) {
+        self.confidence_threshold = threshold;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yunet_outputs() -> Vec<Outlet> {
+        let mut outputs = Vec::new();
+        for (head, width) in HEADS.iter().zip(HEAD_WIDTHS) {
+            for (stride, count) in STRIDES.iter().zip([6400, 1600, 400]) {
+                outputs.push((format!("{}_{}", head, stride), vec![1, count, width]));
+            }
+        }
+        outputs
+    }
+
+    #[test]
+    fn test_find_heads() {
+        let heads = YuNetDetector::find_heads(&yunet_outputs()).unwrap();
+        // cls_16, obj_16, bbox_16, kps_16
+        assert_eq!(heads[1], [1, 4, 7, 10]);
+
+        let mut outputs = yunet_outputs();
+        outputs.retain(|(name, _)| name != "obj_32");
+        assert!(matches!(YuNetDetector::find_heads(&outputs), Err(DetectionError::UnsupportedModel(_))));
+    }
+
+    #[test]
+    fn test_decode() {
+        // Centered in cell (2, 1) at stride 8, 16x32 box
+        let kps = [0.0, 0.0, 1.0, 0.0, 0.5, 0.5, 0.0, 1.0, 1.0, 1.0];
+        let (bbox, points) = YuNetDetector::decode(2, 1, 8.0, &[0.5, 0.5, 2f32.ln(), 4f32.ln()], &kps);
+        assert_eq!(bbox, (12.0, -4.0, 16.0, 32.0));
+        assert_eq!(points[0], (16.0, 8.0));
+        assert_eq!(points[4], (24.0, 16.0));
+    }
+}
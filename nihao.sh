@@ -32,6 +32,10 @@ usage() {
     echo -e "  ${GREEN}remove${NC} [username] [id]    Remove enrolled face(s) (defaults to \$USER)"
     echo -e "  ${GREEN}list${NC} [username]           List enrolled faces (defaults to \$USER)"
     echo -e "  ${GREEN}snapshot${NC} <output.jpg>     Capture camera snapshot"
+    echo -e "  ${GREEN}setup${NC}                     Pick the camera and write /etc/nihao/nihao.toml"
+    echo -e "  ${GREEN}devices${NC} [--json]          List cameras, formats and IR likelihood"
+    echo -e "  ${GREEN}controls${NC} [device]         List camera controls (exposure, gain, ...)"
+    echo -e "  ${GREEN}calibrate-hybrid${NC}          Measure RGB→IR mapping for hybrid mode"
     echo -e "  ${GREEN}config${NC}                    Show configuration"
     echo ""
     echo "Options:"
@@ -46,6 +50,7 @@ usage() {
     echo "  $0 remove face_0            # Remove your face"
     echo "  $0 add johnny \"Johnny's Face\" # Enroll specific user"
     echo "  $0 test johnny              # Test specific user"
+    echo "  $0 test johnny --record s.tar # Record the attempt for a bug report"
     echo "  $0 list johnny              # List faces for specific user"
     echo "  $0 snapshot /tmp/test.jpg   # Capture snapshot"
     echo "  $0 -v test                  # Test with debug logging"
@@ -116,14 +121,15 @@ case $COMMAND in
         ;;
 
     test)
-        # Default to $USER if no username provided
-        if [ $# -lt 1 ]; then
+        # Default to $USER if no username provided; options (--record, ...) pass through
+        if [ $# -lt 1 ] || [[ "$1" == -* ]]; then
             USERNAME="$CURRENT_USER"
         else
             USERNAME="$1"
+            shift
         fi
         echo -e "${BLUE}Testing face recognition for user: ${GREEN}$USERNAME${NC}"
-        cargo run --release --bin nihao -- test "$USERNAME"
+        cargo run --release --bin nihao -- test "$USERNAME" "$@"
         ;;
 
     remove)
@@ -177,6 +183,26 @@ case $COMMAND in
         cargo run --release --bin nihao -- snapshot "$OUTPUT"
         ;;
 
+    setup)
+        echo -e "${BLUE}Running setup wizard${NC}"
+        cargo build --release --bin nihao
+        sudo ./target/release/nihao setup "$@"
+        ;;
+
+    devices)
+        cargo run --release --bin nihao -- devices "$@"
+        ;;
+
+    controls)
+        echo -e "${BLUE}Listing camera controls${NC}"
+        cargo run --release --bin nihao -- controls "$@"
+        ;;
+
+    calibrate-hybrid)
+        echo -e "${BLUE}Calibrating hybrid RGB/IR mode${NC}"
+        cargo run --release --bin nihao -- calibrate-hybrid "$@"
+        ;;
+
     config)
         echo -e "${BLUE}Showing configuration${NC}"
         cargo run --release --bin nihao -- config --validate
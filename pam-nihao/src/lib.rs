@@ -114,8 +114,8 @@ fn authenticate_impl(pamh: &Pam) -> Result<(), String> {
     }
 
     match auth_result {
-        Ok(true) => {
-            log::info!("NiHao: Face recognized for user: {}", user);
+        Ok(outcome) if outcome.matched => {
+            log::info!("NiHao: Face recognized for user: {} ({})", user, outcome);
 
             // Try to set PAM_AUTHTOK for automatic service unlock (KWallet, GNOME Keyring, etc.)
             let password_store = PasswordStore::new("/etc/nihao");
@@ -152,8 +152,12 @@ fn authenticate_impl(pamh: &Pam) -> Result<(), String> {
 
             Ok(())
         }
-        Ok(false) => {
-            log::info!("NiHao: Face not recognized for user: {}", user);
+        Ok(outcome) if outcome.multiple_faces => {
+            log::warn!("NiHao: Refused for user {}: {} faces in frame", user, outcome.faces_seen);
+            Err("Multiple faces in frame".to_string())
+        }
+        Ok(outcome) => {
+            log::info!("NiHao: Face not recognized for user: {} ({})", user, outcome);
             Err("Face not recognized".to_string())
         }
         Err(e) => {
@@ -167,8 +171,6 @@ pamsm::pam_module!(PamNihao);
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-
     #[test]
     fn test_module_compiles() {
         // Just verify the module compiles